@@ -0,0 +1,17 @@
+//! `cargo bench`: every bundled workload on Starlark and python3, reported
+//! through [`starlark_bench::harness`].
+
+use std::process::ExitCode;
+
+use starlark_bench::{EngineConfig, Runner, WorkloadSpec, harness};
+
+fn main() -> ExitCode {
+    let runner = Runner::new()
+        .engine(EngineConfig::Starlark)
+        .engine(EngineConfig::python("python3"));
+    harness::main(
+        WorkloadSpec::all()
+            .into_iter()
+            .fold(runner, Runner::workload),
+    )
+}
@@ -0,0 +1,19 @@
+//! Exposes the version of the starlark crate the bench is built against as
+//! STARLARK_VERSION, read from Cargo.lock ("unknown" without one).
+
+use std::path::Path;
+
+fn main() {
+    let lock = Path::new(&std::env::var("CARGO_MANIFEST_DIR").unwrap()).join("Cargo.lock");
+    println!("cargo:rerun-if-changed={}", lock.display());
+    let version = std::fs::read_to_string(&lock)
+        .ok()
+        .and_then(|text| {
+            let mut lines = text.lines();
+            lines.find(|line| *line == r#"name = "starlark""#)?;
+            let version = lines.next()?.strip_prefix("version = ")?;
+            Some(version.trim_matches('"').to_string())
+        })
+        .unwrap_or_else(|| "unknown".to_string());
+    println!("cargo:rustc-env=STARLARK_VERSION={version}");
+}
@@ -68,8 +68,9 @@ def main():
         print("Usage: make run-all | python3 scripts/plot.py [-o chart.png]", file=sys.stderr)
         sys.exit(1)
 
-    # --- filter: only measurement runs (warmup=false) ---
-    records = [r for r in records if not r.get("warmup", False)]
+    # --- filter: only per-iteration measurement runs (warmup=false) ---
+    # Summary and other derived records carry a "kind" tag; skip them.
+    records = [r for r in records if "kind" not in r and not r.get("warmup", False)]
     if not records:
         print("No measurement records found (all warmup?).", file=sys.stderr)
         sys.exit(1)
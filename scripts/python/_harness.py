@@ -2,55 +2,581 @@
 
 Usage in each workload script:
     from _harness import bench_main
-    def run(n, seed):
+    def run(n, seed):   # or run(n, seed, **params) with --param
         ...
         return checksum
     bench_main(run)
 
 The script is invoked as:
-    python3 <script>.py <N> <SEED> <ITERS>
+    python3 <script>.py <N> <SEED> <ITERS> [PARAMS [stdin]]
+
+PARAMS, given only with --param or --input, is a JSON object of extra keyword
+arguments for run(). With "stdin", the --input value is read from stdin as
+JSON, decoded before every iteration and passed to run() as `data`.
 
 It prints a single JSON line:
-    {"timings_ns": [...], "result": <int>, "rss_kb": <int>}
+    {"timings_ns": [...], "result": <int>, "rss_kb": <int>, "version": <str>,
+     "interpreter": {"implementation": <str>, "version": <str>, "gil_disabled": <bool>},
+     "started_unix_ns": <int>, "import_ns": <int>}
+with --input also "marshal_ns": [...], the per-iteration decoding times.
+"started_unix_ns" is the wall-clock time this module started loading, which
+the workload scripts do first, so the bench can tell interpreter startup
+from the rest; "import_ns" is the time from then until bench_main() was
+called: the imports and top level of the harness and the script.
+With BENCH_PYTHON_NO_GC=1 in the environment (--python-no-gc), the cyclic
+garbage collector is disabled before the first iteration.
+With BENCH_PYTHON_TRACEMALLOC=1 (--mem tracemalloc), tracemalloc traces
+every allocation and the output also has "peak_alloc_bytes": [...], each
+iteration's traced peak above what was allocated when its call began.
+"result" is checksum() of what run() returned. Where /proc/self/statm exists
+(Linux), the output also has "current_rss_kb": [...], the resident set size
+right after each iteration.
+With BENCH_PYTHON_COLD=1 (--python-cold), every iteration compiles the
+script's source again, executes its top level in a fresh module and calls
+that module's run(); the output then also has "compile_ns": [...],
+"module_eval_ns": [...] and "call_ns": [...], and "timings_ns" holds their
+sums. Modules the script imports stay imported.
+With BENCH_PYTHON_THREADS=K (--python-threads), every iteration calls run()
+from K threads at once, released together by a barrier: "timings_ns" then
+spans from the first call's start to the last call's end, and the output
+also has "thread_eval_ns": [[...], ...], each call's own time per
+iteration. The threads must return the same value. A script that splits
+its work over threads passes bench_main(run, run_parallel=run_parallel):
+the threads then call run_parallel(n, seed, K) instead, each doing its
+share of n, and the output also has "parallel": true.
+"cpu_ns": [...] is the process CPU time (time.process_time_ns()) of each
+iteration's run() call, or calls with several threads.
+With BENCH_PYTHON_MIN_SAMPLE_NS=NS (--min-sample-ns), one untimed call
+before the first iteration picks how many back-to-back calls each timed
+iteration makes, enough for it to last NS when a single call is faster;
+the output has "calls_per_sample": <int>, and "timings_ns" and "cpu_ns"
+cover all of an iteration's calls.
+With BENCH_PYTHON_EVICT_BYTES=BYTES (--cache-state cold), one byte of every
+64 of a BYTES-long buffer is written before each iteration, untimed, to
+push the workload's data out of the CPU caches.
+With BENCH_PYTHON_CLOCK=cpu or thread (--clock), "timings_ns" is the
+iteration's process CPU time (time.process_time_ns()) or the calling
+thread's (time.thread_time_ns()) instead of its elapsed time. With
+BENCH_PYTHON_RECORD_CLOCKS=1 (--record-clocks), the output also has
+"clocks": [{"wall_ns": <int>, "cpu_ns": <int>, "thread_ns": <int>}, ...],
+each iteration on every clock.
+"gc_ns": [...] and "gc_collections": [...] are the time each iteration
+spent in the cyclic garbage collector and the collections it ran, from a
+gc.callbacks entry installed before the first iteration; the time is
+elapsed time (time.perf_counter_ns()) and is part of "timings_ns".
+
+With BENCH_PYTHON_WORKER=1 (--python-worker), ITERS is ignored and the
+script instead serves iterations over stdin and stdout, each message a
+4-byte big-endian length followed by that many bytes of JSON. It first
+sends
+    {"version": <str>, "interpreter": {...}, "started_unix_ns": <int>,
+     "import_ns": <int>}
+and then answers each {"op": "iter"} with one iteration,
+    {"eval_ns": <int>, "result": <int>, "rss_kb": <int>}
+with "cpu_ns", "marshal_ns", "peak_alloc_bytes", "current_rss_kb",
+"compile_ns", "module_eval_ns", "call_ns", "thread_eval_ns" (one
+iteration's list), "clocks" (one iteration's object), "gc_ns",
+"gc_collections", "calls_per_sample" and "parallel" as above. {"op": "input", "text":
+<str>} sets the --input JSON for the iterations that follow, and
+{"op": "exit"} or the end of stdin stops it.
 """
 
-import json
-import sys
 import time
 
+_STARTED_UNIX_NS = time.time_ns()
+_STARTED_NS = time.perf_counter_ns()
+
+import functools  # noqa: E402
+import gc  # noqa: E402
+import json  # noqa: E402
+import os  # noqa: E402
+import struct  # noqa: E402
+import sys  # noqa: E402
+import sysconfig  # noqa: E402
+import threading  # noqa: E402
+import tracemalloc  # noqa: E402
+import types  # noqa: E402
+
+MASK = (1 << 64) - 1
+FNV_OFFSET = 0xCBF29CE484222325
+FNV_PRIME = 0x100000001B3
+CHECKSUM_MULTIPLIER = 1000003
+
+
+def _signed(x):
+    """x modulo 2^64, as a signed 64-bit integer."""
+    x &= MASK
+    return x - (1 << 64) if x >= 1 << 63 else x
+
+
+def _fnv1a(data):
+    h = FNV_OFFSET
+    for b in data:
+        h = ((h ^ b) * FNV_PRIME) & MASK
+    return _signed(h)
+
+
+def checksum(value):
+    """Reduce run()'s return value to a signed 64-bit checksum, by the same
+    rules as the Starlark engine's extract_i64: None is 0, bools and ints are
+    themselves (wrapped to 64 bits), floats and strings are FNV-1a hashes of
+    their little-endian IEEE 754 and UTF-8 bytes, and lists and tuples fold
+    their elements' checksums as acc * 1000003 + element, wrapped to 64 bits
+    at every step."""
+    if value is None:
+        return 0
+    if isinstance(value, int):  # includes bool
+        return _signed(value)
+    if isinstance(value, float):
+        return _fnv1a(struct.pack("<d", value))
+    if isinstance(value, str):
+        return _fnv1a(value.encode("utf-8"))
+    if isinstance(value, (list, tuple)):
+        acc = 0
+        for item in value:
+            acc = _signed(acc * CHECKSUM_MULTIPLIER + checksum(item))
+        return acc
+    raise TypeError(f"workload returned unexpected type: {type(value).__name__}")
+
+
+def interpreter():
+    """Describe the interpreter: its implementation, the language version it
+    implements (e.g. "3.13.0" or "3.14.0rc1") and whether it is a
+    free-threaded build."""
+    v = sys.version_info
+    version = f"{v.major}.{v.minor}.{v.micro}"
+    if v.releaselevel != "final":
+        level = {"alpha": "a", "beta": "b", "candidate": "rc"}[v.releaselevel]
+        version += f"{level}{v.serial}"
+    return {
+        "implementation": sys.implementation.name,
+        "version": version,
+        "gil_disabled": bool(sysconfig.get_config_var("Py_GIL_DISABLED")),
+    }
+
+
+class _GcTimer:
+    """A gc.callbacks entry adding up the collector's pauses."""
+
+    def __init__(self):
+        self.ns = 0
+        self.collections = 0
+        self._start = None
+
+    def __call__(self, phase, info):
+        if phase == "start":
+            self._start = time.perf_counter_ns()
+        elif self._start is not None:
+            self.ns += time.perf_counter_ns() - self._start
+            self.collections += 1
+            self._start = None
+
+
+_gc_timer = _GcTimer()
+
+
+# Set while a cold iteration executes the script's top level, to collect the
+# run() it passes to bench_main() instead of starting another benchmark.
+_captured = None
+
+
+def _cold_run(source, path, timings):
+    """Compile `source` and execute it as a fresh module, recording
+    "compile_ns" and "module_eval_ns"; returns the module's run()."""
+    global _captured
+    start = time.perf_counter_ns()
+    code = compile(source, path, "exec")
+    compiled = time.perf_counter_ns()
+    module = types.ModuleType("__bench_cold__")
+    module.__file__ = path
+    _captured = []
+    try:
+        exec(code, module.__dict__)
+        timings["module_eval_ns"] = time.perf_counter_ns() - compiled
+        timings["compile_ns"] = compiled - start
+        if not _captured:
+            sys.exit(f"{path} did not call bench_main()")
+        return _captured[0]
+    finally:
+        _captured = None
+
+
+def _call_threads(workload_fn, n, seed, params, threads):
+    """Call run() from `threads` threads at once. Returns the value, the
+    time from the first call's start to the last call's end, and each
+    call's own time."""
+    barrier = threading.Barrier(threads)
+    calls = [None] * threads
+
+    def call(i):
+        barrier.wait()
+        start = time.perf_counter_ns()
+        try:
+            r = workload_fn(n, seed, **params)
+        except BaseException as e:
+            calls[i] = e
+            return
+        calls[i] = (start, time.perf_counter_ns(), r)
+
+    workers = [threading.Thread(target=call, args=(i,)) for i in range(threads)]
+    for t in workers:
+        t.start()
+    for t in workers:
+        t.join()
+    for c in calls:
+        if isinstance(c, BaseException):
+            raise c
+    results = {checksum(r) for _, _, r in calls}
+    if len(results) > 1:
+        sys.exit(f"ERROR: threads computed different results: {sorted(results)}")
+    span = max(end for _, end, _ in calls) - min(start for start, _, _ in calls)
+    return calls[0][2], span, [end - start for start, end, _ in calls]
+
+
+def _share(run_parallel, threads, n, seed, **params):
+    """One of `threads` concurrent calls of run_parallel()."""
+    return run_parallel(n, seed, threads, **params)
+
+
+def _iteration(
+    workload_fn,
+    n,
+    seed,
+    params,
+    input_text,
+    traced,
+    cold,
+    threads,
+    calls=1,
+    clock="wall",
+    record_clocks=False,
+):
+    """Run one iteration. Returns run()'s value and its timings: "eval_ns",
+    on the `clock` clock, "cpu_ns", and as they apply "marshal_ns",
+    "peak_alloc_bytes", with `cold`, the script's (source, path),
+    "compile_ns", "module_eval_ns" and "call_ns", with `threads` above 1,
+    "thread_eval_ns", with `record_clocks`, "clocks", and once the gc
+    timer is installed, "gc_ns" and "gc_collections". A single thread
+    calls run() `calls` times back to back, timed together."""
+    timings = {}
+    if input_text is not None:
+        start = time.perf_counter_ns()
+        params["data"] = json.loads(input_text)
+        timings["marshal_ns"] = time.perf_counter_ns() - start
+    if traced:
+        tracemalloc.reset_peak()
+        base = tracemalloc.get_traced_memory()[0]
+    gc_start = (_gc_timer.ns, _gc_timer.collections)
+    if cold is not None:
+        workload_fn = _cold_run(*cold, timings)
+    cpu_start = time.process_time_ns()
+    thread_start = time.thread_time_ns()
+    if threads > 1:
+        r, timings["eval_ns"], timings["thread_eval_ns"] = _call_threads(
+            workload_fn, n, seed, params, threads
+        )
+    else:
+        start = time.perf_counter_ns()
+        for _ in range(calls):
+            r = workload_fn(n, seed, **params)
+        timings["eval_ns"] = time.perf_counter_ns() - start
+    timings["cpu_ns"] = time.process_time_ns() - cpu_start
+    thread_ns = time.thread_time_ns() - thread_start
+    if record_clocks:
+        timings["clocks"] = {
+            "wall_ns": timings["eval_ns"],
+            "cpu_ns": timings["cpu_ns"],
+            "thread_ns": thread_ns,
+        }
+    if clock == "cpu":
+        timings["eval_ns"] = timings["cpu_ns"]
+    elif clock == "thread":
+        timings["eval_ns"] = thread_ns
+    if traced:
+        timings["peak_alloc_bytes"] = tracemalloc.get_traced_memory()[1] - base
+    if _gc_timer in gc.callbacks:
+        timings["gc_ns"] = _gc_timer.ns - gc_start[0]
+        timings["gc_collections"] = _gc_timer.collections - gc_start[1]
+    if cold is not None:
+        timings["call_ns"] = timings["eval_ns"]
+        timings["eval_ns"] += timings["compile_ns"] + timings["module_eval_ns"]
+    return r, timings
 
-def bench_main(workload_fn):
+
+def _calls_per_sample(workload_fn, n, seed, params, input_text, min_ns):
+    """How many calls of run() one timed iteration makes to last at least
+    `min_ns`, from the time of one untimed call."""
+    _, timings = _iteration(workload_fn, n, seed, params, input_text, False, None, 1)
+    call_ns = max(timings["eval_ns"], 1)
+    return 1 if call_ns >= min_ns else -(-min_ns // call_ns)
+
+
+def _evictor(size):
+    """A function that writes one byte per cache line of a `size`-byte
+    buffer, or None for a `size` of 0."""
+    if not size:
+        return None
+    buf = bytearray(size)
+    rounds = [bytes([i]) * len(buf[::64]) for i in (1, 2)]
+
+    def evict():
+        rounds.reverse()
+        buf[::64] = rounds[0]
+
+    return evict
+
+
+def _check_result(i, expected, r):
+    if r != expected:
+        print(
+            f"ERROR: result mismatch at iter {i}: expected {expected}, got {r}",
+            file=sys.stderr,
+        )
+        sys.exit(1)
+
+
+def _max_rss_kb():
+    """Best-effort RSS (KiB on Linux, bytes/1024 on macOS)."""
+    try:
+        import resource
+    except ImportError:
+        return 0  # Windows
+    rss_kb = resource.getrusage(resource.RUSAGE_SELF).ru_maxrss
+    if sys.platform == "darwin":
+        rss_kb //= 1024  # macOS reports bytes
+    return rss_kb
+
+
+def _current_rss_kb():
+    """Resident set size now, in KiB; None without /proc/self/statm."""
+    try:
+        with open("/proc/self/statm") as f:
+            resident = int(f.read().split()[1])
+        return resident * os.sysconf("SC_PAGE_SIZE") // 1024
+    except (OSError, ValueError, IndexError, AttributeError):
+        return None
+
+
+def _send(out, message):
+    data = json.dumps(message).encode("utf-8")
+    out.write(struct.pack(">I", len(data)) + data)
+    out.flush()
+
+
+def _receive(inp):
+    header = inp.read(4)
+    if len(header) < 4:
+        return None
+    (length,) = struct.unpack(">I", header)
+    return json.loads(inp.read(length))
+
+
+def _worker(
+    workload_fn,
+    n,
+    seed,
+    params,
+    import_ns,
+    traced,
+    cold,
+    threads,
+    min_sample_ns,
+    evict,
+    parallel,
+    clock,
+    record_clocks,
+):
+    """Serve iterations one request at a time (--python-worker), see the
+    module docstring. Anything the workload prints goes to stderr."""
+    inp, out = sys.stdin.buffer, sys.stdout.buffer
+    sys.stdout = sys.stderr
+    _send(
+        out,
+        {
+            "version": sys.version,
+            "interpreter": interpreter(),
+            "started_unix_ns": _STARTED_UNIX_NS,
+            "import_ns": import_ns,
+        },
+    )
+    input_text = None
+    result = None
+    calls = None
+    i = 0
+    while True:
+        request = _receive(inp)
+        if request is None or request["op"] == "exit":
+            return
+        if request["op"] == "input":
+            input_text = request["text"]
+            continue
+        if calls is None:
+            calls = 1
+            if min_sample_ns is not None:
+                calls = _calls_per_sample(
+                    workload_fn, n, seed, params, input_text, min_sample_ns
+                )
+        if evict is not None:
+            evict()
+        r, reply = _iteration(
+            workload_fn,
+            n,
+            seed,
+            params,
+            input_text,
+            traced,
+            cold,
+            threads,
+            calls,
+            clock,
+            record_clocks,
+        )
+        if i == 0:
+            result = r
+        _check_result(i, result, r)
+        reply["result"] = checksum(r)
+        if min_sample_ns is not None:
+            reply["calls_per_sample"] = calls
+        if parallel:
+            reply["parallel"] = True
+        reply["rss_kb"] = _max_rss_kb()
+        current_rss_kb = _current_rss_kb()
+        if current_rss_kb is not None:
+            reply["current_rss_kb"] = current_rss_kb
+        _send(out, reply)
+        i += 1
+
+
+def bench_main(workload_fn, run_parallel=None):
+    if _captured is not None:
+        _captured.append(workload_fn)
+        return
+    import_ns = time.perf_counter_ns() - _STARTED_NS
     n = int(sys.argv[1])
     seed = int(sys.argv[2])
     iters = int(sys.argv[3])
+    params = json.loads(sys.argv[4]) if len(sys.argv) > 4 else {}
+    input_text = sys.stdin.read() if len(sys.argv) > 5 and sys.argv[5] == "stdin" else None
+
+    if os.environ.get("BENCH_PYTHON_NO_GC") == "1":
+        gc.disable()
+    gc.callbacks.append(_gc_timer)
+    traced = os.environ.get("BENCH_PYTHON_TRACEMALLOC") == "1"
+    if traced:
+        tracemalloc.start()
+    cold = None
+    if os.environ.get("BENCH_PYTHON_COLD") == "1":
+        path = workload_fn.__code__.co_filename
+        with open(path, encoding="utf-8") as f:
+            cold = (f.read(), path)
+    threads = int(os.environ.get("BENCH_PYTHON_THREADS", "1"))
+    parallel = threads > 1 and run_parallel is not None
+    if parallel:
+        workload_fn = functools.partial(_share, run_parallel, threads)
+    min_sample_ns = os.environ.get("BENCH_PYTHON_MIN_SAMPLE_NS")
+    min_sample_ns = int(min_sample_ns) if min_sample_ns else None
+    evict = _evictor(int(os.environ.get("BENCH_PYTHON_EVICT_BYTES", "0")))
+    clock = os.environ.get("BENCH_PYTHON_CLOCK", "wall")
+    record_clocks = os.environ.get("BENCH_PYTHON_RECORD_CLOCKS") == "1"
+    if os.environ.get("BENCH_PYTHON_WORKER") == "1":
+        _worker(
+            workload_fn,
+            n,
+            seed,
+            params,
+            import_ns,
+            traced,
+            cold,
+            threads,
+            min_sample_ns,
+            evict,
+            parallel,
+            clock,
+            record_clocks,
+        )
+        return
+
+    calls = 1
+    if min_sample_ns is not None and iters > 0:
+        calls = _calls_per_sample(
+            workload_fn, n, seed, params, input_text, min_sample_ns
+        )
 
     timings_ns = []
+    cpu_ns = []
+    marshal_ns = []
+    peak_alloc_bytes = []
+    current_rss_kb = []
+    phases = {"compile_ns": [], "module_eval_ns": [], "call_ns": []}
+    thread_eval_ns = []
+    clocks = []
+    gc_ns = []
+    gc_collections = []
     result = None
-
     for i in range(iters):
-        start = time.perf_counter_ns()
-        r = workload_fn(n, seed)
-        elapsed = time.perf_counter_ns() - start
-        timings_ns.append(elapsed)
-
-        if result is None:
+        if evict is not None:
+            evict()
+        r, timings = _iteration(
+            workload_fn,
+            n,
+            seed,
+            params,
+            input_text,
+            traced,
+            cold,
+            threads,
+            calls,
+            clock,
+            record_clocks,
+        )
+        timings_ns.append(timings["eval_ns"])
+        cpu_ns.append(timings["cpu_ns"])
+        if record_clocks:
+            clocks.append(timings["clocks"])
+        gc_ns.append(timings["gc_ns"])
+        gc_collections.append(timings["gc_collections"])
+        if "thread_eval_ns" in timings:
+            thread_eval_ns.append(timings["thread_eval_ns"])
+        if "marshal_ns" in timings:
+            marshal_ns.append(timings["marshal_ns"])
+        if "peak_alloc_bytes" in timings:
+            peak_alloc_bytes.append(timings["peak_alloc_bytes"])
+        if cold is not None:
+            for key, values in phases.items():
+                values.append(timings[key])
+        rss = _current_rss_kb()
+        if rss is not None:
+            current_rss_kb.append(rss)
+        if i == 0:
             result = r
-        elif r != result:
-            print(
-                f"ERROR: result mismatch at iter {i}: expected {result}, got {r}",
-                file=sys.stderr,
-            )
-            sys.exit(1)
-
-    # Best-effort RSS (KiB on Linux, bytes/1024 on macOS).
-    rss_kb = 0
-    try:
-        import resource
-        usage = resource.getrusage(resource.RUSAGE_SELF)
-        rss_kb = usage.ru_maxrss
-        if sys.platform == "darwin":
-            rss_kb //= 1024  # macOS reports bytes
-    except ImportError:
-        pass  # Windows
+        _check_result(i, result, r)
 
-    print(json.dumps({"timings_ns": timings_ns, "result": result, "rss_kb": rss_kb}))
+    output = {
+        "timings_ns": timings_ns,
+        "cpu_ns": cpu_ns,
+        "gc_ns": gc_ns,
+        "gc_collections": gc_collections,
+        "result": checksum(result),
+        "rss_kb": _max_rss_kb(),
+        "version": sys.version,
+        "interpreter": interpreter(),
+        "started_unix_ns": _STARTED_UNIX_NS,
+        "import_ns": import_ns,
+    }
+    if input_text is not None:
+        output["marshal_ns"] = marshal_ns
+    if traced:
+        output["peak_alloc_bytes"] = peak_alloc_bytes
+    if len(current_rss_kb) == iters:
+        output["current_rss_kb"] = current_rss_kb
+    if cold is not None:
+        output.update(phases)
+    if threads > 1:
+        output["thread_eval_ns"] = thread_eval_ns
+    if min_sample_ns is not None:
+        output["calls_per_sample"] = calls
+    if record_clocks:
+        output["clocks"] = clocks
+    if parallel:
+        output["parallel"] = True
+    print(json.dumps(output))
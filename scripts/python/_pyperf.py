@@ -0,0 +1,75 @@
+"""Runs a workload script under pyperf (--python-harness pyperf).
+
+Usage:
+    python3 _pyperf.py [PYPERF OPTIONS] <script>.py <N> <SEED> [PARAMS [INPUT]]
+
+PARAMS is a JSON object of extra keyword arguments for run(), as for
+_harness.py. INPUT is a file holding the --input value as JSON, decoded once
+per process and passed to run() as `data`.
+
+pyperf spawns the worker processes, calibrates the loop count, warms up and
+writes its JSON (with --output) as usual. The benchmark is named after the
+script, and its metadata has "bench_result", the checksum() of what run()
+returned, "bench_version", the interpreter's sys.version, and
+"bench_interpreter", the JSON of _harness.interpreter(). With
+BENCH_PYTHON_NO_GC=1 in the environment, which pyperf passes on to its
+workers with --inherit-environ, the cyclic garbage collector is disabled.
+"""
+
+import functools
+import gc
+import importlib.util
+import json
+import os
+import sys
+
+import pyperf
+
+import _harness
+
+
+def _load(path):
+    """The script's run(), captured from its bench_main() call."""
+    captured = []
+    _harness.bench_main = lambda run, run_parallel=None: captured.append(run)
+    spec = importlib.util.spec_from_file_location("workload", path)
+    module = importlib.util.module_from_spec(spec)
+    spec.loader.exec_module(module)
+    if not captured:
+        sys.exit(f"{path} did not call bench_main()")
+    return captured[0]
+
+
+def _add_cmdline_args(cmd, args):
+    cmd.extend([args.script, str(args.n), str(args.seed), args.params])
+    if args.input is not None:
+        cmd.append(args.input)
+
+
+def main():
+    runner = pyperf.Runner(add_cmdline_args=_add_cmdline_args)
+    runner.argparser.add_argument("script")
+    runner.argparser.add_argument("n", type=int)
+    runner.argparser.add_argument("seed", type=int)
+    runner.argparser.add_argument("params", nargs="?", default="{}")
+    runner.argparser.add_argument("input", nargs="?")
+    args = runner.parse_args()
+
+    if os.environ.get("BENCH_PYTHON_NO_GC") == "1":
+        gc.disable()
+    params = json.loads(args.params)
+    if args.input is not None:
+        with open(args.input, encoding="utf-8") as f:
+            params["data"] = json.load(f)
+    run = functools.partial(_load(args.script), args.n, args.seed, **params)
+
+    runner.metadata["bench_result"] = str(_harness.checksum(run()))
+    # pyperf metadata is a single line.
+    runner.metadata["bench_version"] = " ".join(sys.version.split())
+    runner.metadata["bench_interpreter"] = json.dumps(_harness.interpreter())
+    name = os.path.splitext(os.path.basename(args.script))[0]
+    runner.bench_func(name, run)
+
+
+if __name__ == "__main__":
+    main()
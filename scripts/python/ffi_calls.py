@@ -0,0 +1,20 @@
+"""Embedding-boundary overhead: hot loop calling a native (C builtin) function."""
+
+import os
+import sys
+
+sys.path.insert(0, os.path.dirname(os.path.abspath(__file__)))
+from _harness import bench_main
+
+
+def run(n, seed):
+    x = seed % 1000003
+    checksum = 0
+    for i in range(n):
+        x = pow(x + i, 3, 1000003)
+        checksum = (checksum + x) % 2147483648
+
+    return checksum
+
+
+bench_main(run)
@@ -0,0 +1,29 @@
+"""Host-to-interpreter data transfer: walk a value built by the harness."""
+
+import os
+import sys
+
+sys.path.insert(0, os.path.dirname(os.path.abspath(__file__)))
+from _harness import bench_main
+
+
+def walk(v):
+    if isinstance(v, int):
+        return v
+    if isinstance(v, str):
+        return len(v)
+    total = 0
+    if isinstance(v, dict):
+        for k, x in v.items():
+            total = (total + len(k) + walk(x)) % 2147483648
+    else:
+        for x in v:
+            total = (total + walk(x)) % 2147483648
+    return total
+
+
+def run(n, seed, data):
+    return (walk(data) + n + seed) % 2147483648
+
+
+bench_main(run)
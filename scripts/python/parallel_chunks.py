@@ -0,0 +1,33 @@
+"""Parallel scaling: n items of LCG mixing, split evenly over run_parallel threads."""
+
+import os
+import sys
+
+sys.path.insert(0, os.path.dirname(os.path.abspath(__file__)))
+from _harness import bench_main
+
+
+def run_parallel(n, seed, threads):
+    share = (n + threads - 1) // threads
+    x = seed % 1000000 + 1
+    buckets = [0] * 16
+    checksum = 0
+
+    for i in range(share):
+        x = (x * 1103515245 + 12345) % 2147483648
+        b = x % 16
+        buckets[b] = (buckets[b] + x // 16) % 2147483648
+        if i % 64 == 63:
+            for v in buckets:
+                checksum = (checksum * 31 + v) % 2147483648
+
+    for v in buckets:
+        checksum = (checksum * 31 + v) % 2147483648
+    return checksum
+
+
+def run(n, seed):
+    return run_parallel(n, seed, 1)
+
+
+bench_main(run, run_parallel=run_parallel)
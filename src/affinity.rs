@@ -0,0 +1,92 @@
+//! CPU affinity.
+
+use std::collections::BTreeMap;
+
+use anyhow::{Result, bail};
+
+/// CPUs this process may run on.
+#[cfg(target_os = "linux")]
+pub fn available() -> Vec<usize> {
+    // SAFETY: `set` is a properly sized, zero-initialized cpu_set_t.
+    unsafe {
+        let mut set: libc::cpu_set_t = std::mem::zeroed();
+        if libc::sched_getaffinity(0, std::mem::size_of_val(&set), &mut set) != 0 {
+            return fallback();
+        }
+        (0..libc::CPU_SETSIZE as usize)
+            .filter(|&cpu| libc::CPU_ISSET(cpu, &set))
+            .collect()
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn available() -> Vec<usize> {
+    fallback()
+}
+
+fn fallback() -> Vec<usize> {
+    let n = std::thread::available_parallelism().map_or(1, |n| n.get());
+    (0..n).collect()
+}
+
+/// Group CPUs by physical core, so hyperthread siblings stay together.
+fn physical_cores(cpus: &[usize]) -> Vec<Vec<usize>> {
+    let mut cores: BTreeMap<(String, String, usize), Vec<usize>> = BTreeMap::new();
+    for &cpu in cpus {
+        let topo = format!("/sys/devices/system/cpu/cpu{cpu}/topology");
+        let read = |f: &str| std::fs::read_to_string(format!("{topo}/{f}")).ok();
+        let key = match (read("physical_package_id"), read("core_id")) {
+            (Some(pkg), Some(core)) => (pkg.trim().to_string(), core.trim().to_string(), 0),
+            _ => (String::new(), String::new(), cpu),
+        };
+        cores.entry(key).or_default().push(cpu);
+    }
+    cores.into_values().collect()
+}
+
+/// Split the available CPUs into `jobs` disjoint sets of whole physical
+/// cores, one per parallel worker.
+pub fn split(jobs: usize) -> Result<Vec<Vec<usize>>> {
+    let cores = physical_cores(&available());
+    if jobs > cores.len() {
+        bail!(
+            "--jobs {jobs} needs at least {jobs} physical cores, but only {} are available",
+            cores.len()
+        );
+    }
+    let per_job = cores.len() / jobs;
+    Ok(cores
+        .chunks(per_job)
+        .take(jobs)
+        .map(|chunk| chunk.concat())
+        .collect())
+}
+
+/// Pin the calling thread, and the subprocesses it spawns, to `cpus`.
+/// Returns false where affinity is not supported.
+#[cfg(target_os = "linux")]
+pub fn pin_current_thread(cpus: &[usize]) -> Result<bool> {
+    let max = libc::CPU_SETSIZE as usize;
+    if let Some(cpu) = cpus.iter().find(|&&cpu| cpu >= max) {
+        bail!("CPU {cpu} is out of range (at most {})", max - 1);
+    }
+    // SAFETY: `set` is a properly sized cpu_set_t; pid 0 is the calling thread.
+    unsafe {
+        let mut set: libc::cpu_set_t = std::mem::zeroed();
+        for &cpu in cpus {
+            libc::CPU_SET(cpu, &mut set);
+        }
+        if libc::sched_setaffinity(0, std::mem::size_of_val(&set), &set) != 0 {
+            bail!(
+                "sched_setaffinity({cpus:?}) failed: {}",
+                std::io::Error::last_os_error()
+            );
+        }
+    }
+    Ok(true)
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn pin_current_thread(_cpus: &[usize]) -> Result<bool> {
+    Ok(false)
+}
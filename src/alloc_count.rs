@@ -0,0 +1,100 @@
+//! Allocation counting (--features count-alloc).
+
+use serde::Serialize;
+
+/// Rust heap allocations made by one thread over a measured span:
+/// `alloc`, `alloc_zeroed` and `realloc` calls, and the bytes they
+/// asked for. A `realloc` counts as one allocation of its new size.
+#[derive(Clone, Copy, Default, Serialize)]
+pub struct Counts {
+    pub allocations: u64,
+    pub bytes: u64,
+}
+
+impl Counts {
+    pub fn since(&self, before: &Counts) -> Counts {
+        Counts {
+            allocations: self.allocations - before.allocations,
+            bytes: self.bytes - before.bytes,
+        }
+    }
+}
+
+impl std::ops::Add for Counts {
+    type Output = Counts;
+
+    fn add(self, other: Counts) -> Counts {
+        Counts {
+            allocations: self.allocations + other.allocations,
+            bytes: self.bytes + other.bytes,
+        }
+    }
+}
+
+#[cfg(feature = "count-alloc")]
+mod counting {
+    use std::alloc::{GlobalAlloc, Layout, System};
+    use std::cell::Cell;
+
+    thread_local! {
+        // Const-initialized and without a destructor, so reachable from
+        // the allocator at any point of a thread's life without
+        // allocating itself.
+        pub static COUNTS: Cell<super::Counts> = const {
+            Cell::new(super::Counts { allocations: 0, bytes: 0 })
+        };
+    }
+
+    /// The system allocator, counting per thread what is asked of it.
+    pub struct Counting;
+
+    fn count(size: usize) {
+        COUNTS.with(|c| {
+            let mut counts = c.get();
+            counts.allocations += 1;
+            counts.bytes += size as u64;
+            c.set(counts);
+        });
+    }
+
+    // SAFETY: every call is forwarded to System unchanged.
+    unsafe impl GlobalAlloc for Counting {
+        unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+            count(layout.size());
+            // SAFETY: the caller's guarantees are passed on.
+            unsafe { System.alloc(layout) }
+        }
+
+        unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+            count(layout.size());
+            // SAFETY: as for alloc.
+            unsafe { System.alloc_zeroed(layout) }
+        }
+
+        unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+            count(new_size);
+            // SAFETY: as for alloc.
+            unsafe { System.realloc(ptr, layout, new_size) }
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+            // SAFETY: as for alloc.
+            unsafe { System.dealloc(ptr, layout) }
+        }
+    }
+
+    #[global_allocator]
+    static GLOBAL: Counting = Counting;
+}
+
+/// What the calling thread has allocated so far; None in builds without
+/// the counting allocator.
+#[cfg(feature = "count-alloc")]
+pub fn current() -> Option<Counts> {
+    Some(counting::COUNTS.with(|c| c.get()))
+}
+
+#[cfg(not(feature = "count-alloc"))]
+pub fn current() -> Option<Counts> {
+    None
+}
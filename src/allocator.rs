@@ -0,0 +1,96 @@
+//! Global allocator (--features jemalloc, mimalloc).
+
+#[cfg(any(
+    all(feature = "jemalloc", feature = "mimalloc"),
+    all(feature = "jemalloc", feature = "count-alloc"),
+    all(feature = "mimalloc", feature = "count-alloc"),
+))]
+compile_error!("jemalloc, mimalloc and count-alloc each install a global allocator; enable one");
+
+use serde::{Deserialize, Serialize};
+
+/// The Rust global allocator of this build, which the in-process
+/// Starlark engine allocates from.
+pub const NAME: &str = if cfg!(feature = "jemalloc") {
+    "jemalloc"
+} else if cfg!(feature = "mimalloc") {
+    "mimalloc"
+} else {
+    "system"
+};
+
+#[cfg(feature = "jemalloc")]
+#[global_allocator]
+static GLOBAL: tikv_jemallocator::Jemalloc = tikv_jemallocator::Jemalloc;
+
+#[cfg(feature = "mimalloc")]
+#[global_allocator]
+static GLOBAL: mimalloc::MiMalloc = mimalloc::MiMalloc;
+
+/// What the allocator reports about the whole process; each allocator
+/// tracks only some of these.
+#[derive(Clone, Copy, Default, Serialize, Deserialize)]
+pub struct Stats {
+    /// Bytes handed out and not yet freed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub allocated_bytes: Option<u64>,
+    /// Bytes in pages holding live allocations.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub active_bytes: Option<u64>,
+    /// Bytes the allocator has resident in physical memory, its metadata
+    /// included.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub resident_bytes: Option<u64>,
+    /// Bytes the allocator has committed from the OS.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub committed_bytes: Option<u64>,
+}
+
+/// jemalloc's `stats.allocated`, `stats.active` and `stats.resident`;
+/// None if they cannot be read.
+#[cfg(feature = "jemalloc")]
+pub fn stats() -> Option<Stats> {
+    use tikv_jemalloc_ctl::{epoch, stats};
+
+    // jemalloc caches its statistics until the epoch is advanced.
+    epoch::advance().ok()?;
+    Some(Stats {
+        allocated_bytes: Some(stats::allocated::read().ok()? as u64),
+        active_bytes: Some(stats::active::read().ok()? as u64),
+        resident_bytes: Some(stats::resident::read().ok()? as u64),
+        committed_bytes: None,
+    })
+}
+
+/// mimalloc's `mi_process_info`: its resident and committed memory.
+#[cfg(feature = "mimalloc")]
+pub fn stats() -> Option<Stats> {
+    use std::ptr::null_mut;
+
+    let (mut resident, mut committed) = (0, 0);
+    // SAFETY: mimalloc only writes through the pointers that are not
+    // null, both valid for the duration of the call.
+    unsafe {
+        libmimalloc_sys::mi_process_info(
+            null_mut(),
+            null_mut(),
+            null_mut(),
+            &mut resident,
+            null_mut(),
+            &mut committed,
+            null_mut(),
+            null_mut(),
+        );
+    }
+    Some(Stats {
+        resident_bytes: Some(resident as u64),
+        committed_bytes: Some(committed as u64),
+        ..Stats::default()
+    })
+}
+
+/// The system allocator reports nothing.
+#[cfg(not(any(feature = "jemalloc", feature = "mimalloc")))]
+pub fn stats() -> Option<Stats> {
+    None
+}
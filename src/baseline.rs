@@ -0,0 +1,135 @@
+//! Baseline subcommand.
+
+use std::io::Write;
+
+use anyhow::{Context, Result, bail};
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use super::results::{SizeKey, load};
+use super::{BaselineArgs, FailureClass, SCHEMA_VERSION, fail_on};
+
+/// Stored median of one (engine, workload, size) group.
+#[derive(Serialize, Deserialize)]
+struct BaselineRecord {
+    schema_version: u32,
+    kind: String,
+    engine: String,
+    workload: String,
+    size: String,
+    n: u64,
+    median_eval_ns: f64,
+    /// Whether the group ran with --timeout, whose statement hook slows
+    /// Starlark.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    with_timeout: bool,
+}
+
+pub fn save(args: &BaselineArgs) -> Result<()> {
+    let (medians, instrumented) = load(&args.files)?;
+    let mut out = std::fs::File::create(&args.baseline)
+        .with_context(|| format!("cannot create {}", args.baseline.display()))?;
+    let mut count = 0;
+    for (size, engines) in &medians {
+        for (engine, workloads) in engines {
+            for (workload, &median) in workloads {
+                let record = BaselineRecord {
+                    schema_version: SCHEMA_VERSION,
+                    kind: "baseline".into(),
+                    engine: engine.clone(),
+                    workload: workload.clone(),
+                    size: size.size.clone(),
+                    n: size.n,
+                    median_eval_ns: median,
+                    with_timeout: instrumented.contains(&(
+                        size.clone(),
+                        engine.clone(),
+                        workload.clone(),
+                    )),
+                };
+                writeln!(out, "{}", serde_json::to_string(&record)?)?;
+                count += 1;
+            }
+        }
+    }
+    info!("saved {count} groups to {}", args.baseline.display());
+    Ok(())
+}
+
+pub fn check(args: &BaselineArgs) -> Result<()> {
+    let text = std::fs::read_to_string(&args.baseline)
+        .with_context(|| format!("cannot read {}", args.baseline.display()))?;
+    let (medians, instrumented) = load(&args.files)?;
+
+    println!(
+        "{:<4} {:>8}  {:<20} {:<16} {:>12} {:>12} {:>8}",
+        "size", "n", "workload", "engine", "base (ms)", "now (ms)", "change"
+    );
+    let mut regressions = 0;
+    for (lineno, line) in text.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let base: BaselineRecord = serde_json::from_str(line).with_context(|| {
+            format!(
+                "{}:{}: not a baseline record",
+                args.baseline.display(),
+                lineno + 1
+            )
+        })?;
+        let now = medians
+            .iter()
+            .find(|(size, _)| size.n == base.n && size.size == base.size)
+            .and_then(|(_, engines)| engines.get(&base.engine))
+            .and_then(|workloads| workloads.get(&base.workload));
+        let Some(&now) = now else {
+            continue;
+        };
+        let size = SizeKey {
+            n: base.n,
+            size: base.size.clone(),
+        };
+        let with_timeout =
+            instrumented.contains(&(size, base.engine.clone(), base.workload.clone()));
+        if with_timeout != base.with_timeout {
+            bail!(
+                "{}/{}/{}: the baseline was measured {} --timeout and this run {} it; its \
+                 statement hook slows Starlark, so the two do not compare",
+                base.engine,
+                base.workload,
+                base.size,
+                if base.with_timeout { "with" } else { "without" },
+                if with_timeout { "with" } else { "without" }
+            );
+        }
+        let change_pct = (now - base.median_eval_ns) / base.median_eval_ns * 100.0;
+        let regressed = change_pct > args.threshold;
+        if regressed {
+            regressions += 1;
+        }
+        println!(
+            "{:<4} {:>8}  {:<20} {:<16} {:>12.3} {:>12.3} {:>+7.1}%{}",
+            base.size,
+            base.n,
+            base.workload,
+            base.engine,
+            base.median_eval_ns / 1e6,
+            now / 1e6,
+            change_pct,
+            if regressed { "  REGRESSION" } else { "" }
+        );
+    }
+
+    if regressions > 0 {
+        fail_on(
+            &args.fail_on,
+            FailureClass::Regression,
+            format!(
+                "{regressions} group(s) regressed by more than {}% against {}",
+                args.threshold,
+                args.baseline.display()
+            ),
+        )?;
+    }
+    Ok(())
+}
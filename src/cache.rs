@@ -0,0 +1,87 @@
+//! Cache state control (--cache-state).
+
+use std::hint::black_box;
+
+use anyhow::{Context, Result, bail};
+
+/// Cache line size assumed when touching the eviction buffer.
+const LINE: usize = 64;
+
+/// Eviction buffer size without a readable cache hierarchy.
+const DEFAULT_BYTES: usize = 64 << 20;
+
+/// Where root asks the kernel to drop the page cache, dentries and inodes.
+const DROP_CACHES: &str = "/proc/sys/vm/drop_caches";
+
+/// Twice the largest cache of CPU 0 as sysfs lists it, e.g. the "32768K"
+/// of a shared L3, enough to push everything else out of it; or
+/// [`DEFAULT_BYTES`].
+pub fn evict_bytes() -> usize {
+    let largest = std::fs::read_dir("/sys/devices/system/cpu/cpu0/cache")
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter_map(|entry| {
+            let size = std::fs::read_to_string(entry.path().join("size")).ok()?;
+            let size = size.trim();
+            let (digits, unit) = match size.strip_suffix('K') {
+                Some(kib) => (kib, 1 << 10),
+                None => match size.strip_suffix('M') {
+                    Some(mib) => (mib, 1 << 20),
+                    None => (size, 1),
+                },
+            };
+            Some(digits.parse::<usize>().ok()? * unit)
+        })
+        .max();
+    largest.map_or(DEFAULT_BYTES, |bytes| bytes * 2)
+}
+
+/// A buffer larger than the caches, written through before each
+/// iteration so that it starts from caches holding none of its data.
+pub struct Evictor {
+    buf: Vec<u8>,
+    round: u8,
+}
+
+impl Evictor {
+    pub fn new(bytes: usize) -> Evictor {
+        Evictor {
+            buf: vec![0; bytes],
+            round: 0,
+        }
+    }
+
+    /// Write one byte of every cache line, a different value each time
+    /// so the stores are never redundant.
+    pub fn evict(&mut self) {
+        self.round = self.round.wrapping_add(1);
+        for line in self.buf.iter_mut().step_by(LINE) {
+            *line = self.round;
+        }
+        black_box(&self.buf);
+    }
+}
+
+/// Check that the page cache can be dropped, before measuring.
+pub fn check_drop() -> Result<()> {
+    if !cfg!(target_os = "linux") {
+        bail!("--drop-fs-caches writes {DROP_CACHES}, which only Linux has");
+    }
+    std::fs::OpenOptions::new()
+        .write(true)
+        .open(DROP_CACHES)
+        .with_context(|| format!("--drop-fs-caches needs root to write {DROP_CACHES}"))?;
+    Ok(())
+}
+
+/// Write back dirty pages, then drop the clean page cache, dentries and
+/// inodes.
+pub fn drop_fs() -> Result<()> {
+    #[cfg(target_os = "linux")]
+    // SAFETY: sync takes no arguments and cannot fail.
+    unsafe {
+        libc::sync();
+    }
+    std::fs::write(DROP_CACHES, "3\n").with_context(|| format!("cannot write {DROP_CACHES}"))
+}
@@ -0,0 +1,64 @@
+//! Timer and call overhead calibration.
+
+use std::path::Path;
+use std::time::Instant;
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use super::{starlark_engine, stats};
+
+/// Repetitions of each measurement; the medians are kept.
+const SAMPLES: usize = 1000;
+
+/// A `run` that returns at once.
+const NULL_SCRIPT: &str = "def run(n, seed):\n    return 0\n";
+
+/// Fixed costs of measuring one Starlark call, in nanoseconds.
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct Overhead {
+    /// An empty timed region: `Instant::now()` followed by `elapsed()`.
+    pub timer_ns: u64,
+    /// `eval_ns` of the null `run`: the timer plus entering and leaving
+    /// a Starlark function. This is what --subtract-overhead takes off.
+    pub call_ns: u64,
+    /// What `call_run` spends around the timed region: creating the
+    /// Module and Evaluator, allocating the arguments, and tearing them
+    /// down. In `wall_ns`, not in `eval_ns`.
+    pub setup_ns: u64,
+}
+
+/// Calibrate `language`'s overhead by timing the null `run` the way the
+/// workloads are timed.
+pub fn measure(language: &starlark_engine::Language) -> Result<Overhead> {
+    let timer: Vec<u64> = (0..SAMPLES)
+        .map(|_| {
+            let start = Instant::now();
+            start.elapsed().as_nanos() as u64
+        })
+        .collect();
+
+    let prepared =
+        starlark_engine::prepare(NULL_SCRIPT, Path::new("."), language, None, false, false)
+            .context("cannot prepare the overhead calibration script")?;
+    let call_options = starlark_engine::CallOptions {
+        profile: false,
+        gc: false,
+        calls: 1,
+        parallel: None,
+    };
+    let mut call = Vec::with_capacity(SAMPLES);
+    let mut setup = Vec::with_capacity(SAMPLES);
+    for _ in 0..SAMPLES {
+        let start = Instant::now();
+        let r = starlark_engine::call_run(&prepared, 0, 0, &[], None, call_options)?;
+        let wall = start.elapsed();
+        call.push(r.eval_dur.as_nanos() as u64);
+        setup.push(wall.saturating_sub(r.eval_dur).as_nanos() as u64);
+    }
+    Ok(Overhead {
+        timer_ns: stats::median(&timer) as u64,
+        call_ns: stats::median(&call) as u64,
+        setup_ns: stats::median(&setup) as u64,
+    })
+}
@@ -0,0 +1,37 @@
+//! System noise canary.
+
+use std::hint::black_box;
+use std::time::Instant;
+
+/// Timed repetitions of the loop.
+pub const SAMPLES: usize = 200;
+
+/// Steps of the loop: a few hundred microseconds in a release build,
+/// long enough to dwarf the timer and short enough to catch interrupts.
+const STEPS: u32 = 200_000;
+
+/// A dependent chain of xorshift steps: no memory traffic, no branches
+/// worth predicting, so only the CPU's speed and whatever else runs on
+/// it change how long it takes.
+fn spin() -> u64 {
+    let mut x = black_box(0x9e37_79b9_7f4a_7c15_u64);
+    for _ in 0..STEPS {
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+    }
+    black_box(x)
+}
+
+/// Time the loop [`SAMPLES`] times, after one untimed run, in
+/// nanoseconds.
+pub fn measure() -> Vec<u64> {
+    spin();
+    (0..SAMPLES)
+        .map(|_| {
+            let start = Instant::now();
+            spin();
+            start.elapsed().as_nanos() as u64
+        })
+        .collect()
+}
@@ -0,0 +1,299 @@
+//! cgroup isolation.
+
+use std::fs::File;
+use std::io::{Read, Seek, Write};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use anyhow::{Context, Result, bail};
+use serde::{Deserialize, Serialize};
+use tracing::{debug, warn};
+
+/// Resource usage of a cgroup, from memory.peak and cpu.stat.
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct Usage {
+    /// High-water mark of the memory charged to the cgroup; omitted
+    /// without the memory controller.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub memory_peak_kb: Option<u64>,
+    pub cpu_usage_us: u64,
+    pub cpu_user_us: u64,
+    pub cpu_system_us: u64,
+}
+
+impl Usage {
+    /// CPU time used since `before`; the memory peak is kept as is.
+    pub fn since(&self, before: &Usage) -> Usage {
+        Usage {
+            memory_peak_kb: self.memory_peak_kb,
+            cpu_usage_us: self.cpu_usage_us.saturating_sub(before.cpu_usage_us),
+            cpu_user_us: self.cpu_user_us.saturating_sub(before.cpu_user_us),
+            cpu_system_us: self.cpu_system_us.saturating_sub(before.cpu_system_us),
+        }
+    }
+
+    /// CPU time divided evenly over `iters` iterations.
+    pub fn per_iter(&self, iters: usize) -> Usage {
+        let iters = iters.max(1) as u64;
+        Usage {
+            memory_peak_kb: self.memory_peak_kb,
+            cpu_usage_us: self.cpu_usage_us / iters,
+            cpu_user_us: self.cpu_user_us / iters,
+            cpu_system_us: self.cpu_system_us / iters,
+        }
+    }
+}
+
+/// Limits applied to every cgroup a measured process runs in.
+pub struct Limits {
+    pub cpus: Option<f64>,
+    pub memory_mib: Option<u64>,
+}
+
+/// The cgroups of this invocation: `dir` holds the `host` leaf with the
+/// benchmark process and one leaf per running Python subprocess.
+struct Run {
+    dir: PathBuf,
+    host: PathBuf,
+    /// The cgroup the process started in, restored at the end.
+    origin: PathBuf,
+    limits: Limits,
+    next_leaf: AtomicUsize,
+}
+
+static RUN: OnceLock<Run> = OnceLock::new();
+
+/// Moves the process back and removes the run's cgroups when dropped.
+pub struct Guard;
+
+impl Drop for Guard {
+    fn drop(&mut self) {
+        let Some(run) = RUN.get() else {
+            return;
+        };
+        if let Err(e) = join(&run.origin) {
+            warn!("cannot move back to {}: {e:#}", run.origin.display());
+        }
+        for dir in [&run.host, &run.dir] {
+            if let Err(e) = std::fs::remove_dir(dir) {
+                warn!("cannot remove cgroup {}: {e}", dir.display());
+            }
+        }
+    }
+}
+
+/// Mount point of the cgroup2 hierarchy.
+fn mount_point() -> Result<PathBuf> {
+    let mounts =
+        std::fs::read_to_string("/proc/self/mounts").context("cannot read /proc/self/mounts")?;
+    mounts
+        .lines()
+        .map(|l| l.split_whitespace().collect::<Vec<_>>())
+        .find(|f| f.len() > 2 && f[2] == "cgroup2")
+        .map(|f| PathBuf::from(f[1]))
+        .context("--cgroup needs a cgroup v2 hierarchy, but none is mounted")
+}
+
+fn write(path: &Path, value: &str) -> Result<()> {
+    std::fs::write(path, value)
+        .with_context(|| format!("cannot write {value:?} to {}", path.display()))
+}
+
+/// Move the whole process into the cgroup `dir`.
+fn join(dir: &Path) -> Result<()> {
+    write(&dir.join("cgroup.procs"), &std::process::id().to_string())
+}
+
+/// Create a leaf cgroup with the run's limits.
+fn create_leaf(dir: &Path, limits: &Limits) -> Result<()> {
+    std::fs::create_dir(dir).with_context(|| format!("cannot create cgroup {}", dir.display()))?;
+    if let Some(cpus) = limits.cpus {
+        const PERIOD_US: f64 = 100_000.0;
+        write(
+            &dir.join("cpu.max"),
+            &format!("{} {PERIOD_US}", (cpus * PERIOD_US).round().max(1000.0)),
+        )?;
+    }
+    if let Some(mib) = limits.memory_mib {
+        write(&dir.join("memory.max"), &(mib * 1024 * 1024).to_string())?;
+    }
+    Ok(())
+}
+
+/// The cgroup v2 directory the process runs in, under `mount`.
+fn own_cgroup(mount: &Path) -> Result<PathBuf> {
+    let own =
+        std::fs::read_to_string("/proc/self/cgroup").context("cannot read /proc/self/cgroup")?;
+    own.lines()
+        .find_map(|l| l.strip_prefix("0::"))
+        .map(|p| mount.join(p.trim_start_matches('/')))
+        .context("the process is not in a cgroup v2 hierarchy")
+}
+
+/// Create the run's cgroups under `parent` (default: the cgroup2 root)
+/// and move the benchmark process into its host leaf.
+pub fn init(parent: Option<&Path>, limits: Limits) -> Result<Guard> {
+    if !cfg!(target_os = "linux") {
+        bail!("--cgroup is only supported on Linux");
+    }
+    let mount = mount_point()?;
+    let parent = parent.map_or_else(|| mount.clone(), Path::to_path_buf);
+    let origin = own_cgroup(&mount)?;
+
+    let dir = parent.join(format!("starlark-bench-{}", std::process::id()));
+    std::fs::create_dir(&dir).with_context(|| {
+        format!(
+            "cannot create cgroup {} (--cgroup usually needs root)",
+            dir.display()
+        )
+    })?;
+    let run = Run {
+        host: dir.join("host"),
+        dir,
+        origin,
+        limits,
+        next_leaf: AtomicUsize::new(0),
+    };
+    let setup = || -> Result<()> {
+        let available = std::fs::read_to_string(run.dir.join("cgroup.controllers"))?;
+        let has = |c: &str| available.split_whitespace().any(|a| a == c);
+        let mut wanted = vec!["memory"];
+        if run.limits.cpus.is_some() {
+            wanted.push("cpu");
+        }
+        for controller in wanted {
+            if has(controller) {
+                write(
+                    &run.dir.join("cgroup.subtree_control"),
+                    &format!("+{controller}"),
+                )?;
+            } else if controller == "memory" && run.limits.memory_mib.is_none() {
+                warn!(
+                    "the memory controller is not enabled for {}; records will have no memory_peak_kb",
+                    run.dir.display()
+                );
+            } else {
+                bail!(
+                    "the {controller} controller is not enabled for {}; enable it in the parent's cgroup.subtree_control",
+                    run.dir.display()
+                );
+            }
+        }
+        create_leaf(&run.host, &run.limits)?;
+        join(&run.host)
+    };
+    if let Err(e) = setup() {
+        let _ = std::fs::remove_dir(&run.host);
+        let _ = std::fs::remove_dir(&run.dir);
+        return Err(e);
+    }
+    debug!("running in cgroup {}", run.host.display());
+    let _ = RUN.set(run);
+    Ok(Guard)
+}
+
+/// A fresh cgroup for one subprocess, removed when dropped; None
+/// without --cgroup.
+pub fn leaf() -> Result<Option<Leaf>> {
+    let Some(run) = RUN.get() else {
+        return Ok(None);
+    };
+    let index = run.next_leaf.fetch_add(1, Ordering::Relaxed);
+    let dir = run.dir.join(format!("leaf-{index}"));
+    create_leaf(&dir, &run.limits)?;
+    Ok(Some(Leaf { dir }))
+}
+
+/// Usage of the benchmark process's own cgroup so far; None without
+/// --cgroup.
+pub fn host_usage() -> Option<Usage> {
+    let run = RUN.get()?;
+    read_usage(&run.host)
+        .inspect_err(|e| debug!("cannot read cgroup usage: {e:#}"))
+        .ok()
+}
+
+fn read_usage(dir: &Path) -> Result<Usage> {
+    let stat_path = dir.join("cpu.stat");
+    let stat = std::fs::read_to_string(&stat_path)
+        .with_context(|| format!("cannot read {}", stat_path.display()))?;
+    let field = |name: &str| {
+        stat.lines()
+            .find_map(|l| l.strip_prefix(name)?.trim().parse().ok())
+            .unwrap_or(0)
+    };
+    let memory_peak_kb = std::fs::read_to_string(dir.join("memory.peak"))
+        .ok()
+        .and_then(|s| s.trim().parse::<u64>().ok())
+        .map(|bytes| bytes / 1024);
+    Ok(Usage {
+        memory_peak_kb,
+        cpu_usage_us: field("usage_usec"),
+        cpu_user_us: field("user_usec"),
+        cpu_system_us: field("system_usec"),
+    })
+}
+
+/// The memory high-water mark of the benchmark process's cgroup from
+/// some point on: its memory.peak, opened and reset through the open
+/// file (Linux 6.12 and later), so that reads through that file only
+/// see what followed while other readers are not disturbed.
+pub struct PeakWatch {
+    file: File,
+}
+
+impl PeakWatch {
+    /// Start watching the --cgroup host leaf, or without --cgroup the
+    /// cgroup the process runs in, which its subprocesses share. None
+    /// without cgroup v2, the memory controller or a resettable peak.
+    pub fn start() -> Option<PeakWatch> {
+        let dir = match RUN.get() {
+            Some(run) => run.host.clone(),
+            None => own_cgroup(&mount_point().ok()?).ok()?,
+        };
+        let path = dir.join("memory.peak");
+        let mut file = std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(&path)
+            .inspect_err(|e| debug!("cannot open {}: {e}", path.display()))
+            .ok()?;
+        file.write_all(b"reset\n")
+            .inspect_err(|e| debug!("cannot reset {}: {e}", path.display()))
+            .ok()?;
+        Some(PeakWatch { file })
+    }
+
+    /// The peak since `start`, in KiB.
+    pub fn peak_kb(&mut self) -> Option<u64> {
+        let mut text = String::new();
+        self.file.rewind().ok()?;
+        self.file.read_to_string(&mut text).ok()?;
+        Some(text.trim().parse::<u64>().ok()? / 1024)
+    }
+}
+
+pub struct Leaf {
+    dir: PathBuf,
+}
+
+impl Leaf {
+    /// The file a process writes "0" to in order to join the cgroup.
+    pub fn procs_path(&self) -> PathBuf {
+        self.dir.join("cgroup.procs")
+    }
+
+    /// Usage of the cgroup so far.
+    pub fn usage(&self) -> Result<Usage> {
+        read_usage(&self.dir)
+    }
+}
+
+impl Drop for Leaf {
+    fn drop(&mut self) {
+        if let Err(e) = std::fs::remove_dir(&self.dir) {
+            debug!("cannot remove cgroup {}: {e}", self.dir.display());
+        }
+    }
+}
@@ -0,0 +1,74 @@
+//! Compare subcommand.
+
+use anyhow::Result;
+
+use super::results::{check_instrumented, load};
+use super::{RatioRecord, ReportArgs, SCHEMA_VERSION};
+
+/// Per-workload ratio of every engine against the baseline engine.
+pub fn run(args: &ReportArgs) -> Result<()> {
+    let (medians, instrumented) = load(&args.files)?;
+
+    let mut ratios = Vec::new();
+    for (size, engines) in &medians {
+        let Some(base) = engines.get(&args.baseline_engine) else {
+            continue;
+        };
+        for (engine, workloads) in engines {
+            if *engine == args.baseline_engine {
+                continue;
+            }
+            for (workload, &m) in workloads {
+                let Some(&b) = base.get(workload).filter(|&&b| b > 0.0) else {
+                    continue;
+                };
+                check_instrumented(
+                    &instrumented,
+                    size,
+                    (engine, &args.baseline_engine),
+                    workload,
+                )?;
+                ratios.push(RatioRecord {
+                    schema_version: SCHEMA_VERSION,
+                    run_id: None,
+                    kind: "ratio",
+                    engine: engine.clone(),
+                    baseline_engine: args.baseline_engine.clone(),
+                    workload: workload.clone(),
+                    size: size.size.clone(),
+                    n: size.n as usize,
+                    seed: None,
+                    median_eval_ns: m as u64,
+                    baseline_median_eval_ns: b as u64,
+                    baseline_n: None,
+                    ratio: m / b,
+                });
+            }
+        }
+    }
+
+    if args.json {
+        for r in &ratios {
+            println!("{}", serde_json::to_string(r)?);
+        }
+        return Ok(());
+    }
+
+    println!(
+        "{:<4} {:>8}  {:<20} {:<16} {:>12} {:>12} {:>7}",
+        "size", "n", "workload", "engine", "median (ms)", "base (ms)", "ratio"
+    );
+    for r in &ratios {
+        println!(
+            "{:<4} {:>8}  {:<20} {:<16} {:>12.3} {:>12.3} {:>6.2}x",
+            r.size,
+            r.n,
+            r.workload,
+            r.engine,
+            r.median_eval_ns as f64 / 1e6,
+            r.baseline_median_eval_ns as f64 / 1e6,
+            r.ratio
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,152 @@
+//! Suite configuration file.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result, anyhow};
+use clap::parser::ValueSource;
+use clap::{ArgMatches, ValueEnum};
+use serde::Deserialize;
+
+use super::{RunArgs, Tag, selection};
+
+/// `--config suite.toml`. Every key is optional; a flag given on the
+/// command line wins over the file.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SuiteConfig {
+    engines: Option<Vec<String>>,
+    workloads: Option<Vec<String>>,
+    tags: Option<Vec<String>>,
+    skip_tags: Option<Vec<String>>,
+    sizes: Option<Vec<String>>,
+    n: Option<Vec<usize>>,
+    iters: Option<u32>,
+    warmup: Option<u32>,
+    seed: Option<u64>,
+    seeds: Option<Vec<u64>>,
+    python: Option<String>,
+    pythons: Option<Vec<String>>,
+    provision_python: Option<Vec<String>>,
+    scripts_dir: Option<PathBuf>,
+    output: Option<PathBuf>,
+}
+
+pub fn load(path: &Path) -> Result<SuiteConfig> {
+    let text =
+        std::fs::read_to_string(path).with_context(|| format!("cannot read {}", path.display()))?;
+    toml::from_str(&text).with_context(|| format!("invalid config {}", path.display()))
+}
+
+fn parse_all<T: clap::ValueEnum>(values: &[String]) -> Result<Vec<super::Selection<T>>> {
+    values
+        .iter()
+        .map(|v| selection(v).map_err(|e| anyhow!("config: {e}")))
+        .collect()
+}
+
+fn parse_tags(values: &[String]) -> Result<Vec<Tag>> {
+    values
+        .iter()
+        .map(|v| Tag::from_str(v, true).map_err(|e| anyhow!("config: {e}")))
+        .collect()
+}
+
+impl SuiteConfig {
+    /// Fill `args` from the file wherever the flag was not given
+    /// explicitly, and return the ids of the arguments it set.
+    pub fn apply(self, args: &mut RunArgs, matches: &ArgMatches) -> Result<Vec<&'static str>> {
+        let unset = |id: &str| matches.value_source(id) != Some(ValueSource::CommandLine);
+        let mut set = Vec::new();
+
+        if let Some(v) = self.engines
+            && unset("engine")
+        {
+            args.engine = parse_all(&v)?;
+            set.push("engine");
+        }
+        if let Some(v) = self.workloads
+            && unset("workload")
+        {
+            args.workload = parse_all(&v)?;
+            set.push("workload");
+        }
+        if let Some(v) = self.tags
+            && unset("tags")
+        {
+            args.tags = parse_tags(&v)?;
+            set.push("tags");
+        }
+        if let Some(v) = self.skip_tags
+            && unset("skip_tags")
+        {
+            args.skip_tags = parse_tags(&v)?;
+            set.push("skip_tags");
+        }
+        if let Some(v) = self.sizes
+            && unset("size")
+        {
+            args.size = parse_all(&v)?;
+            set.push("size");
+        }
+        if let Some(v) = self.n
+            && unset("n")
+        {
+            args.n = v;
+            set.push("n");
+        }
+        if let Some(v) = self.iters
+            && unset("iters")
+        {
+            args.iters = v;
+            set.push("iters");
+        }
+        if let Some(v) = self.warmup
+            && unset("warmup")
+        {
+            args.warmup = v;
+            set.push("warmup");
+        }
+        if let Some(v) = self.seed
+            && unset("seed")
+        {
+            args.seed = v;
+            set.push("seed");
+        }
+        if let Some(v) = self.seeds
+            && unset("seeds")
+            && unset("seed")
+            && unset("seed_count")
+        {
+            args.seeds = v;
+            set.push("seeds");
+        }
+        let interpreters_unset = unset("python") && unset("pythons") && unset("provision_python");
+        if let Some(v) = self.python
+            && interpreters_unset
+        {
+            args.python = vec![v];
+            set.push("python");
+        }
+        if let Some(v) = self.pythons
+            && interpreters_unset
+        {
+            args.pythons = v;
+            set.push("pythons");
+        }
+        if let Some(v) = self.provision_python
+            && interpreters_unset
+        {
+            args.provision_python = v;
+            set.push("provision_python");
+        }
+        if unset("scripts_dir") && self.scripts_dir.is_some() {
+            args.scripts_dir = self.scripts_dir;
+            set.push("scripts_dir");
+        }
+        if unset("output") && self.output.is_some() {
+            args.output = self.output;
+            set.push("output");
+        }
+        Ok(set)
+    }
+}
@@ -0,0 +1,159 @@
+//! CPU frequency monitoring (--cpu-freq).
+
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use anyhow::{Result, bail};
+use serde::{Deserialize, Serialize};
+
+use super::affinity;
+
+/// How often a [`Sampler`] reads the frequencies.
+const INTERVAL: Duration = Duration::from_millis(100);
+
+/// Frequencies of the CPUs a group ran on while it measured, in MHz,
+/// pooled over CPUs and samples.
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct Stats {
+    pub min_mhz: u64,
+    pub avg_mhz: u64,
+    pub max_mhz: u64,
+    /// Readings taken, one per CPU per sample.
+    pub samples: usize,
+    /// Thermal throttling events the kernel counted on those CPUs
+    /// meanwhile; omitted where it does not count them.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub throttle_events: Option<u64>,
+}
+
+impl Stats {
+    /// The spread of the frequencies, in percent of the highest.
+    pub fn spread_pct(&self) -> f64 {
+        if self.max_mhz == 0 {
+            return 0.0;
+        }
+        (self.max_mhz - self.min_mhz) as f64 / self.max_mhz as f64 * 100.0
+    }
+}
+
+/// Current frequency of each of `cpus` in MHz: cpufreq's
+/// scaling_cur_freq, or where the kernel has no cpufreq driver (as in
+/// most VMs) the "cpu MHz" lines of /proc/cpuinfo.
+fn read(cpus: &[usize]) -> Vec<u64> {
+    let sysfs: Option<Vec<u64>> = cpus
+        .iter()
+        .map(|cpu| {
+            let path = format!("/sys/devices/system/cpu/cpu{cpu}/cpufreq/scaling_cur_freq");
+            let khz: u64 = std::fs::read_to_string(path).ok()?.trim().parse().ok()?;
+            Some(khz / 1000)
+        })
+        .collect();
+    if let Some(mhz) = sysfs {
+        return mhz;
+    }
+    let Ok(cpuinfo) = std::fs::read_to_string("/proc/cpuinfo") else {
+        return Vec::new();
+    };
+    let mut processor = None;
+    let mut mhz = Vec::new();
+    for line in cpuinfo.lines() {
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        match key.trim() {
+            "processor" => processor = value.trim().parse::<usize>().ok(),
+            "cpu MHz" if processor.is_some_and(|p| cpus.contains(&p)) => {
+                if let Ok(v) = value.trim().parse::<f64>() {
+                    mhz.push(v.round() as u64);
+                }
+            }
+            _ => {}
+        }
+    }
+    mhz
+}
+
+/// Thermal throttling events counted so far on `cpus`, core and
+/// package together; None without the thermal_throttle counters (Intel).
+fn throttle_count(cpus: &[usize]) -> Option<u64> {
+    cpus.iter()
+        .flat_map(|cpu| ["core", "package"].map(|kind| (cpu, kind)))
+        .map(|(cpu, kind)| {
+            let path =
+                format!("/sys/devices/system/cpu/cpu{cpu}/thermal_throttle/{kind}_throttle_count");
+            std::fs::read_to_string(path)
+                .ok()?
+                .trim()
+                .parse::<u64>()
+                .ok()
+        })
+        .sum()
+}
+
+/// Check that the frequency can be read; --cpu-freq fails here rather
+/// than recording nothing.
+pub fn check() -> Result<()> {
+    if !cfg!(target_os = "linux") {
+        bail!("--cpu-freq reads the frequency from sysfs or /proc/cpuinfo, which only Linux has");
+    }
+    if read(&affinity::available()).is_empty() {
+        bail!(
+            "--cpu-freq found neither cpufreq's scaling_cur_freq nor \"cpu MHz\" in /proc/cpuinfo"
+        );
+    }
+    Ok(())
+}
+
+/// Reads the frequencies of the CPUs the calling thread may run on
+/// every [`INTERVAL`] on a thread of its own, until stopped.
+pub struct Sampler {
+    stop: Sender<()>,
+    thread: JoinHandle<Vec<u64>>,
+    cpus: Vec<usize>,
+    throttle_before: Option<u64>,
+}
+
+impl Sampler {
+    pub fn start() -> Sampler {
+        let cpus = affinity::available();
+        let throttle_before = throttle_count(&cpus);
+        let (stop, stopped) = mpsc::channel();
+        let thread = {
+            let cpus = cpus.clone();
+            std::thread::spawn(move || {
+                let mut samples = Vec::new();
+                loop {
+                    samples.extend(read(&cpus));
+                    if stopped.recv_timeout(INTERVAL) != Err(RecvTimeoutError::Timeout) {
+                        return samples;
+                    }
+                }
+            })
+        };
+        Sampler {
+            stop,
+            thread,
+            cpus,
+            throttle_before,
+        }
+    }
+
+    /// Take a last sample and summarize them; None if none could be read.
+    pub fn stop(self) -> Option<Stats> {
+        let _ = self.stop.send(());
+        let mut samples = self.thread.join().ok()?;
+        samples.extend(read(&self.cpus));
+        let throttle_events = self
+            .throttle_before
+            .zip(throttle_count(&self.cpus))
+            .map(|(before, after)| after.saturating_sub(before));
+        Some(Stats {
+            min_mhz: *samples.iter().min()?,
+            avg_mhz: samples.iter().sum::<u64>() / samples.len() as u64,
+            max_mhz: *samples.iter().max()?,
+            samples: samples.len(),
+            throttle_events,
+        })
+    }
+}
@@ -0,0 +1,120 @@
+//! Energy measurement (--energy).
+
+#[cfg(target_os = "linux")]
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+use anyhow::Result;
+#[cfg(target_os = "linux")]
+use anyhow::{Context, bail};
+
+/// A RAPL package domain under /sys/class/powercap: its cumulative
+/// counter and the value at which that wraps around to zero.
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+struct Domain {
+    #[cfg(target_os = "linux")]
+    energy_uj: PathBuf,
+    max_energy_range_uj: u64,
+}
+
+static DOMAINS: OnceLock<Vec<Domain>> = OnceLock::new();
+
+/// The package counters at one point in time, in microjoules.
+pub struct Reading(Vec<u64>);
+
+impl Reading {
+    /// Joules used by all packages since `before`, allowing for each
+    /// counter having wrapped at most once.
+    pub fn joules_since(&self, before: &Reading) -> f64 {
+        let domains = DOMAINS.get().map_or(&[][..], Vec::as_slice);
+        let uj: u64 = self
+            .0
+            .iter()
+            .zip(&before.0)
+            .zip(domains)
+            .map(|((&now, &then), d)| {
+                if now >= then {
+                    now - then
+                } else {
+                    now + d.max_energy_range_uj.saturating_sub(then)
+                }
+            })
+            .sum();
+        uj as f64 / 1e6
+    }
+}
+
+/// Find the package domains and check that they can be read; --energy
+/// fails here rather than in the first iteration.
+#[cfg(target_os = "linux")]
+pub fn open() -> Result<()> {
+    const POWERCAP: &str = "/sys/class/powercap";
+    let mut domains = Vec::new();
+    // AMD's RAPL is exposed under the intel-rapl name too. Top-level
+    // zones (intel-rapl:N) are packages; intel-rapl:N:M are the core,
+    // uncore and DRAM parts of one, already counted in its total
+    // except for DRAM.
+    let entries = std::fs::read_dir(POWERCAP)
+        .with_context(|| format!("--energy needs RAPL, and {POWERCAP} cannot be read"))?;
+    let mut zones: Vec<PathBuf> = entries
+        .filter_map(|e| e.ok())
+        .filter(|e| {
+            e.file_name()
+                .to_str()
+                .and_then(|name| name.strip_prefix("intel-rapl:"))
+                .is_some_and(|rest| !rest.contains(':'))
+        })
+        .map(|e| e.path())
+        .collect();
+    zones.sort();
+    for zone in zones {
+        let read = |file: &str| {
+            std::fs::read_to_string(zone.join(file))
+                .map(|s| s.trim().to_string())
+                .with_context(|| {
+                    format!(
+                        "cannot read {}; energy_uj is readable by root only \
+                         since Linux 5.10",
+                        zone.join(file).display()
+                    )
+                })
+        };
+        if !read("name")?.starts_with("package") {
+            continue;
+        }
+        read("energy_uj")?;
+        domains.push(Domain {
+            energy_uj: zone.join("energy_uj"),
+            max_energy_range_uj: read("max_energy_range_uj")?.parse()?,
+        });
+    }
+    if domains.is_empty() {
+        bail!("--energy needs RAPL, and {POWERCAP} has no package domain");
+    }
+    let _ = DOMAINS.set(domains);
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+pub fn read() -> Result<Reading> {
+    let domains = DOMAINS.get().map_or(&[][..], Vec::as_slice);
+    let uj = domains
+        .iter()
+        .map(|d| {
+            let text = std::fs::read_to_string(&d.energy_uj)
+                .with_context(|| format!("cannot read {}", d.energy_uj.display()))?;
+            Ok(text.trim().parse()?)
+        })
+        .collect::<Result<_>>()?;
+    Ok(Reading(uj))
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn open() -> Result<()> {
+    anyhow::bail!("--energy reads RAPL counters, which only Linux exposes")
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn read() -> Result<Reading> {
+    Ok(Reading(Vec::new()))
+}
@@ -0,0 +1,255 @@
+//! The [`Engine`] trait for custom interpreters, and its implementations
+//! for the built-in engines.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use anyhow::{Context, Result, bail};
+
+use super::runner::RegisteredWorkload;
+use super::{Clock, DialectName, param_list, python_engine, starlark_engine};
+
+/// An interpreter to benchmark, registered with
+/// [`Runner::custom_engine`](crate::Runner::custom_engine): every group
+/// prepares the workload once, then calls `run_iter` for each warmup and
+/// measured iteration, and the records carry what it reports.
+/// Its results are checked against the other engines' like theirs are, and
+/// a Python engine's median is the baseline of its ratio records.
+///
+/// The built-in engines implement it in [`StarlarkEngine`] and
+/// [`PythonEngine`], which measure the plain case only; `--engine` runs them
+/// with everything the `bench run` options add.
+pub trait Engine: Send {
+    /// The engine's name and version, recorded as `engine` and
+    /// `engine_version`; the name should not change.
+    fn metadata(&self) -> EngineMetadata;
+
+    /// Load `workload` for the calls that follow, e.g. by parsing its
+    /// script. Nothing of it is timed.
+    fn prepare(&mut self, workload: &Workload) -> Result<()>;
+
+    /// Call the prepared workload's `run` once, timing only the call.
+    fn run_iter(&mut self, call: &Call) -> Result<Iteration>;
+}
+
+/// What an [`Engine`] reports about itself.
+pub struct EngineMetadata {
+    pub name: String,
+    pub version: Option<String>,
+}
+
+/// A workload for [`Engine::prepare`].
+pub struct Workload<'a> {
+    /// Its name, e.g. "json_building".
+    pub name: &'a str,
+    /// --scripts-dir, holding the bundled scripts in `starlark/` and
+    /// `python/`.
+    pub scripts_dir: &'a Path,
+    /// Set for a workload from
+    /// [`Runner::register_workload`](crate::Runner::register_workload).
+    pub(crate) registered: Option<&'a RegisteredWorkload>,
+}
+
+impl<'a> Workload<'a> {
+    /// The bundled workload `name`, or a script of one's own in the same
+    /// layout under `scripts_dir`.
+    pub fn new(name: &'a str, scripts_dir: &'a Path) -> Workload<'a> {
+        Workload {
+            name,
+            scripts_dir,
+            registered: None,
+        }
+    }
+
+    /// The workload's script in `dir` under the scripts directory, named
+    /// after it with `extension`, e.g. `starlark/arithmetic.star`. For a
+    /// registered workload, its own Starlark or Python script instead.
+    pub fn script(&self, dir: &str, extension: &str) -> PathBuf {
+        self.registered
+            .and_then(|w| w.script(dir))
+            .unwrap_or_else(|| {
+                self.scripts_dir
+                    .join(dir)
+                    .join(format!("{}.{extension}", self.name))
+            })
+    }
+}
+
+/// The arguments of one call of the workload's `run(n, seed, **params)`.
+pub struct Call<'a> {
+    pub n: usize,
+    pub seed: u64,
+    /// --param values, passed as keyword arguments.
+    pub params: &'a BTreeMap<String, serde_json::Value>,
+    /// The workload's --input value, passed as the keyword argument `data`.
+    pub input: Option<&'a serde_json::Value>,
+}
+
+/// One timed call, from [`Engine::run_iter`].
+pub struct Iteration {
+    /// How long the call took.
+    pub eval: Duration,
+    /// The checksum `run` returned.
+    pub result: i64,
+}
+
+/// The bench's Starlark evaluator: one fresh module per call, in the
+/// extended dialect with the default call-stack limit.
+#[derive(Default)]
+pub struct StarlarkEngine {
+    prepared: Option<starlark_engine::PreparedScript>,
+}
+
+impl StarlarkEngine {
+    pub fn new() -> StarlarkEngine {
+        StarlarkEngine::default()
+    }
+}
+
+impl Engine for StarlarkEngine {
+    fn metadata(&self) -> EngineMetadata {
+        EngineMetadata {
+            name: "starlark".into(),
+            version: Some(starlark_engine::VERSION.into()),
+        }
+    }
+
+    fn prepare(&mut self, workload: &Workload) -> Result<()> {
+        let path = workload.script("starlark", "star");
+        let body = std::fs::read_to_string(&path)
+            .with_context(|| format!("cannot read {}", path.display()))?;
+        let language = starlark_engine::Language::new(DialectName::Extended, &[]);
+        let load_dir = path.parent().unwrap_or(Path::new("."));
+        let prepared = starlark_engine::prepare(&body, load_dir, &language, None, false, false)
+            .with_context(|| format!("in {}", path.display()))?;
+        self.prepared = Some(prepared);
+        Ok(())
+    }
+
+    fn run_iter(&mut self, call: &Call) -> Result<Iteration> {
+        let prepared = self.prepared.as_ref().context("no workload prepared")?;
+        let call_options = starlark_engine::CallOptions {
+            profile: false,
+            gc: false,
+            calls: 1,
+            parallel: None,
+        };
+        let r = starlark_engine::call_run(
+            prepared,
+            call.n,
+            call.seed,
+            &param_list(call.params),
+            call.input,
+            call_options,
+        )?;
+        Ok(Iteration {
+            eval: r.eval_dur,
+            result: r.result,
+        })
+    }
+}
+
+/// A Python interpreter running the bench's harness as a --python-worker,
+/// started again whenever N or the seed changes.
+pub struct PythonEngine {
+    binary: String,
+    script: Option<PathBuf>,
+    /// For a registered workload, where `_harness` is.
+    pythonpath: Option<PathBuf>,
+    /// With the N and seed it was started for.
+    worker: Option<(usize, u64, python_engine::Worker)>,
+    /// sys.version, once a worker has reported it.
+    version: Option<String>,
+}
+
+impl PythonEngine {
+    /// An engine for `binary`, by name on PATH or by path (`--python`).
+    pub fn new(binary: impl Into<String>) -> PythonEngine {
+        PythonEngine {
+            binary: binary.into(),
+            script: None,
+            pythonpath: None,
+            worker: None,
+            version: None,
+        }
+    }
+}
+
+impl Engine for PythonEngine {
+    fn metadata(&self) -> EngineMetadata {
+        EngineMetadata {
+            name: "python".into(),
+            version: self.version.clone(),
+        }
+    }
+
+    fn prepare(&mut self, workload: &Workload) -> Result<()> {
+        let path = workload.script("python", "py");
+        if !path.is_file() {
+            bail!("Python script not found: {}", path.display());
+        }
+        if let Some((_, _, worker)) = self.worker.take() {
+            worker.close()?;
+        }
+        self.script = Some(path);
+        self.pythonpath = workload
+            .registered
+            .map(|_| workload.scripts_dir.join("python"));
+        Ok(())
+    }
+
+    fn run_iter(&mut self, call: &Call) -> Result<Iteration> {
+        let script = self.script.as_ref().context("no workload prepared")?;
+        if let Some((n, seed, _)) = &self.worker
+            && (*n, *seed) != (call.n, call.seed)
+            && let Some((_, _, worker)) = self.worker.take()
+        {
+            worker.close()?;
+        }
+        let worker = match &mut self.worker {
+            Some((_, _, worker)) => worker,
+            None => {
+                let launch = python_engine::Launch {
+                    binary: &self.binary,
+                    args: &[],
+                    no_gc: false,
+                    tracemalloc: false,
+                    cold: false,
+                    threads: 1,
+                    sandbox: false,
+                    perf: false,
+                    energy: false,
+                    min_sample_ns: None,
+                    evict_bytes: None,
+                    clock: Clock::Wall,
+                    record_clocks: false,
+                    pythonpath: self.pythonpath.as_deref(),
+                    image: None,
+                };
+                let input = call.input.map(serde_json::to_string).transpose()?;
+                let worker = python_engine::Worker::start(
+                    launch,
+                    script,
+                    call.n,
+                    call.seed,
+                    &param_list(call.params),
+                    input.as_deref(),
+                    None,
+                )?;
+                &mut self.worker.insert((call.n, call.seed, worker)).2
+            }
+        };
+        let r = worker.iterate(None)?;
+        self.version = r.version;
+        let iter = r
+            .iters
+            .into_iter()
+            .next()
+            .context("the worker ran no iteration")?;
+        Ok(Iteration {
+            eval: iter.eval_dur,
+            result: iter.result,
+        })
+    }
+}
@@ -0,0 +1,98 @@
+//! Environment checks.
+
+use serde::{Deserialize, Serialize};
+
+/// 1-minute load average above which other work is likely to compete
+/// with the benchmark for CPU time.
+const MAX_LOAD: f64 = 1.0;
+
+/// Host conditions that skew timings, as found before the first
+/// iteration. Each is None where it cannot be determined.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct EnvInfo {
+    /// cpufreq scaling governor of the CPUs in use, e.g. "performance";
+    /// comma-separated when they differ.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub governor: Option<String>,
+    /// Whether turbo boost (intel_pstate or cpufreq boost) is enabled.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub turbo: Option<bool>,
+    /// Whether simultaneous multithreading is active.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub smt: Option<bool>,
+    /// 1-minute load average.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub load_avg: Option<f64>,
+}
+
+impl EnvInfo {
+    pub fn is_empty(&self) -> bool {
+        self.governor.is_none()
+            && self.turbo.is_none()
+            && self.smt.is_none()
+            && self.load_avg.is_none()
+    }
+}
+
+/// Inspect the host; `cpus` are the CPUs the run may use.
+#[cfg(target_os = "linux")]
+pub fn collect(cpus: &[usize]) -> EnvInfo {
+    let read = |path: &str| {
+        std::fs::read_to_string(path)
+            .ok()
+            .map(|s| s.trim().to_string())
+    };
+    let governors: std::collections::BTreeSet<String> = cpus
+        .iter()
+        .filter_map(|cpu| {
+            read(&format!(
+                "/sys/devices/system/cpu/cpu{cpu}/cpufreq/scaling_governor"
+            ))
+        })
+        .collect();
+    let turbo = match read("/sys/devices/system/cpu/intel_pstate/no_turbo") {
+        Some(no_turbo) => Some(no_turbo == "0"),
+        None => read("/sys/devices/system/cpu/cpufreq/boost").map(|b| b == "1"),
+    };
+    EnvInfo {
+        governor: (!governors.is_empty())
+            .then(|| governors.into_iter().collect::<Vec<_>>().join(",")),
+        turbo,
+        smt: read("/sys/devices/system/cpu/smt/active").map(|a| a == "1"),
+        load_avg: read("/proc/loadavg").and_then(|l| l.split_whitespace().next()?.parse().ok()),
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn collect(_cpus: &[usize]) -> EnvInfo {
+    EnvInfo::default()
+}
+
+/// Findings that make timings less reliable, as warning messages.
+pub fn problems(env: &EnvInfo) -> Vec<String> {
+    let mut problems = Vec::new();
+    if let Some(governor) = &env.governor
+        && governor != "performance"
+    {
+        problems.push(format!(
+            "CPU frequency governor is {governor:?}, not \"performance\"; clock speed follows load"
+        ));
+    }
+    if env.turbo == Some(true) {
+        problems
+            .push("turbo boost is enabled; clock speed varies with temperature and load".into());
+    }
+    if env.smt == Some(true) {
+        problems.push(
+            "SMT is active; a sibling hyperthread can share a core with the benchmark".into(),
+        );
+    }
+    if let Some(load) = env.load_avg
+        && load > MAX_LOAD
+    {
+        problems.push(format!(
+            "system load average is {load:.2}; other processes compete for CPU time"
+        ));
+    }
+    problems
+}
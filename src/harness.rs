@@ -0,0 +1,351 @@
+//! Runs a [`Runner`]'s groups as the benchmarks of a `cargo bench` target
+//! (one with `harness = false`), reporting them the way libtest's bencher
+//! does, so that the usual tooling can track them:
+//!
+//! ```no_run
+//! use starlark_bench::{EngineConfig, Runner, WorkloadSpec, harness};
+//!
+//! fn main() -> std::process::ExitCode {
+//!     let runner = Runner::new().engine(EngineConfig::Starlark);
+//!     harness::main(WorkloadSpec::all().into_iter().fold(runner, Runner::workload))
+//! }
+//! ```
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::Write;
+use std::process::ExitCode;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use anyhow::{Result, bail};
+
+use clap::ValueEnum;
+
+use super::{BenchRecord, Runner, Size, resume, stats};
+
+/// Run the benchmarks the target's command line selects: without
+/// `--bench`, as under `cargo test`, each group runs once as a test.
+/// Other arguments are filters, as for libtest.
+pub fn main(runner: Runner) -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let options = match Options::parse(&args) {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("error: {e}");
+            return ExitCode::from(2);
+        }
+    };
+    if options.list {
+        return match list(runner, &options) {
+            Ok(()) => ExitCode::SUCCESS,
+            Err(e) => {
+                eprintln!("Error: {e:?}");
+                ExitCode::from(super::exit_code(&e))
+            }
+        };
+    }
+    let started = Instant::now();
+    let mut out = std::io::stdout().lock();
+    let tally = Arc::new(Mutex::new(Tally::default()));
+    let result = run(runner, &options, &tally, &mut out);
+    let tally = tally.lock().expect("tally lock poisoned");
+    let status = if result.is_ok() { "ok" } else { "FAILED" };
+    let (passed, measured) = if options.bench {
+        (0, tally.reported)
+    } else {
+        (tally.reported, 0)
+    };
+    let _ = writeln!(
+        out,
+        "\ntest result: {status}. {passed} passed; 0 failed; 0 ignored; \
+         {measured} measured; {} filtered out; finished in {:.2}s\n",
+        tally.filtered_out,
+        started.elapsed().as_secs_f64()
+    );
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {e:?}");
+            ExitCode::from(super::exit_code(&e))
+        }
+    }
+}
+
+/// The benchmark name of a group, e.g. "arithmetic/starlark/n=1000/seed=42",
+/// with the N it was scheduled at: with --target-iter-time, its --size
+/// preset's rather than the one chosen, so that filters see the same name.
+fn name(workload: &str, engine: &str, n: usize, seed: u64) -> String {
+    format!("{workload}/{engine}/n={n}/seed={seed}")
+}
+
+fn record_name(record: &BenchRecord) -> String {
+    let n = match Size::from_str(record.size(), false) {
+        Ok(size) if record.target_iter_ns.is_some() => size.to_n(),
+        _ => record.n(),
+    };
+    name(record.workload(), record.engine(), n, record.seed())
+}
+
+fn key_name((engine, workload, _, n, seed): &resume::GroupKey) -> String {
+    name(workload, engine, *n, *seed)
+}
+
+/// The libtest flags a bench target is run with.
+#[derive(Clone, Default)]
+struct Options {
+    bench: bool,
+    exact: bool,
+    list: bool,
+    /// --ignored: there are no ignored benchmarks, so none is selected.
+    ignored: bool,
+    /// --format terse, which --list prints without its summary line.
+    terse: bool,
+    filters: Vec<String>,
+    skip: Vec<String>,
+}
+
+impl Options {
+    fn parse(args: &[String]) -> Result<Options> {
+        let mut options = Options::default();
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let (flag, inline) = match arg.split_once('=') {
+                Some((flag, value)) if flag.starts_with("--") => (flag, Some(value)),
+                _ => (arg.as_str(), None),
+            };
+            let mut value = || match inline {
+                Some(value) => Ok(value.to_string()),
+                None => args
+                    .next()
+                    .cloned()
+                    .ok_or_else(|| anyhow::anyhow!("{flag} needs a value")),
+            };
+            match flag {
+                "--bench" => options.bench = true,
+                "--exact" => options.exact = true,
+                "--list" => options.list = true,
+                "--ignored" => options.ignored = true,
+                "--skip" => options.skip.push(value()?),
+                "--format" => options.terse = value()? == "terse",
+                // Accepted for the tools that pass them, and ignored: the
+                // groups run one at a time, uncaptured, in schedule order.
+                "--logfile" | "--test-threads" | "--color" | "--shuffle-seed" | "-Z" => {
+                    value()?;
+                }
+                "--test"
+                | "--include-ignored"
+                | "--force-run-in-process"
+                | "--exclude-should-panic"
+                | "--nocapture"
+                | "--show-output"
+                | "-q"
+                | "--quiet"
+                | "--report-time"
+                | "--ensure-time"
+                | "--shuffle" => {}
+                flag if flag.starts_with('-') => bail!("unsupported flag {flag:?}"),
+                filter => options.filters.push(filter.to_string()),
+            }
+        }
+        Ok(options)
+    }
+
+    fn selects(&self, name: &str) -> bool {
+        let matches = |f: &String| {
+            if self.exact {
+                name == f
+            } else {
+                name.contains(f.as_str())
+            }
+        };
+        !self.ignored
+            && !self.skip.iter().any(matches)
+            && (self.filters.is_empty() || self.filters.iter().any(matches))
+    }
+}
+
+/// Print the names of the selected groups, measuring none of them.
+fn list(runner: Runner, options: &Options) -> Result<()> {
+    let names = Arc::new(Mutex::new(BTreeSet::new()));
+    let selected = Arc::clone(&names);
+    let filter = options.clone();
+    runner
+        .only(move |key| {
+            let name = key_name(key);
+            if filter.selects(&name) {
+                selected.lock().expect("names lock poisoned").insert(name);
+            }
+            false
+        })
+        .run()?;
+    let names = names.lock().expect("names lock poisoned");
+    let mut out = std::io::stdout().lock();
+    for name in names.iter() {
+        writeln!(out, "{name}: benchmark")?;
+    }
+    if !options.terse {
+        let plural = if names.len() == 1 { "" } else { "s" };
+        writeln!(out, "\n0 tests, {} benchmark{plural}", names.len())?;
+    }
+    Ok(())
+}
+
+#[derive(Default)]
+struct Tally {
+    /// Names of the groups selected to run, for aligning the report.
+    selected: BTreeSet<String>,
+    filtered_out: usize,
+    reported: usize,
+}
+
+/// The measured iteration times of each benchmark, by name, from its
+/// latest attempt (--max-stddev-pct measures a group again).
+#[derive(Default)]
+struct Samples(BTreeMap<String, (u32, Vec<u64>)>);
+
+impl Samples {
+    fn add(&mut self, record: &BenchRecord) {
+        if record.warmup() {
+            return;
+        }
+        let (attempt, eval_ns) = self.0.entry(record_name(record)).or_default();
+        if record.attempt > *attempt {
+            *attempt = record.attempt;
+            eval_ns.clear();
+        }
+        if record.attempt == *attempt {
+            eval_ns.push(record.eval_ns());
+        }
+    }
+}
+
+fn run(
+    runner: Runner,
+    options: &Options,
+    tally: &Arc<Mutex<Tally>>,
+    out: &mut impl Write,
+) -> Result<()> {
+    let runner = if options.bench { runner } else { runner.once() };
+    let filter = options.clone();
+    let counts = Arc::clone(tally);
+    let runner = runner.only(move |key| {
+        let name = key_name(key);
+        let selected = filter.selects(&name);
+        let mut tally = counts.lock().expect("tally lock poisoned");
+        if selected {
+            tally.selected.insert(name);
+        } else {
+            tally.filtered_out += 1;
+        }
+        selected
+    });
+
+    // Groups measured concurrently (--jobs) write their records at the
+    // same time, so each benchmark is only complete once the run is.
+    // Those measured before a failure are reported all the same.
+    let mut samples = Samples::default();
+    let mut result = Ok(());
+    for record in runner.stream() {
+        match record {
+            Ok(record) => samples.add(&record),
+            Err(e) => result = Err(e),
+        }
+    }
+    let mut tally = tally.lock().expect("tally lock poisoned");
+    let width = tally.selected.iter().map(|n| n.len()).max().unwrap_or(0);
+    for (name, (_, eval_ns)) in &samples.0 {
+        tally.reported += 1;
+        if options.bench {
+            let median = stats::median(eval_ns).round() as u64;
+            let min = eval_ns.iter().min().copied().unwrap_or(0);
+            let max = eval_ns.iter().max().copied().unwrap_or(0);
+            writeln!(
+                out,
+                "test {name:<width$} ... bench: {:>11} ns/iter (+/- {})",
+                grouped(median),
+                grouped(max - min)
+            )?;
+        } else {
+            writeln!(out, "test {name:<width$} ... ok")?;
+        }
+    }
+    result
+}
+
+/// `n` with thousands separators, as libtest prints it.
+fn grouped(n: u64) -> String {
+    let digits = n.to_string();
+    let mut out = String::new();
+    for (i, c) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            out.push(',');
+        }
+        out.push(c);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{EngineConfig, WorkloadSpec};
+
+    fn record(n: usize, attempt: u32, eval_ns: u64) -> BenchRecord {
+        BenchRecord {
+            engine: "starlark".into(),
+            workload: "arithmetic".into(),
+            size: "custom".into(),
+            n,
+            seed: 42,
+            attempt,
+            eval_ns,
+            ..BenchRecord::default()
+        }
+    }
+
+    #[test]
+    fn samples_collect_interleaved_groups_from_their_last_attempt() {
+        let mut samples = Samples::default();
+        for r in [
+            record(10, 0, 1),
+            record(20, 0, 2),
+            record(10, 0, 3),
+            record(20, 1, 4),
+            record(20, 0, 5),
+            record(20, 1, 6),
+        ] {
+            samples.add(&r);
+        }
+        let groups: Vec<_> = samples
+            .0
+            .iter()
+            .map(|(name, (_, ns))| (name.as_str(), ns.clone()))
+            .collect();
+        assert_eq!(
+            groups,
+            [
+                ("arithmetic/starlark/n=10/seed=42", vec![1, 3]),
+                ("arithmetic/starlark/n=20/seed=42", vec![4, 6]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_mode_sets_aside_flags_asking_for_more() {
+        let runner = Runner::new()
+            .engine(EngineConfig::Starlark)
+            .workload(WorkloadSpec::builtin("arithmetic").unwrap())
+            .n(100)
+            .n(200)
+            .args(["--no-canary", "--target-ci", "5", "--auto-warmup"])
+            .args(["--jobs", "2"]);
+        let tally = Arc::new(Mutex::new(Tally::default()));
+        let mut out = Vec::new();
+        run(runner, &Options::default(), &tally, &mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "test arithmetic/starlark/n=100/seed=42 ... ok\n\
+             test arithmetic/starlark/n=200/seed=42 ... ok\n"
+        );
+    }
+}
@@ -0,0 +1,36 @@
+//! Input data (--input).
+
+use serde_json::{Value, json};
+
+use super::InputShape;
+
+/// A deterministic value of `n` elements for `(n, seed)`. Values stay
+/// small ints and ASCII strings, so every engine converts them alike.
+pub fn build(shape: InputShape, n: usize, seed: u64) -> Value {
+    let mut x = seed % 2147483648;
+    let mut next = move || {
+        x = (x * 1103515245 + 12345) % 2147483648;
+        x
+    };
+    match shape {
+        InputShape::Ints => Value::Array((0..n).map(|_| json!(next() % 1000000)).collect()),
+        InputShape::Dict => Value::Object(
+            (0..n)
+                .map(|i| (format!("k{i}"), json!(next() % 1000000)))
+                .collect(),
+        ),
+        InputShape::Records => Value::Array(
+            (0..n)
+                .map(|i| {
+                    let v = next();
+                    json!({
+                        "id": i,
+                        "name": format!("item-{i}"),
+                        "score": v % 1000,
+                        "tags": [format!("t{}", v % 7), format!("t{}", v % 11)],
+                    })
+                })
+                .collect(),
+        ),
+    }
+}
@@ -0,0 +1,5968 @@
+//! A benchmark suite comparing the starlark-rust evaluator with CPython on
+//! the same workloads. The `bench` binary is a thin wrapper over [`main`];
+//! [`Runner`] drives the same runs from Rust and hands back the
+//! [`BenchRecord`]s, and [`harness`] reports them from a `cargo bench`
+//! target.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+use std::io::IsTerminal;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use anyhow::{Context, Result, bail};
+use clap::{Args, CommandFactory, FromArgMatches, Parser, Subcommand, ValueEnum};
+use serde::{Deserialize, Serialize};
+use tracing::{debug, error, info, warn};
+
+mod affinity;
+mod alloc_count;
+mod allocator;
+mod baseline;
+mod cache;
+mod calibrate;
+mod canary;
+mod cgroup;
+mod compare;
+mod config;
+mod cpufreq;
+mod energy;
+mod engine;
+mod envcheck;
+pub mod harness;
+mod input;
+mod logging;
+mod merge;
+mod native_profile;
+mod output;
+mod perf;
+mod priority;
+mod progress;
+mod provision;
+mod python_engine;
+mod remote;
+mod report;
+mod results;
+mod resume;
+mod runner;
+mod starlark_engine;
+mod stats;
+
+pub use engine::{Call, Engine, EngineMetadata, Iteration, PythonEngine, StarlarkEngine, Workload};
+pub use runner::{CustomWorkload, EngineConfig, RecordStream, Runner, Script, WorkloadSpec};
+use runner::{ExpectedResult, GroupFilter, RegisteredEngine, RegisteredWorkload};
+
+// ---------------------------------------------------------------------------
+// CLI
+// ---------------------------------------------------------------------------
+
+#[derive(Parser)]
+#[command(
+    name = "bench",
+    about = "Starlark vs CPython benchmark suite",
+    args_conflicts_with_subcommands = true,
+    subcommand_negates_reqs = true
+)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Commands>,
+
+    /// More log output on stderr: -v for debug, -vv for trace.
+    #[arg(short, long, action = clap::ArgAction::Count, global = true)]
+    verbose: u8,
+
+    /// Only log errors.
+    #[arg(short, long, global = true, conflicts_with = "verbose")]
+    quiet: bool,
+
+    /// Without a subcommand, run a benchmark.
+    #[command(flatten)]
+    run: RunArgs,
+}
+
+#[derive(Subcommand)]
+enum Commands {
+    /// Run benchmarks (the default when no subcommand is given).
+    Run(Box<RunArgs>),
+    /// List available engines, workloads and sizes.
+    List(ListArgs),
+    /// Per-workload engine ratios from results files.
+    Compare(ReportArgs),
+    /// Summarize results files: per-engine geometric-mean scores.
+    Report(ReportArgs),
+    /// Save results as a baseline, or check results against one.
+    #[command(subcommand)]
+    Baseline(BaselineCommand),
+    /// Concatenate results files, dropping runs already seen in an earlier input.
+    Merge(MergeArgs),
+    /// Run every workload once on every available engine and check that
+    /// their checksums agree; exit non-zero on divergence.
+    Selftest(SelftestArgs),
+}
+
+#[derive(Subcommand)]
+enum BaselineCommand {
+    /// Store the per-group medians of results files as a baseline.
+    Save(BaselineArgs),
+    /// Compare results against a stored baseline; exit non-zero on regressions.
+    Check(BaselineArgs),
+}
+
+#[derive(Args)]
+struct RunArgs {
+    /// Engine(s) to benchmark, comma-separated or repeated, or "all".
+    /// When both run, a starlark/python ratio record follows their summaries.
+    /// [possible values: starlark, python, all]
+    #[arg(long, value_delimiter = ',', value_parser = selection::<EngineName>)]
+    engine: Vec<Selection<EngineName>>,
+
+    /// Workload(s) to run, comma-separated or repeated, or "all".
+    /// [possible values: arithmetic, data_structures, string_parsing,
+    /// json_building, function_calls, typed_function_calls, ffi_calls,
+    /// marshalling, parallel_chunks, all]
+    #[arg(long, value_delimiter = ',', value_parser = selection::<WorkloadName>)]
+    workload: Vec<Selection<WorkloadName>>,
+
+    /// Only run workloads carrying at least one of these tags (all workloads
+    /// when --workload is not given).
+    #[arg(long, value_delimiter = ',')]
+    tags: Vec<Tag>,
+
+    /// Skip workloads carrying any of these tags.
+    #[arg(long, value_delimiter = ',')]
+    skip_tags: Vec<Tag>,
+
+    /// Predefined problem size(s), comma-separated, or "all" (overridden by --n).
+    /// [possible values: S, M, L, all]
+    #[arg(long, default_value = "M", value_delimiter = ',', value_parser = selection::<Size>)]
+    size: Vec<Selection<Size>>,
+
+    /// Measurement iterations (excluding warmup).
+    #[arg(long, default_value_t = 10)]
+    iters: u32,
+
+    /// Warmup iterations (results printed but flagged).
+    #[arg(long, default_value_t = 3)]
+    warmup: u32,
+
+    /// RNG seed for deterministic workloads.
+    #[arg(long, default_value_t = 42)]
+    seed: u64,
+
+    /// Run every group once per seed, comma-separated or repeated, so results
+    /// aggregate over several input distributions.
+    #[arg(long, value_delimiter = ',', conflicts_with = "seed")]
+    seeds: Vec<u64>,
+
+    /// Run every group with this many consecutive seeds starting at --seed.
+    #[arg(long, conflicts_with = "seeds", value_parser = clap::value_parser!(u64).range(1..))]
+    seed_count: Option<u64>,
+
+    /// Override the N parameter directly; comma-separated or repeated for
+    /// several values.
+    #[arg(long, value_delimiter = ',')]
+    n: Vec<usize>,
+
+    /// Scaling study: run every N in a range, either geometric
+    /// ("1000:1000000:x10") or linear ("10000:50000:10000").
+    #[arg(long, value_parser = parse_sweep, conflicts_with = "n")]
+    n_sweep: Option<NSweep>,
+
+    /// Choose N for every engine and workload so that one iteration takes
+    /// about this long (e.g. "100ms"), starting from the --size preset;
+    /// records carry the chosen N.
+    #[arg(long, value_name = "SECS", value_parser = parse_secs, conflicts_with_all = ["n", "n_sweep"])]
+    target_iter_time: Option<Duration>,
+
+    /// Extra named parameter passed to every workload as `run(n, seed,
+    /// NAME=VALUE)`; repeat for several. VALUE is an integer, float, true,
+    /// false or else a string.
+    #[arg(long, value_name = "NAME=VALUE", value_parser = parse_param)]
+    param: Vec<Param>,
+
+    /// Python interpreter binary; repeat to measure each in turn, labeling
+    /// the records "python[BINARY]".
+    #[arg(long, value_name = "PATH", default_value = "python3")]
+    python: Vec<String>,
+
+    /// Python versions to measure in turn instead of --python, e.g.
+    /// "3.9,3.12", each run as `pythonVERSION` and labeled
+    /// "python[VERSION]".
+    #[arg(
+        long,
+        value_name = "VERSIONS",
+        value_delimiter = ',',
+        conflicts_with = "python"
+    )]
+    pythons: Vec<String>,
+
+    /// Python versions to install with uv into --python-cache-dir, unless
+    /// they are there already, and measure instead of --python, e.g. "3.12"
+    /// or "3.12.7"; labeled like --pythons.
+    #[arg(
+        long,
+        value_name = "VERSIONS",
+        value_delimiter = ',',
+        conflicts_with_all = ["python", "pythons", "docker_image"]
+    )]
+    provision_python: Vec<String>,
+
+    /// Where --provision-python keeps its interpreters [default:
+    /// starlark-bench/python in the user's cache directory].
+    #[arg(long, value_name = "DIR", requires = "provision_python")]
+    python_cache_dir: Option<PathBuf>,
+
+    /// The --provision-python interpreters as (version, binary), filled in
+    /// before the run.
+    #[arg(skip)]
+    provisioned: Vec<(String, String)>,
+
+    /// With --remote, the options given on the command line, minus the
+    /// local ones, as the remote bench is to be passed them.
+    #[arg(skip)]
+    forwarded: Vec<String>,
+
+    /// Set by [`Runner`], which directs the records to itself instead of
+    /// --output or stdout.
+    #[arg(skip)]
+    library: bool,
+
+    /// Engines added with [`Runner::custom_engine`], measured after the
+    /// built-in ones.
+    #[arg(skip)]
+    custom_engines: Vec<Arc<RegisteredEngine>>,
+
+    /// Workloads added with [`Runner::register_workload`], run after the
+    /// bundled ones.
+    #[arg(skip)]
+    custom_workloads: Vec<RegisteredWorkload>,
+
+    /// Set by [`harness`]: only the groups it returns true for are
+    /// measured.
+    #[arg(skip)]
+    only: Option<Arc<GroupFilter>>,
+
+    /// Options for the Python interpreter, split on whitespace and passed
+    /// before the script, e.g. "-O -X int_max_str_digits=0".
+    #[arg(long, value_name = "ARGS", allow_hyphen_values = true)]
+    python_args: Option<String>,
+
+    /// Disable Python's cyclic garbage collector in the harness before the
+    /// first iteration; reference counting still frees memory.
+    #[arg(long, default_value_t = false)]
+    python_no_gc: bool,
+
+    /// (Python only) Compile the script's source and execute its top level
+    /// again every iteration before calling `run`, timing each phase; the
+    /// counterpart of --starlark-cold. Records are labeled "python-cold".
+    #[arg(long, default_value_t = false)]
+    python_cold: bool,
+
+    /// (Python only) Call `run` from this many threads at once in every
+    /// iteration, the counterpart of --threads for free-threaded CPython
+    /// builds. Records are labeled "python-threads".
+    #[arg(long, value_name = "K", default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..))]
+    python_threads: u32,
+
+    /// Run each Python group in one long-lived interpreter that runs an
+    /// iteration per request, instead of a subprocess per batch, so that
+    /// --timeout, --interleave and --iter-cooldown apply to single
+    /// iterations and RSS is sampled after each.
+    #[arg(long, default_value_t = false)]
+    python_worker: bool,
+
+    /// Run the Python subprocesses without network access and with their
+    /// scripts directory read-only: in user, network and mount namespaces
+    /// on Linux, under sandbox-exec on macOS.
+    #[arg(long, default_value_t = false, conflicts_with = "docker_image")]
+    python_sandbox: bool,
+
+    /// How Python workloads are timed: by the bench's own `_harness.py`, or
+    /// by pyperf, which calibrates, warms up, spawns its worker processes
+    /// and decides the number of values itself.
+    #[arg(long, value_name = "HARNESS", default_value = "builtin")]
+    python_harness: PythonHarness,
+
+    /// Root directory for workload scripts.
+    #[arg(long)]
+    scripts_dir: Option<PathBuf>,
+
+    /// Write records to this file instead of stdout.
+    #[arg(long)]
+    output: Option<PathBuf>,
+
+    /// Suite configuration file (TOML). Flags given on the command line
+    /// override its values.
+    #[arg(long)]
+    config: Option<PathBuf>,
+
+    /// (Starlark only) Whether each iteration evaluates `run` in a fresh
+    /// Module or all iterations share one. "reuse" measures "hot interpreter"
+    /// performance, without the per-call heap setup and teardown.
+    #[arg(long, value_name = "MODE", default_value = "fresh")]
+    starlark_module_mode: ModuleMode,
+
+    /// (Starlark only) Shorthand for --starlark-module-mode reuse.
+    #[arg(long, default_value_t = false, conflicts_with = "starlark_module_mode")]
+    reuse_module: bool,
+
+    /// (Starlark only) What each iteration times: calling `run`, or parsing
+    /// the script again without evaluating it ("parse"), or parsing and
+    /// compiling it ("compile"), whose records are labeled
+    /// "starlark-parse" and "starlark-compile".
+    #[arg(long, value_name = "PHASE", default_value = "run")]
+    phase: Phase,
+
+    /// (Starlark only) Deepest Starlark call stack allowed, in frames;
+    /// starlark-rust's default is 50. Deeper recursion fails the iteration.
+    #[arg(long, value_name = "FRAMES", value_parser = clap::value_parser!(u32).range(1..))]
+    starlark_max_callstack: Option<u32>,
+
+    /// (Starlark only) Run the measurements on threads with this much native
+    /// stack, in MiB. Every Starlark call nests on it, so deep recursion
+    /// needs more than the platform default.
+    #[arg(long, value_name = "MIB", value_parser = clap::value_parser!(u32).range(1..))]
+    starlark_thread_stack: Option<u32>,
+
+    /// (Starlark only) Call the frozen `run` from this many threads at once
+    /// in every iteration, each with its own Module and Evaluator. Records
+    /// are labeled "starlark-threads" and report aggregate throughput.
+    #[arg(long, value_name = "K", default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..))]
+    threads: u32,
+
+    /// (Starlark only) Record the bytes and values each iteration allocates
+    /// on the Starlark heap, and the bytes its result retains.
+    #[arg(long, default_value_t = false)]
+    heap_profile: bool,
+
+    /// Record each iteration's peak allocation in both engines: Python traces
+    /// its allocations with tracemalloc, which slows it down, and Starlark
+    /// turns on --heap-profile.
+    #[arg(long, value_name = "MODE")]
+    mem: Option<MemMode>,
+
+    /// (Starlark only) Collect garbage on the Starlark heap after every
+    /// iteration, outside the timed call, and record how long it took and
+    /// what survived.
+    #[arg(long, default_value_t = false)]
+    starlark_gc: bool,
+
+    /// (Starlark only) Redo the whole script every iteration: parse, evaluate
+    /// and freeze the module, then call `run`, timing each phase. Models
+    /// evaluating a BUILD file once; records are labeled "starlark-cold".
+    #[arg(long, default_value_t = false)]
+    starlark_cold: bool,
+
+    /// (Starlark only) Count the bytecode instructions of one extra,
+    /// unrecorded call of each group into its summary.
+    #[arg(long, default_value_t = false)]
+    bytecode_stats: bool,
+
+    /// Build a value of n elements in Rust and pass it to every workload's
+    /// `run()` as the `data` keyword argument, recording the conversion time
+    /// separately. `marshalling` gets "records" unless this is given.
+    #[arg(long, value_name = "SHAPE")]
+    input: Option<InputShape>,
+
+    /// (Starlark only) Run the static typechecker over each script after
+    /// parsing it and record its time in the first iteration's record.
+    #[arg(long, default_value_t = false)]
+    typecheck: bool,
+
+    /// Profile one extra, unrecorded call of each group after measuring it
+    /// and write the profile to --profile-dir; comma-separated or repeated.
+    #[arg(
+        long,
+        value_name = "KINDS",
+        value_delimiter = ',',
+        conflicts_with = "remote"
+    )]
+    profile: Vec<ProfileKind>,
+
+    /// Directory --profile writes its files to.
+    #[arg(long, value_name = "DIR", default_value = "profiles")]
+    profile_dir: PathBuf,
+
+    /// (Starlark only) Language dialect to parse the scripts with; "custom"
+    /// enables only the --dialect-features listed.
+    #[arg(long, default_value = "extended")]
+    dialect: DialectName,
+
+    /// (Starlark only) Comma-separated features to enable on top of
+    /// --dialect, or to disable with a "no-" prefix (e.g.
+    /// "f-strings,no-lambda"). [possible values: def, lambda, load,
+    /// keyword-only-args, positional-only-args, types, top-level-stmt,
+    /// f-strings, sets]
+    #[arg(long, value_name = "FEATURES", value_delimiter = ',', value_parser = parse_dialect_toggle)]
+    dialect_features: Vec<DialectToggle>,
+
+    /// (Starlark only) Optional starlark-rust libraries to add to the
+    /// globals, comma-separated or repeated, or "all".
+    /// [possible values: json, struct, record, enum, namespace, map, filter,
+    /// partial, typing, print, all]
+    #[arg(long, value_name = "LIBS", value_delimiter = ',', value_parser = selection::<StarlarkLib>)]
+    starlark_lib: Vec<Selection<StarlarkLib>>,
+
+    /// (Starlark only) Measure every Starlark group once per evaluator
+    /// configuration, each NAME=OPTIONS with the OPTIONS joined by "+" and
+    /// empty for the defaults, e.g. "base=,nogc=no-gc". Records are labeled
+    /// "starlark[NAME]". [possible values: static-typecheck, no-gc,
+    /// verbose-gc]
+    #[arg(long, value_name = "NAME=OPTIONS", value_delimiter = ',', value_parser = parse_eval_config)]
+    eval_config: Vec<EvalConfig>,
+
+    /// Alternate measurement iterations between engines (ABAB…) instead of
+    /// running each engine's group to completion, so slow environmental
+    /// drift affects all engines alike.
+    #[arg(long, default_value_t = false)]
+    interleave: bool,
+
+    /// Run the (engine, workload, size) groups in a random order derived
+    /// from --seed, recording each group's `position` in its records.
+    #[arg(long, default_value_t = false)]
+    shuffle: bool,
+
+    /// Abort an iteration that runs longer than this many seconds, emit a
+    /// timeout record for its group and move on to the next group.
+    #[arg(long, value_parser = parse_secs)]
+    timeout: Option<Duration>,
+
+    /// Don't show the progress bar (it is also hidden when stderr is not a
+    /// terminal, or when records go to the same terminal).
+    #[arg(long, default_value_t = false)]
+    no_progress: bool,
+
+    /// Run this many groups concurrently, each worker pinned to its own set
+    /// of physical cores.
+    #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u64).range(1..))]
+    jobs: u64,
+
+    /// Pin the benchmark, and the Python subprocesses it spawns, to these
+    /// CPUs ("2,3" or "0-3,6"). With --jobs, the workers split this set.
+    #[arg(long, value_name = "CORE_LIST", value_parser = parse_cpu_list)]
+    pin_cpu: Option<CpuList>,
+
+    /// Run at this nice value (-20 to 19); negative values need
+    /// CAP_SYS_NICE or a raised RLIMIT_NICE.
+    #[arg(long, allow_hyphen_values = true, value_parser = clap::value_parser!(i32).range(-20..=19))]
+    nice: Option<i32>,
+
+    /// Run under SCHED_FIFO at this real-time priority (1-99, default 1);
+    /// needs CAP_SYS_NICE or a raised RLIMIT_RTPRIO.
+    #[arg(long, value_name = "PRIO", num_args = 0..=1, default_missing_value = "1",
+          value_parser = clap::value_parser!(u32).range(1..=99), conflicts_with = "nice")]
+    realtime: Option<u32>,
+
+    /// Refuse to run when the environment checks find a problem (frequency
+    /// governor, turbo boost, SMT, system load) instead of only warning.
+    #[arg(long, default_value_t = false)]
+    strict_env: bool,
+
+    /// (Linux, cgroup v2) Run each Python subprocess in its own cgroup, and
+    /// the benchmark itself in another, and record their memory.peak and
+    /// cpu.stat. The cgroups are created under PARENT, by default the root
+    /// of the cgroup2 mount; creating them usually needs root.
+    #[arg(long, value_name = "PARENT")]
+    cgroup: Option<Option<PathBuf>>,
+
+    /// Run the Python interpreter (--python) inside this container image,
+    /// with the workload scripts mounted read-only and no network.
+    #[arg(long, value_name = "IMAGE", conflicts_with = "cgroup")]
+    docker_image: Option<String>,
+
+    /// (--cgroup) Limit each measured process to this many CPUs' worth of
+    /// time (cpu.max), e.g. 1 or 0.5.
+    #[arg(long, value_name = "CPUS", requires = "cgroup", value_parser = parse_cpus)]
+    cgroup_cpus: Option<f64>,
+
+    /// (--cgroup) Limit each measured process to this much memory in MiB
+    /// (memory.max); a process exceeding it is killed.
+    #[arg(long, value_name = "MIB", requires = "cgroup",
+          value_parser = clap::value_parser!(u64).range(1..))]
+    cgroup_memory: Option<u64>,
+
+    /// (Linux or macOS, built with `--features perf`) Count instructions and
+    /// cycles, and on Linux branch and cache misses: per iteration for
+    /// Starlark, per subprocess for Python.
+    #[arg(long, default_value_t = false)]
+    perf_counters: bool,
+
+    /// (Linux) Read the RAPL package energy counters around each measurement
+    /// and report joules per iteration. Usually needs root.
+    #[arg(long, default_value_t = false)]
+    energy: bool,
+
+    /// (Linux) Sample the CPU frequency while each group measures and
+    /// record its minimum, mean and maximum on the summary.
+    #[arg(long, default_value_t = false)]
+    cpu_freq: bool,
+
+    /// Frequency spread (percent of the maximum) above which a --cpu-freq
+    /// group is flagged freq_unstable.
+    #[arg(long, value_name = "PCT", value_parser = parse_pct, default_value = "10")]
+    freq_threshold: f64,
+
+    /// (Starlark only) Take the calibrated timer and call overhead off every
+    /// eval_ns, for very small N where it is a large part of the time.
+    #[arg(long, default_value_t = false)]
+    subtract_overhead: bool,
+
+    /// Time run() calls faster than NS nanoseconds in samples of as many
+    /// back-to-back calls as it takes to last NS, and report eval_ns per
+    /// call. The number is chosen from one untimed call per group (per
+    /// Python subprocess).
+    #[arg(long, value_name = "NS", value_parser = clap::value_parser!(u64).range(1..))]
+    min_sample_ns: Option<u64>,
+
+    /// Sleep this many milliseconds between groups, so that thermals settle
+    /// before the next workload.
+    #[arg(long, value_name = "MS")]
+    cooldown: Option<u64>,
+
+    /// Sleep this many milliseconds before each measurement round: every
+    /// Starlark iteration, every Python subprocess.
+    #[arg(long, value_name = "MS")]
+    iter_cooldown: Option<u64>,
+
+    /// What the CPU caches hold when an iteration starts: whatever the
+    /// previous one left ("warm"), or nothing of the workload's ("cold"),
+    /// by writing through a buffer twice the size of the largest cache
+    /// before every iteration.
+    #[arg(long, value_name = "STATE", default_value = "warm")]
+    cache_state: CacheState,
+
+    /// (Linux, root, --cache-state cold) Also drop the page cache, dentries
+    /// and inodes before each measurement round.
+    #[arg(long, default_value_t = false)]
+    drop_fs_caches: bool,
+
+    /// The clock eval_ns is read from: elapsed time ("wall"), the process's
+    /// CPU time ("cpu") or the evaluating thread's CPU time ("thread").
+    #[arg(long, value_name = "CLOCK", default_value = "wall")]
+    clock: Clock,
+
+    /// Also record each iteration's time on every clock, as `clocks`.
+    #[arg(long, default_value_t = false)]
+    record_clocks: bool,
+
+    /// Check the selected scripts and print the planned groups with an
+    /// estimated runtime, without measuring anything.
+    #[arg(long, default_value_t = false)]
+    dry_run: bool,
+
+    /// Continue the interrupted run with this run_id: read --output, skip
+    /// the groups it already completed and append the rest to the file.
+    #[arg(long, value_name = "RUN_ID")]
+    resume: Option<String>,
+
+    /// Run the benchmark on this machine over SSH instead ("user@host"),
+    /// writing its records here. The scripts and --config are copied over;
+    /// `bench` must be installed there.
+    #[arg(long, value_name = "HOST", conflicts_with = "resume")]
+    remote: Option<String>,
+
+    /// (--remote) The bench executable on the remote machine.
+    #[arg(
+        long,
+        value_name = "PATH",
+        default_value = "bench",
+        requires = "remote"
+    )]
+    remote_bench: String,
+
+    /// (Python only) Retry a failed subprocess up to this many times before
+    /// aborting the run.
+    #[arg(long, default_value_t = 0)]
+    retries: u32,
+
+    /// Coefficient of variation (percent) above which a measurement group
+    /// is flagged as noisy.
+    #[arg(long, default_value_t = 5.0)]
+    noise_threshold: f64,
+
+    /// Don't time the native canary loop before and after the run, which
+    /// records the machine's noise floor.
+    #[arg(long, default_value_t = false)]
+    no_canary: bool,
+
+    /// Adaptive mode: keep measuring until the 95% confidence interval of the
+    /// median is within this many percent of the median (e.g. "2%").
+    /// Replaces the fixed --iters count.
+    #[arg(long, value_parser = parse_pct, conflicts_with = "iters")]
+    target_ci: Option<f64>,
+
+    /// Keep measuring until the group's measurement iterations add up to at
+    /// least this much eval time (e.g. "2s"); --iters stays the minimum
+    /// count.
+    #[arg(long, value_name = "SECS", value_parser = parse_secs, conflicts_with = "target_ci")]
+    min_time: Option<Duration>,
+
+    /// Upper bound on measurement iterations in --target-ci mode.
+    #[arg(long, default_value_t = 200)]
+    max_iters: u32,
+
+    /// Replace the fixed --warmup count with steady-state detection: warm up
+    /// until the medians of two consecutive windows of timings agree within
+    /// --noise-threshold.
+    #[arg(long, default_value_t = false, conflicts_with = "warmup")]
+    auto_warmup: bool,
+
+    /// Upper bound on warmup iterations in --auto-warmup mode.
+    #[arg(long, default_value_t = 50)]
+    max_warmup: u32,
+
+    /// Variance budget: re-measure a group whose relative standard deviation
+    /// (CV) exceeds this many percent, and exit non-zero if it still does
+    /// after --remeasure attempts.
+    #[arg(long, value_parser = parse_pct)]
+    max_stddev_pct: Option<f64>,
+
+    /// Re-measurement attempts per group with --max-stddev-pct.
+    #[arg(long, default_value_t = 2)]
+    remeasure: u32,
+
+    /// Outcomes that fail the run, comma-separated; the others only warn.
+    /// Groups over --max-stddev-pct fail regardless.
+    #[arg(
+        long,
+        value_name = "CLASSES",
+        value_delimiter = ',',
+        default_value = "checksum-mismatch,regression"
+    )]
+    fail_on: Vec<FailureClass>,
+}
+
+#[derive(Args)]
+struct MergeArgs {
+    /// Output file (overwritten).
+    output: PathBuf,
+
+    /// Input results files (JSON lines).
+    #[arg(required = true)]
+    inputs: Vec<PathBuf>,
+}
+
+#[derive(Args)]
+struct ReportArgs {
+    /// Results files (JSON lines); reads stdin if none are given.
+    files: Vec<PathBuf>,
+
+    /// Engine the others are compared against.
+    #[arg(long, default_value = "python")]
+    baseline_engine: String,
+
+    /// Emit score records as JSON lines instead of a text table.
+    #[arg(long, default_value_t = false)]
+    json: bool,
+}
+
+#[derive(Args)]
+struct ListArgs {
+    /// Python interpreter binary.
+    #[arg(long, default_value = "python3")]
+    python: String,
+
+    /// Root directory for workload scripts.
+    #[arg(long)]
+    scripts_dir: Option<PathBuf>,
+}
+
+#[derive(Args)]
+struct SelftestArgs {
+    /// Python interpreter binary.
+    #[arg(long, default_value = "python3")]
+    python: String,
+
+    /// Root directory for workload scripts.
+    #[arg(long)]
+    scripts_dir: Option<PathBuf>,
+
+    /// Problem size to check at.
+    #[arg(long, default_value = "S")]
+    size: Size,
+
+    /// RNG seed passed to every workload.
+    #[arg(long, default_value_t = 42)]
+    seed: u64,
+}
+
+#[derive(Args)]
+struct BaselineArgs {
+    /// Results files (JSON lines); reads stdin if none are given.
+    files: Vec<PathBuf>,
+
+    /// Baseline file.
+    #[arg(long, default_value = "baseline.jsonl")]
+    baseline: PathBuf,
+
+    /// (check) Median slowdown, in percent, that counts as a regression.
+    #[arg(long, default_value = "5", value_parser = parse_pct)]
+    threshold: f64,
+
+    /// (check) Outcomes that fail the check, comma-separated; only
+    /// "regression" applies here.
+    #[arg(
+        long,
+        value_name = "CLASSES",
+        value_delimiter = ',',
+        default_value = "checksum-mismatch,regression"
+    )]
+    fail_on: Vec<FailureClass>,
+}
+
+/// A command-line choice of one value or every value of an enum.
+#[derive(Clone)]
+enum Selection<T> {
+    All,
+    One(T),
+}
+
+fn selection<T: ValueEnum>(s: &str) -> Result<Selection<T>, String> {
+    if s.eq_ignore_ascii_case("all") {
+        return Ok(Selection::All);
+    }
+    T::from_str(s, true).map(Selection::One)
+}
+
+/// Expand selections into distinct values, preserving the order given.
+fn expand<T: ValueEnum + Clone + PartialEq>(selections: &[Selection<T>]) -> Vec<T> {
+    let mut out: Vec<T> = Vec::new();
+    for sel in selections {
+        let values = match sel {
+            Selection::All => T::value_variants().to_vec(),
+            Selection::One(v) => vec![v.clone()],
+        };
+        for v in values {
+            if !out.contains(&v) {
+                out.push(v);
+            }
+        }
+    }
+    out
+}
+
+/// Accept a percentage with or without a trailing `%`.
+fn parse_pct(s: &str) -> Result<f64, String> {
+    let v: f64 = s
+        .trim()
+        .trim_end_matches('%')
+        .parse()
+        .map_err(|_| format!("invalid percentage: {s:?}"))?;
+    if v <= 0.0 {
+        return Err(format!("percentage must be positive: {s:?}"));
+    }
+    Ok(v)
+}
+
+/// The N values of an `--n-sweep` range, in ascending order.
+#[derive(Clone)]
+struct NSweep(Vec<usize>);
+
+/// A `--param NAME=VALUE` workload parameter.
+#[derive(Clone)]
+struct Param {
+    name: String,
+    value: serde_json::Value,
+}
+
+/// Parse `NAME=VALUE`. Numbers and booleans keep their type; anything else,
+/// or a JSON string such as `'"5"'`, is a string.
+fn parse_param(s: &str) -> Result<Param, String> {
+    let (name, value) = s
+        .split_once('=')
+        .ok_or_else(|| format!("invalid parameter {s:?}, expected NAME=VALUE"))?;
+    let mut chars = name.chars();
+    let identifier = chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if !identifier {
+        return Err(format!("parameter name {name:?} is not an identifier"));
+    }
+    if name == "n" || name == "seed" {
+        return Err(format!(
+            "parameter name {name:?} is reserved; use --n or --seed"
+        ));
+    }
+    let value = match serde_json::from_str(value) {
+        Ok(v @ (serde_json::Value::Number(_) | serde_json::Value::Bool(_))) => v,
+        Ok(v @ serde_json::Value::String(_)) => v,
+        _ => serde_json::Value::String(value.to_string()),
+    };
+    Ok(Param {
+        name: name.to_string(),
+        value,
+    })
+}
+
+/// The --param values as recorded, by name.
+fn param_map(params: &[Param]) -> BTreeMap<String, serde_json::Value> {
+    params
+        .iter()
+        .map(|p| (p.name.clone(), p.value.clone()))
+        .collect()
+}
+
+/// The --param values of `param_map` again.
+fn param_list(params: &BTreeMap<String, serde_json::Value>) -> Vec<Param> {
+    params
+        .iter()
+        .map(|(name, value)| Param {
+            name: name.clone(),
+            value: value.clone(),
+        })
+        .collect()
+}
+
+/// Parse `start:stop:xFACTOR` (geometric) or `start:stop:STEP` (linear).
+fn parse_sweep(s: &str) -> Result<NSweep, String> {
+    let bad = || format!("invalid sweep {s:?}, expected start:stop:xFACTOR or start:stop:STEP");
+    let parts: Vec<&str> = s.trim().split(':').collect();
+    let [start, stop, step] = parts[..] else {
+        return Err(bad());
+    };
+    let start: usize = start.parse().map_err(|_| bad())?;
+    let stop: usize = stop.parse().map_err(|_| bad())?;
+    if start == 0 || start > stop {
+        return Err(format!("sweep needs 0 < start <= stop: {s:?}"));
+    }
+    let mut ns = Vec::new();
+    if let Some(factor) = step.strip_prefix('x') {
+        let factor: f64 = factor.parse().map_err(|_| bad())?;
+        if factor <= 1.0 {
+            return Err(format!("sweep factor must be greater than 1: {s:?}"));
+        }
+        let mut x = start as f64;
+        while x.round() as usize <= stop {
+            let n = x.round() as usize;
+            if ns.last() != Some(&n) {
+                ns.push(n);
+            }
+            x *= factor;
+        }
+    } else {
+        let step: usize = step.parse().map_err(|_| bad())?;
+        if step == 0 {
+            return Err(format!("sweep step must be positive: {s:?}"));
+        }
+        ns.extend((start..=stop).step_by(step));
+    }
+    Ok(NSweep(ns))
+}
+
+/// CPU indices given to --pin-cpu, ascending and without duplicates.
+#[derive(Clone)]
+struct CpuList(Vec<usize>);
+
+/// Parse a comma-separated list of CPUs and inclusive ranges, e.g. "0-3,6".
+fn parse_cpu_list(s: &str) -> Result<CpuList, String> {
+    let bad = || format!("invalid CPU list {s:?}, expected e.g. \"2,3\" or \"0-3,6\"");
+    let mut cpus = Vec::new();
+    for part in s.trim().split(',') {
+        let (first, last) = match part.trim().split_once('-') {
+            Some((a, b)) => (a.trim(), b.trim()),
+            None => (part.trim(), part.trim()),
+        };
+        let first: usize = first.parse().map_err(|_| bad())?;
+        let last: usize = last.parse().map_err(|_| bad())?;
+        if first > last {
+            return Err(bad());
+        }
+        cpus.extend(first..=last);
+    }
+    cpus.sort_unstable();
+    cpus.dedup();
+    Ok(CpuList(cpus))
+}
+
+fn parse_cpus(s: &str) -> Result<f64, String> {
+    match s.trim().parse::<f64>() {
+        Ok(v) if v > 0.0 && v.is_finite() => Ok(v),
+        _ => Err(format!("expected a positive number of CPUs: {s:?}")),
+    }
+}
+
+/// Seconds, optionally suffixed "s", or milliseconds suffixed "ms".
+fn parse_secs(s: &str) -> Result<Duration, String> {
+    let (digits, scale) = match s.trim().strip_suffix("ms") {
+        Some(ms) => (ms, 1e-3),
+        None => (s.trim().trim_end_matches('s'), 1.0),
+    };
+    let v: f64 = digits
+        .parse::<f64>()
+        .map(|v| v * scale)
+        .map_err(|_| format!("invalid duration in seconds: {s:?}"))?;
+    if !(v > 0.0 && v.is_finite()) {
+        return Err(format!("duration must be positive: {s:?}"));
+    }
+    Ok(Duration::from_secs_f64(v))
+}
+
+#[derive(Clone, PartialEq, ValueEnum)]
+enum EngineName {
+    Starlark,
+    Python,
+    /// The engines registered with [`Runner::custom_engine`].
+    #[value(skip)]
+    Custom,
+}
+
+#[derive(Clone, PartialEq, ValueEnum)]
+#[clap(rename_all = "snake_case")]
+enum WorkloadName {
+    Arithmetic,
+    DataStructures,
+    StringParsing,
+    JsonBuilding,
+    FunctionCalls,
+    TypedFunctionCalls,
+    FfiCalls,
+    Marshalling,
+    ParallelChunks,
+}
+
+/// Workload categories for --tags / --skip-tags.
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+enum Tag {
+    /// Integer and float arithmetic.
+    Numeric,
+    /// String building, splitting and formatting.
+    String,
+    /// Allocates many short-lived lists, dicts or strings.
+    Allocation,
+    /// A single hot operation in a loop.
+    Micro,
+    /// Mixes several kinds of work, closer to real scripts.
+    Macro,
+    /// Uses type annotations (needs the `types` dialect feature).
+    Typed,
+    /// Calls functions implemented in the host language.
+    Native,
+    /// Splits its work over threads with `run_parallel`, for --threads and
+    /// --python-threads.
+    Parallel,
+}
+
+impl std::fmt::Display for Tag {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let value = self.to_possible_value().expect("no skipped variants");
+        f.write_str(value.get_name())
+    }
+}
+
+/// Outcomes that fail a run with --fail-on, each with its own exit code so
+/// CI can tell them apart. Any other error exits with 1, usage errors with 2.
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+enum FailureClass {
+    /// Engines computed different results for the same group (exit 3).
+    ChecksumMismatch,
+    /// A median is slower than the baseline by more than --threshold (exit 4).
+    Regression,
+    /// A group was flagged noisy or exceeded --max-stddev-pct (exit 5).
+    Noisy,
+}
+
+impl FailureClass {
+    fn exit_code(self) -> u8 {
+        match self {
+            FailureClass::ChecksumMismatch => 3,
+            FailureClass::Regression => 4,
+            FailureClass::Noisy => 5,
+        }
+    }
+
+    fn from_exit_code(code: i32) -> Option<Self> {
+        FailureClass::value_variants()
+            .iter()
+            .copied()
+            .find(|class| i32::from(class.exit_code()) == code)
+    }
+}
+
+impl std::fmt::Display for FailureClass {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let value = self.to_possible_value().expect("no skipped variants");
+        f.write_str(value.get_name())
+    }
+}
+
+/// Profilers for --profile.
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+enum ProfileKind {
+    /// Starlark: time spent on each script line (statement profile, CSV).
+    StarlarkTime,
+    /// Starlark: time per call stack, as folded stacks for flamegraph.pl.
+    StarlarkFlame,
+    /// Starlark: how often each line of the script ran, gcov style.
+    StarlarkCoverage,
+    /// Starlark: where the Rust evaluator spends its time, sampled with
+    /// pprof-rs (pprof protobuf and flamegraph, --features pprof).
+    Native,
+}
+
+impl ProfileKind {
+    /// File name suffix of the profile.
+    fn suffix(self) -> &'static str {
+        match self {
+            ProfileKind::StarlarkTime => "starlark-time.csv",
+            ProfileKind::StarlarkFlame => "starlark-flame.folded",
+            ProfileKind::StarlarkCoverage => "starlark-coverage.txt",
+            ProfileKind::Native => "native.pb",
+        }
+    }
+}
+
+/// Module lifetimes for --starlark-module-mode.
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+enum ModuleMode {
+    /// A new Module, and heap, for every iteration.
+    Fresh,
+    /// One Module shared by all iterations of a group; its heap keeps
+    /// everything earlier iterations allocated.
+    Reuse,
+}
+
+impl std::fmt::Display for ModuleMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let value = self.to_possible_value().expect("no skipped variants");
+        f.write_str(value.get_name())
+    }
+}
+
+/// What a Starlark iteration times, for --phase.
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+enum Phase {
+    /// Call `run(n, seed)`.
+    Run,
+    /// Parse the script.
+    Parse,
+    /// Parse the script and evaluate its top level, which compiles its
+    /// functions to bytecode.
+    Compile,
+}
+
+impl std::fmt::Display for Phase {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let value = self.to_possible_value().expect("no skipped variants");
+        f.write_str(value.get_name())
+    }
+}
+
+/// What the caches hold when an iteration starts, for --cache-state.
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+enum CacheState {
+    /// Whatever the previous iteration left, usually its own data.
+    Warm,
+    /// Nothing of the workload's: a buffer larger than the caches is
+    /// written through before each iteration.
+    Cold,
+}
+
+/// The clock behind eval_ns, for --clock.
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+enum Clock {
+    /// Elapsed time (a monotonic clock).
+    Wall,
+    /// CPU time of the whole process, all of its threads.
+    Cpu,
+    /// CPU time of the thread that evaluates the workload.
+    Thread,
+}
+
+impl std::fmt::Display for Clock {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let value = self.to_possible_value().expect("no skipped variants");
+        f.write_str(value.get_name())
+    }
+}
+
+/// Shapes of the Rust-built value --input passes to `run()` as `data`.
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+enum InputShape {
+    /// A list of n ints.
+    Ints,
+    /// A dict of n string keys to ints.
+    Dict,
+    /// A list of n small dicts mixing ints, strings and a list.
+    Records,
+}
+
+impl std::fmt::Display for InputShape {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let value = self.to_possible_value().expect("no skipped variants");
+        f.write_str(value.get_name())
+    }
+}
+
+/// Starlark dialect presets for --dialect.
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+enum DialectName {
+    /// The Starlark specification.
+    Standard,
+    /// starlark-rust's extensions: type annotations, keyword-only arguments
+    /// and top-level control flow.
+    Extended,
+    /// Nothing but the --dialect-features listed.
+    Custom,
+}
+
+/// Language features toggled by --dialect-features.
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+enum DialectFeature {
+    Def,
+    Lambda,
+    Load,
+    KeywordOnlyArgs,
+    PositionalOnlyArgs,
+    /// Type annotations, checked at runtime.
+    Types,
+    /// `if` and `for` outside of functions.
+    TopLevelStmt,
+    FStrings,
+    /// The `set` type and `set()` constructor.
+    Sets,
+}
+
+impl std::fmt::Display for DialectFeature {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let value = self.to_possible_value().expect("no skipped variants");
+        f.write_str(value.get_name())
+    }
+}
+
+/// Optional starlark-rust libraries for --starlark-lib.
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+enum StarlarkLib {
+    /// `json.encode()` and `json.decode()`.
+    Json,
+    /// The `struct()` constructor.
+    Struct,
+    /// `record()` and `field()`.
+    Record,
+    /// `enum()`.
+    Enum,
+    /// `namespace()`, a stricter `struct()`.
+    Namespace,
+    /// `map(f, xs)`.
+    Map,
+    /// `filter(f, xs)`.
+    Filter,
+    /// `partial(f, *args, **kwargs)`.
+    Partial,
+    /// `typing.Any`, `typing.Callable` and the rest, for annotations.
+    Typing,
+    /// `print()`, which writes to stderr.
+    Print,
+}
+
+impl std::fmt::Display for StarlarkLib {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let value = self.to_possible_value().expect("no skipped variants");
+        f.write_str(value.get_name())
+    }
+}
+
+/// One --dialect-features entry: `FEATURE` or `no-FEATURE`.
+#[derive(Clone, Copy)]
+struct DialectToggle {
+    feature: DialectFeature,
+    enabled: bool,
+}
+
+fn parse_dialect_toggle(s: &str) -> Result<DialectToggle, String> {
+    let (name, enabled) = match s.trim().strip_prefix("no-") {
+        Some(name) => (name, false),
+        None => (s.trim(), true),
+    };
+    let feature = DialectFeature::from_str(name, false).map_err(|_| {
+        let names: Vec<String> = DialectFeature::value_variants()
+            .iter()
+            .map(DialectFeature::to_string)
+            .collect();
+        format!(
+            "unknown dialect feature {name:?} [possible values: {}]",
+            names.join(", ")
+        )
+    })?;
+    Ok(DialectToggle { feature, enabled })
+}
+
+/// Allocation tracing for --mem.
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+enum MemMode {
+    /// Python's tracemalloc, paired with the Starlark heap profile.
+    Tracemalloc,
+}
+
+impl std::fmt::Display for MemMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let value = self.to_possible_value().expect("no skipped variants");
+        f.write_str(value.get_name())
+    }
+}
+
+/// What times Python workloads, for --python-harness.
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+enum PythonHarness {
+    /// `_harness.py`, iterations as the bench asks for them.
+    Builtin,
+    /// pyperf's Runner, through `_pyperf.py`.
+    Pyperf,
+}
+
+impl std::fmt::Display for PythonHarness {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let value = self.to_possible_value().expect("no skipped variants");
+        f.write_str(value.get_name())
+    }
+}
+
+/// Evaluator settings for --eval-config.
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+enum EvalOption {
+    /// Typecheck every module's functions as it is evaluated, failing on
+    /// type errors.
+    StaticTypecheck,
+    /// Never collect garbage.
+    NoGc,
+    /// Log every garbage collection to stderr.
+    VerboseGc,
+}
+
+impl std::fmt::Display for EvalOption {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let value = self.to_possible_value().expect("no skipped variants");
+        f.write_str(value.get_name())
+    }
+}
+
+/// One --eval-config entry: a name for the records and its options.
+#[derive(Clone)]
+struct EvalConfig {
+    name: String,
+    options: Vec<EvalOption>,
+}
+
+/// As given on the command line, e.g. "nogc=no-gc+verbose-gc".
+impl std::fmt::Display for EvalConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let options: Vec<String> = self.options.iter().map(EvalOption::to_string).collect();
+        write!(f, "{}={}", self.name, options.join("+"))
+    }
+}
+
+fn parse_eval_config(s: &str) -> Result<EvalConfig, String> {
+    let Some((name, options)) = s.trim().split_once('=') else {
+        return Err(format!("expected NAME=OPTIONS, got {s:?}"));
+    };
+    if name.is_empty()
+        || !name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        return Err(format!(
+            "configuration name {name:?} must be letters, digits, '-' and '_'"
+        ));
+    }
+    let options = options
+        .split('+')
+        .filter(|o| !o.is_empty())
+        .map(|o| {
+            EvalOption::from_str(o, false).map_err(|_| {
+                let names: Vec<String> = EvalOption::value_variants()
+                    .iter()
+                    .map(EvalOption::to_string)
+                    .collect();
+                format!(
+                    "unknown evaluator option {o:?} [possible values: {}]",
+                    names.join(", ")
+                )
+            })
+        })
+        .collect::<Result<_, _>>()?;
+    Ok(EvalConfig {
+        name: name.to_string(),
+        options,
+    })
+}
+
+/// The --size presets of N.
+#[derive(Clone, PartialEq, ValueEnum)]
+#[clap(rename_all = "UPPER")]
+pub enum Size {
+    S,
+    M,
+    L,
+}
+
+impl Size {
+    fn to_n(&self) -> usize {
+        match self {
+            Size::S => 1_000,
+            Size::M => 50_000,
+            Size::L => 500_000,
+        }
+    }
+}
+
+impl std::fmt::Display for Size {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Size::S => f.write_str("S"),
+            Size::M => f.write_str("M"),
+            Size::L => f.write_str("L"),
+        }
+    }
+}
+
+impl std::fmt::Display for EngineName {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EngineName::Starlark => f.write_str("starlark"),
+            EngineName::Python => f.write_str("python"),
+            EngineName::Custom => f.write_str("custom"),
+        }
+    }
+}
+
+impl WorkloadName {
+    fn file_stem(&self) -> &'static str {
+        match self {
+            WorkloadName::Arithmetic => "arithmetic",
+            WorkloadName::DataStructures => "data_structures",
+            WorkloadName::StringParsing => "string_parsing",
+            WorkloadName::JsonBuilding => "json_building",
+            WorkloadName::FunctionCalls => "function_calls",
+            WorkloadName::TypedFunctionCalls => "typed_function_calls",
+            WorkloadName::FfiCalls => "ffi_calls",
+            WorkloadName::Marshalling => "marshalling",
+            WorkloadName::ParallelChunks => "parallel_chunks",
+        }
+    }
+
+    fn description(&self) -> &'static str {
+        match self {
+            WorkloadName::Arithmetic => "integer LCG, float accumulation, branching on remainders",
+            WorkloadName::DataStructures => "dict insert/lookup, list append, iteration, slicing",
+            WorkloadName::StringParsing => {
+                "build CSV records, join into blob, split and parse back"
+            }
+            WorkloadName::JsonBuilding => "construct nested dicts/lists, serialize to JSON strings",
+            WorkloadName::FunctionCalls => "hot loop calling small/medium nested functions",
+            WorkloadName::TypedFunctionCalls => "function_calls with type annotations",
+            WorkloadName::FfiCalls => "hot loop calling a native (Rust / C) function",
+            WorkloadName::Marshalling => "walk a host-built value passed to run() as data",
+            WorkloadName::ParallelChunks => "LCG mixing split over threads with run_parallel()",
+        }
+    }
+
+    fn tags(&self) -> &'static [Tag] {
+        match self {
+            WorkloadName::Arithmetic => &[Tag::Numeric, Tag::Micro],
+            WorkloadName::DataStructures => &[Tag::Allocation, Tag::Macro],
+            WorkloadName::StringParsing => &[Tag::String, Tag::Allocation, Tag::Macro],
+            WorkloadName::JsonBuilding => &[Tag::String, Tag::Allocation, Tag::Macro],
+            WorkloadName::FunctionCalls => &[Tag::Micro],
+            WorkloadName::TypedFunctionCalls => &[Tag::Micro, Tag::Typed],
+            WorkloadName::FfiCalls => &[Tag::Numeric, Tag::Micro, Tag::Native],
+            WorkloadName::Marshalling => &[Tag::Allocation, Tag::Macro],
+            WorkloadName::ParallelChunks => &[Tag::Numeric, Tag::Parallel],
+        }
+    }
+
+    /// The --input shape the workload runs with when none is given.
+    fn default_input(&self) -> Option<InputShape> {
+        match self {
+            WorkloadName::Marshalling => Some(InputShape::Records),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for WorkloadName {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.file_stem())
+    }
+}
+
+// ---------------------------------------------------------------------------
+// JSON-lines report record
+// ---------------------------------------------------------------------------
+
+/// Version of the record schema; bumped on incompatible changes.
+/// Records written before versioning carry no `schema_version` (read as 0).
+const SCHEMA_VERSION: u32 = 1;
+
+/// One iteration of one group: a line of the results file without a
+/// `kind`, whose fields are documented under "Output format" in README.md.
+/// The accessors cover those every engine records: the group, iteration and
+/// warmup flag, eval and wall time, throughput, result and the stamps. The
+/// record gains fields as the bench measures more, so the rest are read from
+/// its serialized form, which is that JSON line without the stamps:
+///
+/// ```no_run
+/// # fn cpu_ns(record: &starlark_bench::BenchRecord) -> serde_json::Result<Option<u64>> {
+/// let json = serde_json::to_value(record)?;
+/// Ok(json.get("cpu_ns").and_then(|ns| ns.as_u64()))
+/// # }
+/// ```
+#[derive(Default, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct BenchRecord {
+    schema_version: u32,
+    /// Identifies the invocation that produced the record.
+    run_id: String,
+    engine: String,
+    /// The engine build: the starlark crate version, or Python's
+    /// `sys.version`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    engine_version: Option<String>,
+    /// Python only: `sys.implementation.name` (e.g. "cpython"), the language
+    /// version from `sys.version_info`, and whether the interpreter is a
+    /// free-threaded (`Py_GIL_DISABLED`) build.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    python_implementation: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    python_version: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    python_gil_disabled: Option<bool>,
+    workload: String,
+    size: String,
+    n: usize,
+    /// --target-iter-time only: the iteration time `n` was chosen for.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    target_iter_ns: Option<u64>,
+    seed: u64,
+    /// --param values; omitted when there are none.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    params: BTreeMap<String, serde_json::Value>,
+    iter: u32,
+    warmup: bool,
+    /// Re-measurement attempt (--max-stddev-pct); omitted for the first.
+    #[serde(default, skip_serializing_if = "is_zero")]
+    attempt: u32,
+    /// Index of the group in the --shuffle schedule; omitted otherwise.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    position: Option<usize>,
+    /// Python-only: failed subprocess runs retried (--retries) before the
+    /// one that produced this record; omitted when zero.
+    #[serde(default, skip_serializing_if = "is_zero")]
+    retry: u32,
+    /// Starlark: time spent parsing the AST (nanoseconds). Python, with
+    /// --python-cold: compiling the script's source.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    parse_ns: Option<u64>,
+    /// With `parse_ns`: time spent evaluating the module's top level, and
+    /// (Starlark-only) freezing it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    module_eval_ns: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    freeze_ns: Option<u64>,
+    /// Starlark-only, with `parse_ns` and --typecheck: time spent in the
+    /// static typechecker, and the type errors it found.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    typecheck_ns: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    typecheck_errors: Option<u64>,
+    /// Starlark-only, with `parse_ns` for scripts that call load(): time
+    /// spent loading other modules, and how many were loaded.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    load_ns: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    loaded_modules: Option<u64>,
+    /// Python-only, for the subprocess that ran this iteration (repeated on
+    /// each of its records): interpreter startup until the harness began
+    /// loading, and the imports and top level from then until the first
+    /// iteration (nanoseconds, not included in `eval_ns`).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    startup_ns: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    import_ns: Option<u64>,
+    /// With --input: time spent converting the input into interpreter
+    /// values for this iteration (nanoseconds, not included in `eval_ns`).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    marshal_ns: Option<u64>,
+    /// Time spent evaluating the workload (nanoseconds), on the --clock
+    /// clock. With --starlark-cold, parse + load + module eval + freeze +
+    /// `call_ns`; with --python-cold, compile + module eval + `call_ns`.
+    eval_ns: u64,
+    /// "cpu" or "thread" with --clock other than wall: what `eval_ns` and
+    /// `items_per_sec` measure.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    clock: Option<String>,
+    /// With --record-clocks: the timed call on every clock.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    clocks: Option<ClockTimes>,
+    /// With --starlark-cold or --python-cold: the `run` call alone.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    call_ns: Option<u64>,
+    /// Workload items processed per second (n / eval time).
+    items_per_sec: f64,
+    /// Per-iteration wall-clock time measured from Rust (nanoseconds).
+    /// Comparable across engines. Includes per-iteration overhead
+    /// (Module setup for Starlark, subprocess-amortized wall time for Python).
+    wall_ns: u64,
+    /// Legacy: for Starlark == eval_ns; for Python == subprocess wall / iter count.
+    /// Prefer wall_ns or eval_ns for cross-engine comparisons.
+    total_ns: u64,
+    /// Checksum returned by the workload (for correctness verification).
+    result: i64,
+    /// Resident set size in KiB (best-effort, 0 if unavailable).
+    /// Engine-local only — not comparable across engines.
+    rss_kb: u64,
+    /// Describes the RSS measurement method for this engine.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    rss_note: Option<String>,
+    /// CPU time of the timed call (nanoseconds): the evaluating thread's for
+    /// Starlark, summed over --threads evaluators; the interpreter process's
+    /// for Python. Well below `eval_ns` means the call was waiting or
+    /// preempted rather than working.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    cpu_ns: Option<u64>,
+    /// Python-only (Linux and macOS): user and system CPU time of the
+    /// subprocess, divided evenly over its iterations (nanoseconds), as the
+    /// kernel accounted them rather than as the harness reported them.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    cpu_user_ns: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    cpu_sys_ns: Option<u64>,
+    /// Peak RSS from getrusage (KiB): for Python the subprocess's, repeated
+    /// on each of its records; for Starlark (Linux, --jobs 1) the bench's
+    /// during this iteration, with the peak reset just before it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_rss_kb: Option<u64>,
+    /// Context switches and page faults, from getrusage: for Starlark (Linux)
+    /// the evaluating thread's during the timed call, summed over --threads
+    /// evaluators; for Python the subprocess's, divided evenly over its
+    /// iterations.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    voluntary_ctx_switches: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    involuntary_ctx_switches: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    major_page_faults: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    minor_page_faults: Option<u64>,
+    /// --cgroup only: memory.peak and per-iteration cpu.stat of the cgroup
+    /// the process ran in.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    cgroup: Option<cgroup::Usage>,
+    /// --perf-counters only: user-space hardware events of this iteration;
+    /// for Python, the subprocess's divided evenly over its iterations.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    counters: Option<perf::Counts>,
+    /// --energy only: joules all CPU packages used over this iteration; for
+    /// Python, over the subprocess divided evenly over its iterations.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    energy_j: Option<f64>,
+    /// --heap-profile only (Starlark): bytes and values allocated on the
+    /// Starlark heap by this iteration.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    heap_allocated_bytes: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    heap_allocations: Option<u64>,
+    /// Starlark only, in builds with `--features count-alloc`: Rust heap
+    /// allocations made by the call and the bytes they requested.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    rust_allocations: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    rust_allocated_bytes: Option<u64>,
+    /// Starlark only, with --features jemalloc or mimalloc: the allocator's
+    /// own statistics for the whole process after the call.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    allocator_stats: Option<allocator::Stats>,
+    /// Starlark only: the timer and call overhead calibrated at startup for
+    /// the group's language.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    overhead: Option<calibrate::Overhead>,
+    /// --subtract-overhead: nanoseconds taken off the call's time, and so
+    /// off `eval_ns`, `call_ns` and `items_per_sec`'s basis.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    overhead_subtracted_ns: Option<u64>,
+    /// --heap-profile only: bytes reachable from the iteration's result;
+    /// omitted in reuse module mode.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    heap_retained_bytes: Option<u64>,
+    /// --mem only: the tracing mode, and the most the iteration's call had
+    /// allocated at once, in bytes: tracemalloc's peak for Python, and
+    /// `heap_allocated_bytes` for Starlark, whose heap frees nothing during
+    /// a call.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    mem: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    peak_alloc_bytes: Option<u64>,
+    /// Starlark only: bytes allocated on the module's heap before and after
+    /// the call.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    heap_bytes_before: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    heap_bytes_after: Option<u64>,
+    /// --starlark-gc only: time spent letting the collector run after the
+    /// call, the collections that ran (0 or 1), and the heap bytes left.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    gc_ns: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    gc_count: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    heap_bytes_after_gc: Option<u64>,
+    /// Python only: time the cyclic collector paused the timed call for
+    /// (nanoseconds, part of `eval_ns`), and the collections that ran.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    python_gc_ns: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    python_gc_collections: Option<u64>,
+    /// Starlark only: --dialect and any --dialect-features, e.g.
+    /// "standard:f-strings".
+    #[serde(skip_serializing_if = "Option::is_none")]
+    dialect: Option<String>,
+    /// Starlark only: the --starlark-lib libraries; omitted when none.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    starlark_libs: Option<Vec<String>>,
+    /// Starlark only: the --eval-config the group ran in, as given.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    eval_config: Option<String>,
+    /// The --input shape passed to `run()` as `data`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    input: Option<String>,
+    /// Starlark only: --starlark-module-mode, "fresh" or "reuse".
+    #[serde(skip_serializing_if = "Option::is_none")]
+    module_mode: Option<String>,
+    /// --phase, when it is not "run", and the size of the script it parsed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    phase: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    source_bytes: Option<u64>,
+    /// Starlark only: the call-stack limit in frames, and the native stack
+    /// of the measuring thread in MiB when --starlark-thread-stack set it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_callstack: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    thread_stack_mib: Option<u32>,
+    /// --threads (Starlark) or --python-threads when above 1: concurrent
+    /// calls per iteration, and each call's eval time.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    threads: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    thread_eval_ns: Option<Vec<u64>>,
+    /// `true` when the threads called `run_parallel`, splitting `n` between
+    /// them, rather than `run` with all of it each.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    run_parallel: bool,
+    /// --min-sample-ns only: calls of `run` timed together in this sample.
+    /// `eval_ns`, `cpu_ns` and the Rust allocation counts are per call, the
+    /// sample's divided by this; the other measurements cover the sample.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    calls_per_sample: Option<u32>,
+    /// Python only: the --docker-image the interpreter ran in.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    container_image: Option<String>,
+    /// Python only: the --python-args, and whether --python-no-gc disabled
+    /// the cyclic collector.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    python_args: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    python_no_gc: bool,
+    /// Python only: whether the records came from a --python-worker, and
+    /// the --python-harness unless it is the builtin one.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    python_worker: bool,
+    /// Python only: whether the interpreter ran in --python-sandbox.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    python_sandbox: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    python_harness: Option<String>,
+    /// --cooldown between groups, in milliseconds.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    cooldown_ms: Option<u64>,
+    /// --iter-cooldown before each measurement round, in milliseconds.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    iter_cooldown_ms: Option<u64>,
+    /// "cold" with --cache-state cold, "cold+fs" with --drop-fs-caches too.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    cache_state: Option<String>,
+    /// Starlark only: --timeout in seconds. Its statement hook slows
+    /// evaluation, so `results` refuses to mix these runs with plain ones.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    timeout_secs: Option<f64>,
+    cpu_model: String,
+    os: String,
+    rustc: String,
+    /// Starlark only: the Rust global allocator, "system", "jemalloc" or
+    /// "mimalloc".
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    allocator: Option<String>,
+    /// Scheduling policy and priority the measurement ran at, e.g.
+    /// "normal nice=0" or "fifo priority=1" (--nice, --realtime).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    priority: Option<String>,
+    /// Host conditions checked before measuring; see `envcheck::EnvInfo`.
+    #[serde(default, skip_serializing_if = "envcheck::EnvInfo::is_empty")]
+    env: envcheck::EnvInfo,
+    /// When the record was made, for one a [`Runner`] hands out; the JSON
+    /// lines carry it flattened instead.
+    #[serde(skip)]
+    stamp: Option<output::Stamp>,
+}
+
+impl BenchRecord {
+    /// The engine label, e.g. "starlark" or "python[python3.12]".
+    pub fn engine(&self) -> &str {
+        &self.engine
+    }
+
+    pub fn workload(&self) -> &str {
+        &self.workload
+    }
+
+    /// The --size preset the group ran at, e.g. "M".
+    pub fn size(&self) -> &str {
+        &self.size
+    }
+
+    pub fn n(&self) -> usize {
+        self.n
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// The iteration's index among the group's warmup or measurement
+    /// iterations.
+    pub fn iter(&self) -> u32 {
+        self.iter
+    }
+
+    pub fn warmup(&self) -> bool {
+        self.warmup
+    }
+
+    /// The primary metric, in nanoseconds.
+    pub fn eval_ns(&self) -> u64 {
+        self.eval_ns
+    }
+
+    pub fn items_per_sec(&self) -> f64 {
+        self.items_per_sec
+    }
+
+    pub fn wall_ns(&self) -> u64 {
+        self.wall_ns
+    }
+
+    /// The workload's checksum.
+    pub fn result(&self) -> i64 {
+        self.result
+    }
+
+    /// `ts_unix_ms`: when the record was made, in milliseconds since the
+    /// Unix epoch. None unless a [`Runner`] handed the record out.
+    pub fn ts_unix_ms(&self) -> Option<u64> {
+        self.stamp.map(|s| s.ts_unix_ms)
+    }
+
+    /// `elapsed_ms_since_start`: when the record was made, in milliseconds
+    /// since its [`Runner`] run began.
+    pub fn elapsed_ms_since_start(&self) -> Option<u64> {
+        self.stamp.map(|s| s.elapsed_ms_since_start)
+    }
+}
+
+/// One timed call on every clock (--record-clocks), in nanoseconds per
+/// call; a CPU clock the platform or engine cannot read is left out.
+#[derive(Clone, Copy, Serialize, Deserialize)]
+struct ClockTimes {
+    wall_ns: u64,
+    /// The whole process's CPU time.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    cpu_ns: Option<u64>,
+    /// The evaluating thread's CPU time.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    thread_ns: Option<u64>,
+}
+
+impl ClockTimes {
+    /// Each clock divided over `calls` back-to-back calls.
+    fn per_call(self, calls: u64) -> ClockTimes {
+        ClockTimes {
+            wall_ns: self.wall_ns / calls,
+            cpu_ns: self.cpu_ns.map(|ns| ns / calls),
+            thread_ns: self.thread_ns.map(|ns| ns / calls),
+        }
+    }
+}
+
+/// Emitted once per measurement group, after its iteration records.
+/// Tagged with `"kind": "summary"`; iteration records carry no `kind`.
+#[derive(Serialize, Deserialize)]
+struct SummaryRecord {
+    schema_version: u32,
+    run_id: String,
+    #[serde(skip_deserializing)]
+    kind: &'static str,
+    engine: String,
+    /// As on the group's iteration records.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    engine_version: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    python_implementation: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    python_version: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    python_gil_disabled: Option<bool>,
+    workload: String,
+    size: String,
+    n: usize,
+    /// As on the group's iteration records.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    target_iter_ns: Option<u64>,
+    seed: u64,
+    /// --param values; omitted when there are none.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    params: BTreeMap<String, serde_json::Value>,
+    /// Re-measurement attempt (--max-stddev-pct); omitted for the first.
+    #[serde(default, skip_serializing_if = "is_zero")]
+    attempt: u32,
+    /// Index of the group in the --shuffle schedule; omitted otherwise.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    position: Option<usize>,
+    /// Number of measurement (non-warmup) iterations summarized.
+    iters: usize,
+    /// Number of warmup iterations run before measuring.
+    warmup_iters: usize,
+    /// "cpu" or "thread" with --clock other than wall.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    clock: Option<String>,
+    median_eval_ns: u64,
+    /// n / median eval time.
+    median_items_per_sec: f64,
+    mean_eval_ns: u64,
+    stddev_eval_ns: u64,
+    min_eval_ns: u64,
+    max_eval_ns: u64,
+    /// Python only: the median `python_gc_ns` of the measurement iterations,
+    /// the part of `median_eval_ns` the cyclic collector typically takes.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    median_python_gc_ns: Option<u64>,
+    /// `result` of the last measurement iteration, compared across engines
+    /// (--fail-on checksum-mismatch).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    result: Option<i64>,
+    /// --bytecode-stats only (Starlark): bytecode instructions one call of
+    /// `run` executes, an architecture-independent measure of its work.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    bytecode_instructions: Option<u64>,
+    /// Starlark only: "miss" when the script was parsed for this group,
+    /// "hit" when the group reused one parsed earlier in the invocation.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    script_cache: Option<String>,
+    /// The kernel's memory high-water mark over the whole group (KiB), from
+    /// cgroup v2 memory.peak: the subprocesses' own cgroups for Python
+    /// with --cgroup, otherwise the cgroup the bench runs in (--jobs 1,
+    /// not --interleave, Linux 6.12 and later).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    cgroup_memory_peak_kb: Option<u64>,
+    /// --cpu-freq only: the frequencies of the CPUs the group ran on while
+    /// it measured, and thermal throttling meanwhile.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    cpu_freq: Option<cpufreq::Stats>,
+    /// `true` when the frequency spread exceeds --freq-threshold or the CPUs
+    /// were throttled, so the timings mix clock speeds.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    freq_unstable: bool,
+    /// Starlark only: the medians and spread are of eval_ns with the
+    /// calibrated overhead taken off (--subtract-overhead).
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    overhead_subtracted: bool,
+    /// Coefficient of variation of eval_ns (stddev / mean), in percent.
+    cv_pct: f64,
+    /// True when `cv_pct` exceeds `--noise-threshold`.
+    noisy: bool,
+    /// Half-width of the 95% confidence interval of the median, in percent
+    /// of the median.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ci_pct: Option<f64>,
+    /// Least-squares slope of eval_ns over the measurement iteration index.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    trend_ns_per_iter: Option<f64>,
+    /// True when the slope is significantly positive (t > 2): timings grew
+    /// during the run, e.g. from thermal throttling or heap growth.
+    drifting: bool,
+    /// --max-stddev-pct only: this attempt exceeded the budget and was
+    /// re-measured; its records should be discarded.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    rejected: bool,
+    /// --target-ci mode only: whether the target was reached before --max-iters.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    converged: Option<bool>,
+    /// Starlark only: --dialect and any --dialect-features, e.g.
+    /// "standard:f-strings".
+    #[serde(skip_serializing_if = "Option::is_none")]
+    dialect: Option<String>,
+    /// Starlark only: the --starlark-lib libraries; omitted when none.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    starlark_libs: Option<Vec<String>>,
+    /// Starlark only: the --eval-config the group ran in, as given.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    eval_config: Option<String>,
+    /// The --input shape passed to `run()` as `data`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    input: Option<String>,
+    /// Starlark only: --starlark-module-mode, "fresh" or "reuse".
+    #[serde(skip_serializing_if = "Option::is_none")]
+    module_mode: Option<String>,
+    /// --phase, when it is not "run".
+    #[serde(skip_serializing_if = "Option::is_none")]
+    phase: Option<String>,
+    /// Starlark only: the call-stack limit in frames, and the native stack
+    /// of the measuring thread in MiB when --starlark-thread-stack set it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_callstack: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    thread_stack_mib: Option<u32>,
+    /// --threads (Starlark) or --python-threads when above 1: concurrent
+    /// calls per iteration.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    threads: Option<u32>,
+    /// `true` when the threads split `n` with `run_parallel`.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    run_parallel: bool,
+    /// Python only: the --docker-image the interpreter ran in.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    container_image: Option<String>,
+    /// Python only: the --python-args, and whether --python-no-gc disabled
+    /// the cyclic collector.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    python_args: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    python_no_gc: bool,
+    /// Python only: whether the records came from a --python-worker, and
+    /// the --python-harness unless it is the builtin one.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    python_worker: bool,
+    /// Python only: whether the interpreter ran in --python-sandbox.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    python_sandbox: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    python_harness: Option<String>,
+    /// --cooldown between groups, in milliseconds.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    cooldown_ms: Option<u64>,
+    /// --iter-cooldown before each measurement round, in milliseconds.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    iter_cooldown_ms: Option<u64>,
+    /// "cold" with --cache-state cold, "cold+fs" with --drop-fs-caches too.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    cache_state: Option<String>,
+    /// Starlark only: --timeout in seconds. Its statement hook slows
+    /// evaluation, so `results` refuses to mix these runs with plain ones.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    timeout_secs: Option<f64>,
+    cpu_model: String,
+    os: String,
+    rustc: String,
+    /// Starlark only: the Rust global allocator, "system", "jemalloc" or
+    /// "mimalloc".
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    allocator: Option<String>,
+    /// Scheduling policy and priority the measurement ran at, e.g.
+    /// "normal nice=0" or "fifo priority=1" (--nice, --realtime).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    priority: Option<String>,
+    /// Host conditions checked before measuring; see `envcheck::EnvInfo`.
+    #[serde(default, skip_serializing_if = "envcheck::EnvInfo::is_empty")]
+    env: envcheck::EnvInfo,
+}
+
+/// Emitted instead of a summary when an iteration exceeds --timeout; the
+/// rest of the group is skipped. Tagged with `"kind": "timeout"`.
+#[derive(Serialize)]
+struct TimeoutRecord {
+    schema_version: u32,
+    run_id: String,
+    kind: &'static str,
+    engine: String,
+    workload: String,
+    size: String,
+    n: usize,
+    seed: u64,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    params: BTreeMap<String, serde_json::Value>,
+    #[serde(skip_serializing_if = "is_zero")]
+    attempt: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    position: Option<usize>,
+    /// Whether the iteration that timed out was a warmup iteration.
+    warmup: bool,
+    /// Measurement iterations completed before the timeout.
+    completed_iters: usize,
+    timeout_secs: f64,
+    /// Python only: the last lines the killed subprocess wrote to stderr.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stderr_tail: Option<String>,
+}
+
+/// The memory a workload's frozen module keeps resident once prepared,
+/// emitted when its script is parsed. Tagged with `"kind": "frozen_heap"`.
+#[derive(Serialize)]
+struct FrozenHeapRecord {
+    schema_version: u32,
+    run_id: String,
+    kind: &'static str,
+    engine: String,
+    engine_version: String,
+    workload: String,
+    /// Bytes and values on the module's frozen heap.
+    bytes: u64,
+    values: u64,
+    /// Values and bytes per value type, e.g. "function" or "list".
+    types: BTreeMap<String, HeapTypeCount>,
+    /// Frozen heap bytes of the modules it `load()`s; omitted when none.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    loaded_bytes: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    dialect: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    eval_config: Option<String>,
+}
+
+#[derive(Serialize)]
+struct HeapTypeCount {
+    count: u64,
+    bytes: u64,
+}
+
+fn is_zero(v: &u32) -> bool {
+    *v == 0
+}
+
+/// Cross-engine speedup for one workload and size, emitted after the
+/// summaries when both engines ran in the same invocation, or by `compare`.
+/// Tagged with `"kind": "ratio"`.
+#[derive(Serialize)]
+struct RatioRecord {
+    schema_version: u32,
+    /// None when computed by `compare` over pooled results.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    run_id: Option<String>,
+    kind: &'static str,
+    engine: String,
+    baseline_engine: String,
+    workload: String,
+    size: String,
+    n: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    seed: Option<u64>,
+    median_eval_ns: u64,
+    baseline_median_eval_ns: u64,
+    /// The baseline's N where it differs from `n` (--target-iter-time).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    baseline_n: Option<usize>,
+    /// median_eval_ns / baseline_median_eval_ns, per unit of N when the
+    /// two differ; > 1 means slower than baseline.
+    ratio: f64,
+}
+
+/// Emitted in place of the ratio records of a comparison whose engines
+/// computed different results, or not the one a registered workload
+/// expects. Tagged with `"kind": "mismatch"`.
+#[derive(Serialize)]
+struct MismatchRecord {
+    schema_version: u32,
+    run_id: String,
+    kind: &'static str,
+    engine: String,
+    /// The first engine of the comparison, whose result the others are
+    /// checked against, or "expected".
+    baseline_engine: String,
+    workload: String,
+    size: String,
+    n: usize,
+    seed: u64,
+    result: i64,
+    baseline_result: i64,
+}
+
+/// The machine's noise floor: the timings of a fixed native loop before
+/// the first group and after the last. Tagged with `"kind": "canary"`.
+#[derive(Serialize)]
+struct CanaryRecord {
+    schema_version: u32,
+    run_id: String,
+    kind: &'static str,
+    /// `before` or `after` the measurements.
+    when: &'static str,
+    samples: usize,
+    median_ns: u64,
+    min_ns: u64,
+    max_ns: u64,
+    cv_pct: f64,
+    /// After only: how much `median_ns` changed since before, in percent.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    median_change_pct: Option<f64>,
+    cpu_model: String,
+    os: String,
+}
+
+// ---------------------------------------------------------------------------
+// System information helpers
+// ---------------------------------------------------------------------------
+
+/// Invocation metadata (run id and host details) attached to every record,
+/// collected once per invocation.
+struct SysInfo {
+    run_id: String,
+    cpu_model: String,
+    os: String,
+    rustc: String,
+    /// Effective scheduling priority; None where it cannot be queried.
+    priority: Option<String>,
+    /// Frequency scaling, SMT and load of the CPUs the run uses.
+    env: envcheck::EnvInfo,
+    /// Starlark call overhead per --eval-config (one without), calibrated
+    /// at startup; empty when Starlark is not measured.
+    overheads: Vec<calibrate::Overhead>,
+}
+
+impl SysInfo {
+    fn collect() -> Self {
+        SysInfo {
+            run_id: new_run_id(),
+            cpu_model: cpu_model(),
+            os: os_info(),
+            rustc: rustc_version(),
+            priority: priority::current(),
+            env: envcheck::collect(&affinity::available()),
+            overheads: Vec::new(),
+        }
+    }
+}
+
+/// Random RFC 4122 version-4 UUID, drawn from std's randomly keyed hasher.
+fn new_run_id() -> String {
+    use std::hash::{BuildHasher, Hasher};
+
+    let random_u64 = || {
+        let mut h = std::collections::hash_map::RandomState::new().build_hasher();
+        h.write_u128(
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map_or(0, |d| d.as_nanos()),
+        );
+        h.write_u32(std::process::id());
+        h.finish()
+    };
+    let hi = (random_u64() & !0xF000) | 0x4000; // version 4
+    let lo = (random_u64() & !(0xC0 << 56)) | (0x80 << 56); // RFC 4122 variant
+    format!(
+        "{:08x}-{:04x}-{:04x}-{:04x}-{:012x}",
+        hi >> 32,
+        (hi >> 16) & 0xFFFF,
+        hi & 0xFFFF,
+        lo >> 48,
+        lo & 0xFFFF_FFFF_FFFF
+    )
+}
+
+fn cpu_model() -> String {
+    #[cfg(target_os = "linux")]
+    {
+        if let Ok(info) = std::fs::read_to_string("/proc/cpuinfo") {
+            for line in info.lines() {
+                if line.starts_with("model name")
+                    && let Some(val) = line.split(':').nth(1)
+                {
+                    return val.trim().to_string();
+                }
+            }
+        }
+    }
+    #[cfg(target_os = "macos")]
+    {
+        if let Ok(out) = Command::new("sysctl")
+            .args(["-n", "machdep.cpu.brand_string"])
+            .output()
+            && out.status.success()
+        {
+            return String::from_utf8_lossy(&out.stdout).trim().to_string();
+        }
+    }
+    "unknown".to_string()
+}
+
+fn os_info() -> String {
+    format!("{}-{}", std::env::consts::OS, std::env::consts::ARCH)
+}
+
+fn rustc_version() -> String {
+    Command::new("rustc")
+        .arg("--version")
+        .output()
+        .map(|o| String::from_utf8_lossy(&o.stdout).trim().to_string())
+        .unwrap_or_else(|_| "unknown".to_string())
+}
+
+fn process_rss_kb() -> u64 {
+    #[cfg(target_os = "linux")]
+    {
+        if let Ok(status) = std::fs::read_to_string("/proc/self/status") {
+            for line in status.lines() {
+                if let Some(rest) = line.strip_prefix("VmRSS:") {
+                    return rest
+                        .split_whitespace()
+                        .next()
+                        .and_then(|v| v.parse().ok())
+                        .unwrap_or(0);
+                }
+            }
+        }
+    }
+    #[cfg(target_os = "macos")]
+    {
+        if let Ok(out) = Command::new("ps")
+            .args(["-o", "rss=", "-p", &std::process::id().to_string()])
+            .output()
+            && out.status.success()
+        {
+            return String::from_utf8_lossy(&out.stdout)
+                .trim()
+                .parse()
+                .unwrap_or(0);
+        }
+    }
+    0
+}
+
+/// CPU time the calling thread has used (CLOCK_THREAD_CPUTIME_ID).
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+fn thread_cpu_time() -> Option<Duration> {
+    // SAFETY: timespec is plain data, filled in by the call.
+    let mut ts: libc::timespec = unsafe { std::mem::zeroed() };
+    // SAFETY: ts is valid for the duration of the call.
+    (unsafe { libc::clock_gettime(libc::CLOCK_THREAD_CPUTIME_ID, &mut ts) } == 0)
+        .then(|| Duration::new(ts.tv_sec as u64, ts.tv_nsec as u32))
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+fn thread_cpu_time() -> Option<Duration> {
+    None
+}
+
+/// CPU time the whole process has used (CLOCK_PROCESS_CPUTIME_ID).
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+fn process_cpu_time() -> Option<Duration> {
+    // SAFETY: timespec is plain data, filled in by the call.
+    let mut ts: libc::timespec = unsafe { std::mem::zeroed() };
+    // SAFETY: ts is valid for the duration of the call.
+    (unsafe { libc::clock_gettime(libc::CLOCK_PROCESS_CPUTIME_ID, &mut ts) } == 0)
+        .then(|| Duration::new(ts.tv_sec as u64, ts.tv_nsec as u32))
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+fn process_cpu_time() -> Option<Duration> {
+    None
+}
+
+/// Reset this process's peak RSS, so that the next [`peak_rss_kb`] only
+/// covers what follows: Linux (4.0 and later) resets it when 5 is written to
+/// /proc/self/clear_refs. False where it cannot be reset.
+#[cfg(target_os = "linux")]
+fn reset_peak_rss() -> bool {
+    std::fs::write("/proc/self/clear_refs", "5").is_ok()
+}
+
+#[cfg(not(target_os = "linux"))]
+fn reset_peak_rss() -> bool {
+    false
+}
+
+/// This process's peak RSS in KiB, from `getrusage(RUSAGE_SELF)`.
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+fn peak_rss_kb() -> Option<u64> {
+    // SAFETY: rusage is plain data, filled in by the kernel.
+    let mut usage: libc::rusage = unsafe { std::mem::zeroed() };
+    // SAFETY: usage is valid for the duration of the call.
+    (unsafe { libc::getrusage(libc::RUSAGE_SELF, &mut usage) } == 0)
+        .then(|| maxrss_kb(usage.ru_maxrss))
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+fn peak_rss_kb() -> Option<u64> {
+    None
+}
+
+/// Context switches and page faults, as getrusage counts them.
+#[derive(Clone, Copy, Default)]
+struct Rusage {
+    /// Switches away because the thread blocked or yielded.
+    voluntary_switches: u64,
+    /// Switches away because the scheduler preempted it.
+    involuntary_switches: u64,
+    /// Faults that had to read from disk, and faults served from memory.
+    major_faults: u64,
+    minor_faults: u64,
+}
+
+impl Rusage {
+    #[cfg(any(target_os = "linux", target_os = "macos"))]
+    fn from_raw(usage: &libc::rusage) -> Rusage {
+        Rusage {
+            voluntary_switches: usage.ru_nvcsw as u64,
+            involuntary_switches: usage.ru_nivcsw as u64,
+            major_faults: usage.ru_majflt as u64,
+            minor_faults: usage.ru_minflt as u64,
+        }
+    }
+
+    fn since(&self, before: &Rusage) -> Rusage {
+        Rusage {
+            voluntary_switches: self
+                .voluntary_switches
+                .saturating_sub(before.voluntary_switches),
+            involuntary_switches: self
+                .involuntary_switches
+                .saturating_sub(before.involuntary_switches),
+            major_faults: self.major_faults.saturating_sub(before.major_faults),
+            minor_faults: self.minor_faults.saturating_sub(before.minor_faults),
+        }
+    }
+
+    /// Divided evenly over `iters` iterations.
+    fn per_iter(&self, iters: usize) -> Rusage {
+        let iters = iters.max(1) as u64;
+        Rusage {
+            voluntary_switches: self.voluntary_switches / iters,
+            involuntary_switches: self.involuntary_switches / iters,
+            major_faults: self.major_faults / iters,
+            minor_faults: self.minor_faults / iters,
+        }
+    }
+}
+
+impl std::ops::Add for Rusage {
+    type Output = Rusage;
+
+    fn add(self, other: Rusage) -> Rusage {
+        Rusage {
+            voluntary_switches: self.voluntary_switches + other.voluntary_switches,
+            involuntary_switches: self.involuntary_switches + other.involuntary_switches,
+            major_faults: self.major_faults + other.major_faults,
+            minor_faults: self.minor_faults + other.minor_faults,
+        }
+    }
+}
+
+/// The calling thread's context switches and page faults so far, from
+/// `getrusage(RUSAGE_THREAD)`, which only Linux has.
+#[cfg(target_os = "linux")]
+fn thread_rusage() -> Option<Rusage> {
+    // SAFETY: rusage is plain data, filled in by the kernel.
+    let mut usage: libc::rusage = unsafe { std::mem::zeroed() };
+    // SAFETY: usage is valid for the duration of the call.
+    (unsafe { libc::getrusage(libc::RUSAGE_THREAD, &mut usage) } == 0)
+        .then(|| Rusage::from_raw(&usage))
+}
+
+#[cfg(not(target_os = "linux"))]
+fn thread_rusage() -> Option<Rusage> {
+    None
+}
+
+/// `ru_maxrss` in KiB: Linux reports KiB, macOS bytes.
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+fn maxrss_kb(maxrss: libc::c_long) -> u64 {
+    if cfg!(target_os = "macos") {
+        maxrss as u64 / 1024
+    } else {
+        maxrss as u64
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Script directory resolution
+// ---------------------------------------------------------------------------
+
+/// The scripts of the crate's source tree, which a [`Runner`] uses by
+/// default wherever it runs.
+const BUNDLED_SCRIPTS: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/scripts");
+
+fn resolve_scripts_dir(explicit: Option<PathBuf>) -> PathBuf {
+    if let Some(p) = explicit {
+        return p;
+    }
+    // Try CWD/scripts first (most common when running from repo root).
+    let cwd = PathBuf::from("scripts");
+    if cwd.is_dir() {
+        return cwd;
+    }
+    // Try relative to the executable (for installed / CI layouts).
+    if let Ok(exe) = std::env::current_exe() {
+        let candidate = exe
+            .parent()
+            .unwrap_or(exe.as_path())
+            .join("../share/starlark_bench/scripts");
+        if candidate.is_dir() {
+            return candidate;
+        }
+    }
+    // Then the source tree the bench was built from.
+    let bundled = PathBuf::from(BUNDLED_SCRIPTS);
+    if bundled.is_dir() {
+        return bundled;
+    }
+    cwd // fall back, will error later with a clear message
+}
+
+// ---------------------------------------------------------------------------
+// Main
+// ---------------------------------------------------------------------------
+
+/// The `bench` command. Exit codes: 0 on success, 3 to 5 for a --fail-on
+/// class (see `FailureClass`), 2 for usage errors and 1 for anything else.
+/// Public only for `src/main.rs`; not part of the library API.
+#[doc(hidden)]
+pub fn main() -> std::process::ExitCode {
+    match try_main() {
+        Ok(()) => std::process::ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {e:?}");
+            std::process::ExitCode::from(exit_code(&e))
+        }
+    }
+}
+
+/// The exit code of a failed run.
+fn exit_code(e: &anyhow::Error) -> u8 {
+    e.downcast_ref::<Failure>()
+        .map_or(1, |f| f.class.exit_code())
+}
+
+fn try_main() -> Result<()> {
+    let matches = Cli::command().get_matches();
+    let cli = Cli::from_arg_matches(&matches)?;
+    logging::init(cli.verbose, cli.quiet);
+    let (mut args, run_matches) = match cli.command {
+        Some(Commands::Run(run_args)) => (*run_args, matches.subcommand_matches("run")),
+        Some(Commands::List(list_args)) => return list(&list_args),
+        Some(Commands::Compare(compare_args)) => return compare::run(&compare_args),
+        Some(Commands::Report(report_args)) => return report::run(&report_args),
+        Some(Commands::Baseline(BaselineCommand::Save(b))) => return baseline::save(&b),
+        Some(Commands::Baseline(BaselineCommand::Check(b))) => return baseline::check(&b),
+        Some(Commands::Merge(merge_args)) => return merge::run(&merge_args),
+        Some(Commands::Selftest(selftest_args)) => return selftest(&selftest_args),
+        None => (cli.run, Some(&matches)),
+    };
+    complete_run_args(&mut args, run_matches.unwrap_or(&matches))?;
+    run(&args)
+}
+
+/// Apply the --config file and install the --provision-python interpreters;
+/// `matches` tells which flags were given, which win over the file. Returns
+/// the arguments the file set, with where from, for [`check_conflicts`].
+fn complete_run_args(
+    args: &mut RunArgs,
+    matches: &clap::ArgMatches,
+) -> Result<Vec<(&'static str, &'static str)>> {
+    let mut set = Vec::new();
+    if let Some(path) = args.config.clone() {
+        debug!("loading suite configuration from {}", path.display());
+        let ids = config::load(&path)?.apply(args, matches)?;
+        set.extend(ids.into_iter().map(|id| (id, "--config")));
+        check_conflicts(matches, &set)?;
+    }
+    if args.remote.is_some() {
+        args.forwarded = remote::forwarded_args(matches);
+    }
+    // A --remote run provisions on the remote host.
+    if args.remote.is_none() && !args.provision_python.is_empty() {
+        let dir = provision::cache_dir(args.python_cache_dir.as_deref())?;
+        args.provisioned = args
+            .provision_python
+            .iter()
+            .map(|v| Ok((v.clone(), provision::python(v, &dir)?.display().to_string())))
+            .collect::<Result<_>>()?;
+    }
+    Ok(set)
+}
+
+/// Fail if two conflicting run options are both in effect. clap checks
+/// them only on the command line it parsed into `matches`; `set` holds the
+/// ids of the arguments set after that, each with what set it, the last
+/// of an id winning.
+fn check_conflicts(matches: &clap::ArgMatches, set: &[(&str, &str)]) -> Result<()> {
+    let mut command = Cli::command();
+    command.build();
+    let run = command.find_subcommand("run").expect("the run subcommand");
+    let origin = |id: &str| {
+        set.iter()
+            .rev()
+            .find(|(set_id, _)| *set_id == id)
+            .map(|(_, by)| *by)
+    };
+    let given = |id: &str| {
+        origin(id).is_some()
+            || matches.value_source(id) == Some(clap::parser::ValueSource::CommandLine)
+    };
+    let describe = |arg: &clap::Arg| {
+        let id = arg.get_id().as_str();
+        let flag = format!("--{}", arg.get_long().unwrap_or(id));
+        match origin(id) {
+            Some(by) => format!("{flag} (from {by})"),
+            None => flag,
+        }
+    };
+    for arg in run.get_arguments() {
+        let id = arg.get_id().as_str();
+        if arg.is_global_set() || !given(id) {
+            continue;
+        }
+        for other in run.get_arg_conflicts_with(arg) {
+            let other_id = other.get_id().as_str();
+            if given(other_id) && (origin(id).is_some() || origin(other_id).is_some()) {
+                bail!("{} cannot be used with {}", describe(arg), describe(other));
+            }
+        }
+    }
+    Ok(())
+}
+
+// ---------------------------------------------------------------------------
+// List subcommand
+// ---------------------------------------------------------------------------
+
+fn list(args: &ListArgs) -> Result<()> {
+    let scripts_dir = resolve_scripts_dir(args.scripts_dir.clone());
+
+    println!("engines:");
+    println!(
+        "  {:<10} {}, embedded in bench {}",
+        EngineName::Starlark.to_string(),
+        starlark_engine::VERSION,
+        env!("CARGO_PKG_VERSION")
+    );
+    let python = match find_executable(&args.python) {
+        Some(path) => {
+            let version = Command::new(&path)
+                .arg("--version")
+                .output()
+                .map(|o| {
+                    // Python 2 printed its version on stderr.
+                    let out = if o.stdout.is_empty() {
+                        o.stderr
+                    } else {
+                        o.stdout
+                    };
+                    String::from_utf8_lossy(&out).trim().to_string()
+                })
+                .unwrap_or_else(|_| "version unknown".to_string());
+            format!("{version} ({})", path.display())
+        }
+        None => format!("not found: {}", args.python),
+    };
+    println!("  {:<10} {python}", EngineName::Python.to_string());
+
+    println!("workloads (scripts in {}):", scripts_dir.display());
+    for w in WorkloadName::value_variants() {
+        let stem = w.file_stem();
+        let mark = |p: PathBuf| if p.is_file() { "yes" } else { "missing" };
+        let tags: Vec<String> = w.tags().iter().map(Tag::to_string).collect();
+        println!(
+            "  {stem:<20} {:<56} {:<26} [star: {}, py: {}]",
+            w.description(),
+            tags.join(","),
+            mark(scripts_dir.join("starlark").join(format!("{stem}.star"))),
+            mark(scripts_dir.join("python").join(format!("{stem}.py"))),
+        );
+    }
+
+    println!("sizes:");
+    for s in Size::value_variants() {
+        println!("  {s}  n={}", s.to_n());
+    }
+    Ok(())
+}
+
+// ---------------------------------------------------------------------------
+// Selftest subcommand
+// ---------------------------------------------------------------------------
+
+fn selftest(args: &SelftestArgs) -> Result<()> {
+    let scripts_dir = resolve_scripts_dir(args.scripts_dir.clone());
+    let python = find_executable(&args.python);
+    if python.is_none() {
+        warn!(
+            "python interpreter {:?} not found; only the Starlark scripts are checked",
+            args.python
+        );
+    }
+    let (n, seed) = (args.size.to_n(), args.seed);
+    let language = starlark_engine::Language::new(DialectName::Extended, &[]);
+
+    let mut diverged = 0;
+    for w in WorkloadName::value_variants() {
+        let stem = w.file_stem();
+        let input = w.default_input().map(|shape| input::build(shape, n, seed));
+        let star_path = scripts_dir.join("starlark").join(format!("{stem}.star"));
+        let star = std::fs::read_to_string(&star_path)
+            .with_context(|| format!("cannot read {}", star_path.display()))
+            .and_then(|body| {
+                let load_dir = scripts_dir.join("starlark");
+                starlark_engine::prepare(&body, &load_dir, &language, None, false, false)
+            })
+            .and_then(|prepared| {
+                let call_options = starlark_engine::CallOptions {
+                    profile: false,
+                    gc: false,
+                    calls: 1,
+                    parallel: None,
+                };
+                starlark_engine::call_run(&prepared, n, seed, &[], input.as_ref(), call_options)
+            })
+            .map(|r| r.result)
+            .with_context(|| format!("starlark/{stem} failed"))?;
+        let Some(python) = &python else {
+            println!("SKIP {stem:<20} starlark={star}");
+            continue;
+        };
+        let py_path = scripts_dir.join("python").join(format!("{stem}.py"));
+        let py_input = input.as_ref().map(|v| v.to_string());
+        let launch = python_engine::Launch {
+            binary: &python.to_string_lossy(),
+            args: &[],
+            no_gc: false,
+            tracemalloc: false,
+            cold: false,
+            threads: 1,
+            sandbox: false,
+            perf: false,
+            energy: false,
+            min_sample_ns: None,
+            evict_bytes: None,
+            clock: Clock::Wall,
+            record_clocks: false,
+            pythonpath: None,
+            image: None,
+        };
+        let py = python_engine::run(launch, &py_path, n, seed, &[], py_input.as_deref(), 1, None)
+            .with_context(|| format!("python/{stem} failed"))?
+            .iters
+            .first()
+            .map(|ir| ir.result)
+            .with_context(|| format!("python/{stem} reported no result"))?;
+        if star == py {
+            println!("OK   {stem:<20} checksum={star}");
+        } else {
+            println!("FAIL {stem:<20} starlark={star} python={py}");
+            diverged += 1;
+        }
+    }
+    if diverged > 0 {
+        return Err(Failure {
+            class: FailureClass::ChecksumMismatch,
+            message: format!(
+                "{diverged} workload(s) compute different checksums on Starlark and Python"
+            ),
+        }
+        .into());
+    }
+    Ok(())
+}
+
+/// Resolve a binary name against PATH (paths are checked as given).
+fn find_executable(bin: &str) -> Option<PathBuf> {
+    let candidate = Path::new(bin);
+    if candidate.components().count() > 1 {
+        return candidate.is_file().then(|| candidate.to_path_buf());
+    }
+    let path = std::env::var_os("PATH")?;
+    std::env::split_paths(&path)
+        .flat_map(|dir| {
+            let plain = dir.join(bin);
+            let exe = dir.join(format!("{bin}.exe"));
+            [plain, exe]
+        })
+        .find(|p| p.is_file())
+}
+
+fn run(args: &RunArgs) -> Result<()> {
+    if let Some(host) = &args.remote {
+        return remote::run(args, host);
+    }
+    let mut engines = expand(&args.engine);
+    if !args.custom_engines.is_empty() {
+        engines.push(EngineName::Custom);
+    }
+    let mut workloads = expand(&args.workload);
+    if workloads.is_empty() && !args.tags.is_empty() {
+        workloads = WorkloadName::value_variants().to_vec();
+    }
+    workloads.retain(|w| {
+        let tags = w.tags();
+        (args.tags.is_empty() || args.tags.iter().any(|t| tags.contains(t)))
+            && !args.skip_tags.iter().any(|t| tags.contains(t))
+    });
+    if engines.is_empty() {
+        bail!("no engine selected: pass --engine or set `engines` in --config");
+    }
+    if workloads.is_empty() && args.custom_workloads.is_empty() {
+        if !args.tags.is_empty() || !args.skip_tags.is_empty() {
+            bail!("no workload matches --tags/--skip-tags");
+        }
+        bail!("no workload selected: pass --workload or set `workloads` in --config");
+    }
+    let mut custom_names = HashSet::new();
+    for w in &args.custom_workloads {
+        if w.name.is_empty()
+            || !w
+                .name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+        {
+            bail!(
+                "workload name {:?} may only hold letters, digits, '_' and '-'",
+                w.name
+            );
+        }
+        if !custom_names.insert(w.name)
+            || WorkloadName::value_variants()
+                .iter()
+                .any(|b| b.file_stem() == w.name)
+        {
+            bail!("workload name {:?} is taken", w.name);
+        }
+        if engines.contains(&EngineName::Starlark) && w.starlark.is_none() {
+            bail!(
+                "workload {} has no Starlark script for --engine starlark",
+                w.name
+            );
+        }
+        if engines.contains(&EngineName::Python) {
+            let Some(path) = &w.python else {
+                bail!(
+                    "workload {} has no Python script for --engine python",
+                    w.name
+                );
+            };
+            if args.python_harness == PythonHarness::Pyperf || args.docker_image.is_some() {
+                bail!(
+                    "--python-harness pyperf and --docker-image need the scripts in --scripts-dir; \
+                     {} is not",
+                    path.display()
+                );
+            }
+        }
+    }
+    if args.phase != Phase::Run {
+        if engines.contains(&EngineName::Python) || engines.contains(&EngineName::Custom) {
+            bail!(
+                "--phase {} only measures the Starlark parser; run it with --engine starlark",
+                args.phase
+            );
+        }
+        if heap_profile(args) || args.starlark_gc || args.bytecode_stats || !args.profile.is_empty()
+        {
+            bail!(
+                "--phase {} never calls run(), so --heap-profile, --mem, --starlark-gc, \
+                 --bytecode-stats and --profile do not apply",
+                args.phase
+            );
+        }
+    }
+    if args.threads > 1 {
+        if args.jobs > 1 {
+            bail!("--threads and --jobs both run on several cores; use one of them");
+        }
+        if args.phase != Phase::Run || module_mode(args) == ModuleMode::Reuse {
+            bail!("--threads needs --phase run and --starlark-module-mode fresh");
+        }
+        if heap_profile(args) || args.starlark_gc {
+            bail!(
+                "--heap-profile, --mem and --starlark-gc measure one call; they do not combine with --threads"
+            );
+        }
+    }
+    if args.perf_counters {
+        // Fails early where the counters cannot be opened.
+        perf::Counters::current()?;
+        if cfg!(target_os = "macos") && args.jobs > 1 {
+            bail!("--perf-counters counts the whole process on macOS; it needs --jobs 1");
+        }
+    }
+    if args.profile.contains(&ProfileKind::Native) {
+        native_profile::check()?;
+        if args.jobs > 1 {
+            bail!("--profile native samples the whole process; it needs --jobs 1");
+        }
+    }
+    if args.cpu_freq {
+        cpufreq::check()?;
+    }
+    if args.energy {
+        energy::open()?;
+        if args.jobs > 1 {
+            bail!("--energy reads package-wide counters; it needs --jobs 1");
+        }
+    }
+    if args.python_sandbox && !cfg!(any(target_os = "linux", target_os = "macos")) {
+        bail!("--python-sandbox needs Linux or macOS");
+    }
+    if args.python_threads > 1 {
+        if args.jobs > 1 {
+            bail!("--python-threads and --jobs both run on several cores; use one of them");
+        }
+        if args.python_cold || args.mem.is_some() {
+            bail!(
+                "--python-cold and --mem measure one call; they do not combine with --python-threads"
+            );
+        }
+    }
+    if args.starlark_cold
+        && (args.phase != Phase::Run || args.threads > 1 || module_mode(args) == ModuleMode::Reuse)
+    {
+        bail!(
+            "--starlark-cold recompiles the script for every call; it needs --phase run, \
+             --threads 1 and --starlark-module-mode fresh"
+        );
+    }
+    if args.target_iter_time.is_some() {
+        if expand(&args.size).len() > 1 {
+            bail!("--target-iter-time chooses one N per engine and workload; give a single --size");
+        }
+        if args.resume.is_some() {
+            bail!("--resume matches groups by N, which --target-iter-time chooses anew");
+        }
+    }
+    if args.min_sample_ns.is_some() {
+        if args.phase != Phase::Run {
+            bail!("--min-sample-ns batches calls of run(); it needs --phase run");
+        }
+        if args.threads > 1
+            || args.python_threads > 1
+            || args.starlark_cold
+            || args.python_cold
+            || args.mem.is_some()
+            || heap_profile(args)
+        {
+            bail!(
+                "--min-sample-ns times several calls together; --threads, --python-threads, \
+                 --starlark-cold, --python-cold, --mem and --heap-profile measure one"
+            );
+        }
+        if args.python_harness == PythonHarness::Pyperf && engines.contains(&EngineName::Python) {
+            bail!(
+                "--python-harness pyperf calibrates its own loops; --min-sample-ns does not apply"
+            );
+        }
+    }
+    if args.python_harness == PythonHarness::Pyperf
+        && engines.contains(&EngineName::Python)
+        && (args.python_worker
+            || args.mem.is_some()
+            || args.docker_image.is_some()
+            || args.timeout.is_some()
+            || args.python_cold
+            || args.python_threads > 1
+            || args.cache_state == CacheState::Cold)
+    {
+        bail!(
+            "--python-harness pyperf runs and times the workload in pyperf's own processes; \
+             --python-worker, --mem, --docker-image, --timeout, --python-cold, \
+             --python-threads and --cache-state cold do not apply"
+        );
+    }
+    if args.cache_state == CacheState::Cold && args.min_sample_ns.is_some() {
+        bail!(
+            "--cache-state cold clears the caches between iterations, not between the calls \
+             --min-sample-ns batches; use one or the other"
+        );
+    }
+    if args.drop_fs_caches {
+        if args.cache_state != CacheState::Cold {
+            bail!("--drop-fs-caches needs --cache-state cold");
+        }
+        cache::check_drop()?;
+    }
+    if args.clock != Clock::Wall || args.record_clocks {
+        if args.threads > 1 || args.python_threads > 1 || args.starlark_cold || args.python_cold {
+            bail!(
+                "--clock and --record-clocks time one call on one thread; --threads, \
+                 --python-threads, --starlark-cold and --python-cold do not apply"
+            );
+        }
+        if args.python_harness == PythonHarness::Pyperf && engines.contains(&EngineName::Python) {
+            bail!(
+                "--python-harness pyperf times on its own clock; --clock and --record-clocks do not apply"
+            );
+        }
+    }
+    if engines.contains(&EngineName::Custom) {
+        if args.clock != Clock::Wall || args.record_clocks {
+            bail!(
+                "custom engines report their own call time; --clock and --record-clocks do not apply"
+            );
+        }
+        if args.timeout.is_some() {
+            bail!("--timeout cannot interrupt a custom engine's run_iter");
+        }
+    }
+    if args.clock != Clock::Wall {
+        if args.subtract_overhead {
+            bail!(
+                "--subtract-overhead calibrates the overhead on the wall clock; it needs --clock wall"
+            );
+        }
+        if engines.contains(&EngineName::Starlark) {
+            if process_cpu_time().is_none() {
+                bail!("--clock {} needs Linux or macOS for Starlark", args.clock);
+            }
+            if args.clock == Clock::Cpu && args.jobs > 1 {
+                bail!("--clock cpu counts the whole bench process for Starlark; it needs --jobs 1");
+            }
+        }
+    }
+    let mut config_names = HashSet::new();
+    if let Some(c) = args
+        .eval_config
+        .iter()
+        .find(|c| !config_names.insert(&c.name))
+    {
+        bail!("--eval-config {} given more than once", c.name);
+    }
+    let mut param_names = HashSet::new();
+    if let Some(p) = args.param.iter().find(|p| !param_names.insert(&p.name)) {
+        bail!("--param {} given more than once", p.name);
+    }
+    let stems: Vec<&'static str> = workloads
+        .iter()
+        .map(WorkloadName::file_stem)
+        .chain(args.custom_workloads.iter().map(|w| w.name))
+        .collect();
+    if param_names.iter().any(|name| *name == "data")
+        && stems.iter().any(|stem| input_shape(args, stem).is_some())
+    {
+        bail!("parameter name \"data\" is reserved for the --input value");
+    }
+    // --n overrides the size presets; the first size only labels the records.
+    let ns = match &args.n_sweep {
+        Some(sweep) => &sweep.0,
+        None => &args.n,
+    };
+    let sizes: Vec<(Size, usize)> = if ns.is_empty() {
+        expand(&args.size)
+            .into_iter()
+            .map(|s| (s.clone(), s.to_n()))
+            .collect()
+    } else {
+        let label = expand(&args.size).into_iter().next().unwrap_or(Size::M);
+        ns.iter().map(|&n| (label.clone(), n)).collect()
+    };
+
+    let seeds: Vec<u64> = if !args.seeds.is_empty() {
+        args.seeds.clone()
+    } else {
+        let count = args.seed_count.unwrap_or(1);
+        (args.seed..args.seed.saturating_add(count)).collect()
+    };
+
+    let scripts_dir = resolve_scripts_dir(args.scripts_dir.clone());
+    debug!("scripts directory: {}", scripts_dir.display());
+    if let Some(image) = &args.docker_image
+        && engines.contains(&EngineName::Python)
+    {
+        python_engine::ensure_image(image)?;
+    } else if engines.contains(&EngineName::Python) {
+        for (_, binary) in pythons(args) {
+            match find_executable(&binary) {
+                Some(path) => debug!("python interpreter: {}", path.display()),
+                None => debug!("python interpreter {binary:?} not found on PATH"),
+            }
+        }
+    }
+
+    // Collect system metadata once; a resumed run keeps its run_id.
+    let mut sys = SysInfo::collect();
+    let resumed = match &args.resume {
+        Some(id) => {
+            let Some(path) = &args.output else {
+                bail!("--resume needs --output: the file holding the interrupted run");
+            };
+            sys.run_id = id.clone();
+            Some(resume::load(path, id)?)
+        }
+        None => None,
+    };
+    if engines.contains(&EngineName::Starlark) {
+        sys.overheads = starlark_languages(args)
+            .iter()
+            .map(calibrate::measure)
+            .collect::<Result<_>>()?;
+        for (i, o) in sys.overheads.iter().enumerate() {
+            debug!(
+                "overhead of config {i}: timer {} ns, call {} ns, setup {} ns",
+                o.timer_ns, o.call_ns, o.setup_ns
+            );
+        }
+    }
+
+    // Engines measured together: all of them with --interleave, otherwise
+    // one at a time.
+    let group_engines: Vec<GroupEngine> = engines
+        .iter()
+        .flat_map(|engine| {
+            let configs = match engine {
+                EngineName::Starlark => args.eval_config.len().max(1),
+                EngineName::Python => pythons(args).len(),
+                EngineName::Custom => args.custom_engines.len(),
+            };
+            (0..configs).map(move |config| GroupEngine { engine, config })
+        })
+        .collect();
+    let labels: Vec<String> = group_engines
+        .iter()
+        .map(|&g| group_label(args, g))
+        .collect();
+    if let Some(custom) = args
+        .custom_engines
+        .iter()
+        .find(|c| labels.iter().filter(|&l| *l == c.name).count() > 1)
+    {
+        bail!(
+            "more than one engine is named {:?}; their records could not be told apart",
+            custom.name
+        );
+    }
+    let batches: Vec<Vec<GroupEngine>> = if args.interleave {
+        vec![group_engines]
+    } else {
+        group_engines.into_iter().map(|g| vec![g]).collect()
+    };
+
+    // One schedule entry per (size, workload, seed, engine batch), in nested
+    // order unless --shuffle permutes it.
+    let mut schedule = Vec::new();
+    for size_idx in 0..sizes.len() {
+        for &stem in &stems {
+            for &seed in &seeds {
+                for batch in &batches {
+                    schedule.push(ScheduleEntry {
+                        position: 0,
+                        size_idx,
+                        stem,
+                        seed,
+                        engines: batch.clone(),
+                        attempt_base: 0,
+                    });
+                }
+            }
+        }
+    }
+    if args.shuffle {
+        shuffle(&mut schedule, args.seed);
+    }
+    for (position, entry) in schedule.iter_mut().enumerate() {
+        entry.position = position;
+    }
+
+    // Drop the groups the resumed run completed; partially measured ones
+    // start over at the next attempt number, so that only the new records
+    // count.
+    if let Some(resumed) = &resumed {
+        let total = schedule.len();
+        for entry in &mut schedule {
+            let (size, n) = &sizes[entry.size_idx];
+            let key = |&g: &GroupEngine| {
+                resume::group_key(&group_label(args, g), entry.stem, size, *n, entry.seed)
+            };
+            entry.engines.retain(|e| !resumed.done.contains(&key(e)));
+            entry.attempt_base = entry
+                .engines
+                .iter()
+                .filter_map(|e| resumed.attempts.get(&key(e)).copied())
+                .max()
+                .unwrap_or(0);
+        }
+        schedule.retain(|entry| !entry.engines.is_empty());
+        info!(
+            "resuming run {}: {} of {total} group(s) left",
+            sys.run_id,
+            schedule.len()
+        );
+    }
+
+    if let Some(only) = &args.only {
+        for entry in &mut schedule {
+            let (size, n) = &sizes[entry.size_idx];
+            entry.engines.retain(|&g| {
+                only(&resume::group_key(
+                    &group_label(args, g),
+                    entry.stem,
+                    size,
+                    *n,
+                    entry.seed,
+                ))
+            });
+        }
+        schedule.retain(|entry| !entry.engines.is_empty());
+        if schedule.is_empty() {
+            debug!("the filter left no group to measure");
+            return Ok(());
+        }
+    }
+
+    if args.dry_run {
+        return dry_run(args, &schedule, &sizes, &scripts_dir);
+    }
+    // Pinning the main thread before parsing or spawning anything covers the
+    // Python subprocesses too: they inherit the affinity of their parent.
+    if let Some(CpuList(cpus)) = &args.pin_cpu {
+        if affinity::pin_current_thread(cpus)? {
+            debug!("pinned to CPUs {cpus:?}");
+        } else {
+            warn!("CPU pinning is not supported on this platform; --pin-cpu is ignored");
+        }
+    }
+    let supported = match (args.nice, args.realtime) {
+        (Some(nice), _) => priority::set_nice(nice)?,
+        (_, Some(prio)) => priority::set_realtime(prio)?,
+        (None, None) => true,
+    };
+    if !supported {
+        warn!("priority control is not supported on this platform; --nice/--realtime is ignored");
+    }
+    sys.priority = priority::current();
+    if let Some(priority) = &sys.priority {
+        debug!("scheduling priority: {priority}");
+    }
+
+    sys.env = envcheck::collect(&affinity::available());
+    let problems = envcheck::problems(&sys.env);
+    if args.strict_env && !problems.is_empty() {
+        bail!(
+            "environment checks failed (--strict-env):\n  {}",
+            problems.join("\n  ")
+        );
+    }
+    for problem in &problems {
+        warn!("{problem}");
+    }
+    if !problems.is_empty() {
+        warn!(
+            "pass --strict-env to refuse such runs; see \"Reducing measurement noise\" in README.md"
+        );
+    }
+    // Removes the cgroups when the run ends, successfully or not.
+    let _cgroup = match &args.cgroup {
+        Some(parent) => Some(cgroup::init(
+            parent.as_deref(),
+            cgroup::Limits {
+                cpus: args.cgroup_cpus,
+                memory_mib: args.cgroup_memory,
+            },
+        )?),
+        None => None,
+    };
+    if !args.library {
+        output::init(args.output.as_deref(), resumed.is_some())?;
+    }
+    // The bar would tear records written to the same terminal.
+    let show_progress = !args.no_progress
+        && std::io::stderr().is_terminal()
+        && (args.output.is_some() || !std::io::stdout().is_terminal());
+    progress::init(schedule.len(), show_progress);
+
+    // Parse every Starlark script up front, so workers can share them; the
+    // first scheduled group of each workload reports the setup times.
+    let languages = starlark_languages(args);
+    let mut scripts = ScriptCache::default();
+    let mut prepare_times = HashMap::new();
+    for entry in &schedule {
+        if entry
+            .engines
+            .iter()
+            .any(|g| *g.engine == EngineName::Starlark)
+            && let Some(times) = prepare_starlark(
+                &mut scripts,
+                entry.stem,
+                &script_path(args, &scripts_dir, entry.stem, "starlark"),
+                &languages,
+                args.timeout,
+                args.profile.iter().any(|&k| k != ProfileKind::Native),
+                args.typecheck,
+            )?
+        {
+            prepare_times.insert(entry.position, times);
+            for config in 0..languages.len() {
+                let prepared = scripts.get(entry.stem, config);
+                emit_frozen_heap(args, &sys, entry.stem, config, prepared)?;
+            }
+        }
+    }
+    let suite = Suite {
+        args,
+        sys,
+        scripts_dir,
+        scripts,
+        sizes,
+        prepare_times,
+    };
+
+    // Summaries per (size, workload, seed) with the number of entries still
+    // to run; ratios are emitted once all of them have finished. A resumed
+    // run starts from the summaries it already recorded.
+    let mut comparisons: HashMap<_, (usize, Vec<SummaryRecord>)> = HashMap::new();
+    for entry in &schedule {
+        comparisons
+            .entry((entry.size_idx, entry.stem, entry.seed))
+            .or_insert((0, Vec::new()))
+            .0 += 1;
+    }
+    if let Some(resumed) = resumed {
+        for summary in resumed.summaries {
+            let comparison = comparisons.iter_mut().find(|((size_idx, stem, seed), _)| {
+                let (size, n) = &suite.sizes[*size_idx];
+                *stem == summary.workload
+                    && *seed == summary.seed
+                    && *n == summary.n
+                    && size.to_string() == summary.size
+            });
+            if let Some((_, (_, done))) = comparison {
+                done.push(summary);
+            }
+        }
+    }
+    let canary_before = run_canary(args, &suite.sys, "before", None)?;
+    let comparisons = Mutex::new(comparisons);
+    let next = AtomicUsize::new(0);
+    let failed = AtomicBool::new(false);
+
+    // Each worker takes the next schedule entry until none are left or
+    // another worker has failed.
+    let work = |cpus: Option<&[usize]>| -> Result<Tally> {
+        if let Some(cpus) = cpus {
+            debug!("pinning worker to CPUs {cpus:?}");
+        }
+        if let Some(cpus) = cpus
+            && !affinity::pin_current_thread(cpus)?
+        {
+            warn!("CPU pinning is not supported on this platform; --jobs runs unpinned");
+        }
+        let mut tally = Tally::default();
+        let mut first = true;
+        while !failed.load(Ordering::Relaxed) {
+            let position = next.fetch_add(1, Ordering::Relaxed);
+            let Some(entry) = schedule.get(position) else {
+                break;
+            };
+            if !std::mem::take(&mut first) {
+                cool_down(args.cooldown);
+            }
+            let result = run_entry(&suite, entry).and_then(|(summaries, t)| {
+                tally.over_budget += t.over_budget;
+                tally.timeouts += t.timeouts;
+                tally.noisy += t.noisy;
+                let mut comparisons = comparisons.lock().expect("comparisons lock poisoned");
+                let (remaining, done) = comparisons
+                    .get_mut(&(entry.size_idx, entry.stem, entry.seed))
+                    .expect("every entry is counted");
+                done.extend(summaries);
+                *remaining -= 1;
+                // A wrong result makes the ratio meaningless, so it is
+                // replaced by the mismatch records.
+                if *remaining == 0 {
+                    let expected =
+                        custom_workload(args, entry.stem).and_then(|w| w.expected.as_deref());
+                    if check_results(done, expected)? {
+                        emit_ratios(done)?;
+                    } else {
+                        tally.mismatches += 1;
+                    }
+                }
+                progress::inc();
+                Ok(())
+            });
+            if let Err(e) = result {
+                failed.store(true, Ordering::Relaxed);
+                return Err(e);
+            }
+        }
+        Ok(tally)
+    };
+
+    let tallies: Result<Vec<Tally>> = if args.jobs > 1 || args.starlark_thread_stack.is_some() {
+        // Without --jobs, a single unpinned worker thread, for its stack size.
+        let core_sets = if args.jobs > 1 {
+            affinity::split(args.jobs as usize)?
+                .into_iter()
+                .map(Some)
+                .collect()
+        } else {
+            vec![None]
+        };
+        std::thread::scope(|scope| {
+            let handles = core_sets
+                .iter()
+                .map(|cpus| {
+                    let mut builder = std::thread::Builder::new();
+                    if let Some(mib) = args.starlark_thread_stack {
+                        builder = builder.stack_size(mib as usize * 1024 * 1024);
+                    }
+                    builder
+                        .spawn_scoped(scope, || work(cpus.as_deref()))
+                        .context("cannot start worker thread")
+                })
+                .collect::<Result<Vec<_>>>()?;
+            handles
+                .into_iter()
+                .map(|h| h.join().expect("worker panicked"))
+                .collect()
+        })
+    } else {
+        work(None).map(|t| vec![t])
+    };
+    progress::finish();
+    let tallies = tallies?;
+    run_canary(args, &suite.sys, "after", canary_before)?;
+    let timeouts: usize = tallies.iter().map(|t| t.timeouts).sum();
+    let over_budget_groups: usize = tallies.iter().map(|t| t.over_budget).sum();
+    let mismatches: usize = tallies.iter().map(|t| t.mismatches).sum();
+    let noisy_groups: usize = tallies.iter().map(|t| t.noisy).sum();
+
+    output::flush()?;
+    if timeouts > 0 {
+        bail!("{timeouts} group(s) timed out");
+    }
+    if mismatches > 0 {
+        fail_on(
+            &args.fail_on,
+            FailureClass::ChecksumMismatch,
+            format!("{mismatches} group(s) computed different or unexpected results"),
+        )?;
+    }
+    if over_budget_groups > 0 {
+        return Err(Failure {
+            class: FailureClass::Noisy,
+            message: format!(
+                "{over_budget_groups} group(s) still exceed --max-stddev-pct after {} re-measurement(s)",
+                args.remeasure
+            ),
+        }
+        .into());
+    }
+    if noisy_groups > 0 {
+        fail_on(
+            &args.fail_on,
+            FailureClass::Noisy,
+            format!(
+                "{noisy_groups} group(s) exceed --noise-threshold of {}%",
+                args.noise_threshold
+            ),
+        )?;
+    }
+    Ok(())
+}
+
+/// Everything a worker needs to run schedule entries.
+struct Suite<'a> {
+    args: &'a RunArgs,
+    sys: SysInfo,
+    scripts_dir: PathBuf,
+    scripts: ScriptCache,
+    sizes: Vec<(Size, usize)>,
+    /// Starlark setup times per --eval-config, keyed by the position of the
+    /// entry that reports them.
+    prepare_times: HashMap<usize, Vec<starlark_engine::PrepareTimes>>,
+}
+
+/// Groups of a schedule entry that failed the run's quality checks.
+#[derive(Default)]
+struct Tally {
+    over_budget: usize,
+    timeouts: usize,
+    noisy: usize,
+    /// Comparisons whose engines computed different results.
+    mismatches: usize,
+}
+
+/// Measure one schedule entry, re-measuring rejected groups, and return the
+/// accepted summaries.
+fn run_entry(suite: &Suite, entry: &ScheduleEntry) -> Result<(Vec<SummaryRecord>, Tally)> {
+    let args = suite.args;
+    let &ScheduleEntry {
+        position,
+        size_idx,
+        stem,
+        seed,
+        attempt_base,
+        ..
+    } = entry;
+    let (size, n) = &suite.sizes[size_idx];
+    let mut summaries = Vec::new();
+    let mut tally = Tally::default();
+    debug!("group {position}: {stem}/{size} n={n} seed={seed}");
+    // Each engine's N, chosen once for all of its attempts.
+    let mut pending = entry
+        .engines
+        .iter()
+        .map(|&g| match args.target_iter_time {
+            Some(target) => Ok((g, target_n(suite, g, stem, size, *n, seed, target)?)),
+            None => Ok((g, *n)),
+        })
+        .collect::<Result<Vec<_>>>()?;
+    for attempt in attempt_base.. {
+        if attempt > attempt_base {
+            cool_down(args.cooldown);
+        }
+        let prepare_times = if attempt == attempt_base {
+            suite.prepare_times.get(&position)
+        } else {
+            None
+        };
+        let cells: Vec<Cell> = pending
+            .iter()
+            .map(|&(_, n)| Cell {
+                stem,
+                size,
+                n,
+                seed,
+                attempt,
+                attempt_base,
+                position: args.shuffle.then_some(position),
+            })
+            .collect();
+        let mut groups = pending
+            .iter()
+            .zip(&cells)
+            .map(|(&(g, _), cell)| {
+                Group::new(
+                    g,
+                    args,
+                    cell,
+                    &suite.scripts_dir,
+                    &suite.sys,
+                    &suite.scripts,
+                    prepare_times.map(|times| times[g.config]),
+                )
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let timed_out = measure_groups(args, &mut groups)?;
+
+        let mut rejected = Vec::new();
+        for ((engine, mut group), timed_out) in pending.iter().zip(groups).zip(timed_out) {
+            if timed_out {
+                group.write_records()?;
+                let record = group.timeout_record();
+                warn!(
+                    "{}/{stem}/{size}: iteration exceeded --timeout of {}s; skipping the rest of the group",
+                    record.engine, record.timeout_secs
+                );
+                output::emit(&record)?;
+                tally.timeouts += 1;
+                continue;
+            }
+            let Some(summary) = group.finish()? else {
+                continue;
+            };
+            if summary.rejected {
+                info!(
+                    "re-measuring {}/{stem}/{size}: CV {:.1}% exceeds --max-stddev-pct (attempt {} of {})",
+                    summary.engine,
+                    summary.cv_pct,
+                    attempt - attempt_base + 1,
+                    args.remeasure
+                );
+                rejected.push(*engine);
+                continue;
+            }
+            if over_budget(args, summary.cv_pct) {
+                tally.over_budget += 1;
+            }
+            if summary.noisy {
+                tally.noisy += 1;
+            }
+            summaries.push(summary);
+        }
+        if rejected.is_empty() {
+            break;
+        }
+        pending = rejected;
+    }
+    Ok((summaries, tally))
+}
+
+/// Time the noise canary (unless --no-canary) and emit its record, warning
+/// when it is noisier than --noise-threshold or, `after`, when its median
+/// moved by more than that since `before`. Returns the median.
+fn run_canary(
+    args: &RunArgs,
+    sys: &SysInfo,
+    when: &'static str,
+    before_ns: Option<u64>,
+) -> Result<Option<u64>> {
+    if args.no_canary {
+        return Ok(None);
+    }
+    let samples = canary::measure();
+    let median_ns = stats::median(&samples) as u64;
+    let cv_pct = stats::cv_pct(&samples);
+    let median_change_pct = before_ns
+        .filter(|&b| b > 0)
+        .map(|b| (median_ns as f64 - b as f64) / b as f64 * 100.0);
+    debug!("canary {when}: median {median_ns} ns, CV {cv_pct:.1}%");
+    if cv_pct > args.noise_threshold {
+        warn!(
+            "noise canary {when} the run: CV {cv_pct:.1}% exceeds {:.1}%; the machine is busy",
+            args.noise_threshold
+        );
+    }
+    if let Some(change) = median_change_pct.filter(|c| c.abs() > args.noise_threshold) {
+        warn!(
+            "noise canary: the machine ran {:.1}% {} after the run than before it",
+            change.abs(),
+            if change > 0.0 { "slower" } else { "faster" }
+        );
+    }
+    output::emit(&CanaryRecord {
+        schema_version: SCHEMA_VERSION,
+        run_id: sys.run_id.clone(),
+        kind: "canary",
+        when,
+        samples: samples.len(),
+        median_ns,
+        min_ns: samples.iter().copied().min().unwrap_or(0),
+        max_ns: samples.iter().copied().max().unwrap_or(0),
+        cv_pct,
+        median_change_pct,
+        cpu_model: sys.cpu_model.clone(),
+        os: sys.os.clone(),
+    })?;
+    Ok(Some(median_ns))
+}
+
+/// Probe calls --target-iter-time makes per engine and workload at most.
+const TARGET_N_PROBES: u32 = 8;
+
+/// The N at which one iteration of `stem` on `g` takes about `target`
+/// (--target-iter-time): starting from `n`, untimed probe calls rescale it
+/// linearly until a call lands within 10% of the target.
+fn target_n(
+    suite: &Suite,
+    g: GroupEngine,
+    stem: &'static str,
+    size: &Size,
+    mut n: usize,
+    seed: u64,
+    target: Duration,
+) -> Result<usize> {
+    let args = suite.args;
+    let target_ns = target.as_nanos() as f64;
+    let mut last: Option<(usize, f64)> = None;
+    for _ in 0..TARGET_N_PROBES {
+        let cell = Cell {
+            stem,
+            size,
+            n,
+            seed,
+            attempt: 0,
+            attempt_base: 0,
+            position: None,
+        };
+        let mut group = Group::new(
+            g,
+            args,
+            &cell,
+            &suite.scripts_dir,
+            &suite.sys,
+            &suite.scripts,
+            None,
+        )?;
+        let ns = (group.probe()?.as_nanos() as f64).max(1.0);
+        debug!(
+            "{}/{stem}: n={n} takes {:.3} ms",
+            group_label(args, g),
+            ns / 1e6
+        );
+        let scale = target_ns / ns;
+        if (0.9..=1.1).contains(&scale) {
+            break;
+        }
+        // A workload whose time does not follow N would grow it forever.
+        if let Some((last_n, last_ns)) = last
+            && (n as f64 / last_n as f64 - 1.0).abs() > 1.0
+            && (ns / last_ns - 1.0).abs() < 0.1
+        {
+            warn!(
+                "{}/{stem}: eval time does not follow N; keeping n={n}",
+                group_label(args, g)
+            );
+            break;
+        }
+        last = Some((n, ns));
+        let next = ((n as f64 * scale.clamp(0.01, 100.0)).round() as usize).max(1);
+        if next == n {
+            break;
+        }
+        n = next;
+    }
+    info!(
+        "{}/{stem}: n={n} for --target-iter-time {:?}",
+        group_label(args, g),
+        target
+    );
+    Ok(n)
+}
+
+/// --target-iter-time in nanoseconds, for the records.
+fn target_iter_ns(args: &RunArgs) -> Option<u64> {
+    args.target_iter_time.map(|t| t.as_nanos() as u64)
+}
+
+/// A planned group: one workload, size and seed, measured on the engines
+/// run together.
+struct ScheduleEntry<'a> {
+    /// Index in the full schedule, stable across --resume.
+    position: usize,
+    size_idx: usize,
+    stem: &'static str,
+    seed: u64,
+    engines: Vec<GroupEngine<'a>>,
+    /// First re-measurement attempt number; past the attempts a resumed run
+    /// already recorded.
+    attempt_base: u32,
+}
+
+/// An engine measured in a schedule entry, in one of its configurations:
+/// the index into --eval-config for Starlark (0 without any), and into
+/// [`pythons`] for Python.
+#[derive(Clone, Copy)]
+struct GroupEngine<'a> {
+    engine: &'a EngineName,
+    config: usize,
+}
+
+/// The `engine` recorded for a group: its engine label, followed in
+/// brackets by the --eval-config name for Starlark, or by the interpreter's
+/// name when several are measured.
+fn group_label(args: &RunArgs, g: GroupEngine) -> String {
+    let label = engine_label(args, g.engine);
+    match g.engine {
+        EngineName::Starlark => match args.eval_config.get(g.config) {
+            Some(config) => format!("{label}[{}]", config.name),
+            None => label.to_string(),
+        },
+        EngineName::Python => {
+            let pythons = pythons(args);
+            match pythons.len() {
+                1 => label.to_string(),
+                _ => format!("{label}[{}]", pythons[g.config].0),
+            }
+        }
+        EngineName::Custom => args.custom_engines[g.config].name.clone(),
+    }
+}
+
+/// --python-args split into words, None without any.
+fn python_args(args: &RunArgs) -> Option<Vec<String>> {
+    let words: Vec<String> = args
+        .python_args
+        .iter()
+        .flat_map(|a| a.split_whitespace())
+        .map(str::to_string)
+        .collect();
+    (!words.is_empty()).then_some(words)
+}
+
+/// Whether Starlark records the heap profile: with --heap-profile, or to pair
+/// with --mem.
+fn heap_profile(args: &RunArgs) -> bool {
+    args.heap_profile || args.mem.is_some()
+}
+
+/// "pyperf" with --python-harness pyperf, for the records.
+fn pyperf_label(args: &RunArgs) -> Option<String> {
+    (args.python_harness == PythonHarness::Pyperf).then(|| args.python_harness.to_string())
+}
+
+/// Whether `engine` labels Python records ("python", "python-cold",
+/// "python-threads", and any of them with an interpreter name in brackets).
+fn is_python(engine: &str) -> bool {
+    engine.starts_with("python")
+}
+
+/// The interpreters to measure, as (name, binary): each --python as given,
+/// `pythonVERSION` for each of --pythons, named by its version, or the
+/// --provision-python installs.
+fn pythons(args: &RunArgs) -> Vec<(String, String)> {
+    if !args.provisioned.is_empty() {
+        return args.provisioned.clone();
+    }
+    if args.pythons.is_empty() {
+        return args.python.iter().map(|p| (p.clone(), p.clone())).collect();
+    }
+    args.pythons
+        .iter()
+        .map(|v| (v.clone(), format!("python{v}")))
+        .collect()
+}
+
+/// The language of every --eval-config, in order, or of the defaults
+/// without any.
+fn starlark_languages(args: &RunArgs) -> Vec<starlark_engine::Language> {
+    let language = starlark_engine::Language::new(args.dialect, &args.dialect_features)
+        .with_max_callstack(args.starlark_max_callstack)
+        .with_libraries(&expand(&args.starlark_lib));
+    if args.eval_config.is_empty() {
+        return vec![language];
+    }
+    args.eval_config
+        .iter()
+        .map(|config| language.clone().with_eval_options(&config.options))
+        .collect()
+}
+
+/// How many back-to-back calls of `run`, each taking about `call`, one
+/// timed sample needs to last at least `min_ns` (--min-sample-ns).
+fn calls_per_sample(min_ns: u64, call: std::time::Duration) -> u32 {
+    let call_ns = (call.as_nanos() as u64).max(1);
+    if call_ns >= min_ns {
+        return 1;
+    }
+    u32::try_from(min_ns.div_ceil(call_ns)).unwrap_or(u32::MAX)
+}
+
+/// --starlark-module-mode, with --reuse-module applied.
+fn module_mode(args: &RunArgs) -> ModuleMode {
+    if args.reuse_module {
+        ModuleMode::Reuse
+    } else {
+        args.starlark_module_mode
+    }
+}
+
+/// Items an iteration of `threads` concurrent calls works through: `n` each,
+/// or `n` between them with `run_parallel`.
+fn work_items(n: usize, threads: u32, run_parallel: bool) -> usize {
+    if run_parallel {
+        n
+    } else {
+        n * threads as usize
+    }
+}
+
+/// The `clock` of the records: None for wall, the default.
+fn clock_label(args: &RunArgs) -> Option<String> {
+    Some(args.clock.to_string()).filter(|_| args.clock != Clock::Wall)
+}
+
+/// The `timeout_secs` of the Starlark records: None without --timeout.
+fn timeout_secs(args: &RunArgs) -> Option<f64> {
+    args.timeout.map(|t| t.as_secs_f64())
+}
+
+/// The `cache_state` of the records: None when warm, the default.
+fn cache_state(args: &RunArgs) -> Option<String> {
+    match (args.cache_state, args.drop_fs_caches) {
+        (CacheState::Warm, _) => None,
+        (CacheState::Cold, false) => Some("cold".into()),
+        (CacheState::Cold, true) => Some("cold+fs".into()),
+    }
+}
+
+/// --starlark-max-callstack, or starlark-rust's default.
+fn max_callstack(args: &RunArgs) -> u32 {
+    args.starlark_max_callstack
+        .unwrap_or(starlark_engine::DEFAULT_MAX_CALLSTACK)
+}
+
+/// Engine name as written to records.
+fn engine_label(args: &RunArgs, engine: &EngineName) -> &'static str {
+    match engine {
+        EngineName::Starlark if args.starlark_cold => "starlark-cold",
+        EngineName::Starlark if args.threads > 1 => "starlark-threads",
+        EngineName::Starlark if args.phase == Phase::Parse => "starlark-parse",
+        EngineName::Starlark if args.phase == Phase::Compile => "starlark-compile",
+        EngineName::Starlark if module_mode(args) == ModuleMode::Reuse => "starlark-reuse",
+        EngineName::Starlark => "starlark",
+        EngineName::Python if args.python_cold => "python-cold",
+        EngineName::Python if args.python_threads > 1 => "python-threads",
+        EngineName::Python => "python",
+        EngineName::Custom => "custom",
+    }
+}
+
+/// The --input shape `stem` runs with: the flag, or the workload's default.
+fn input_shape(args: &RunArgs, stem: &str) -> Option<InputShape> {
+    args.input.or_else(|| {
+        WorkloadName::value_variants()
+            .iter()
+            .find(|w| w.file_stem() == stem)
+            .and_then(WorkloadName::default_input)
+    })
+}
+
+/// The workload registered as `stem`, if any.
+fn custom_workload<'a>(args: &'a RunArgs, stem: &str) -> Option<&'a RegisteredWorkload> {
+    args.custom_workloads.iter().find(|w| w.name == stem)
+}
+
+/// The script of workload `stem` in `dir` ("starlark" or "python"): a
+/// registered workload's own, or the bundled one under `scripts_dir`.
+fn script_path(args: &RunArgs, scripts_dir: &Path, stem: &str, dir: &str) -> PathBuf {
+    custom_workload(args, stem)
+        .and_then(|w| w.script(dir))
+        .unwrap_or_else(|| {
+            let extension = if dir == "starlark" { "star" } else { "py" };
+            scripts_dir.join(dir).join(format!("{stem}.{extension}"))
+        })
+}
+
+/// The --starlark-lib names, None without any.
+fn starlark_libs(args: &RunArgs) -> Option<Vec<String>> {
+    let libs: Vec<String> = expand(&args.starlark_lib)
+        .iter()
+        .map(StarlarkLib::to_string)
+        .collect();
+    (!libs.is_empty()).then_some(libs)
+}
+
+/// --dialect as recorded: the preset, then any --dialect-features.
+fn dialect_label(args: &RunArgs) -> String {
+    let name = args
+        .dialect
+        .to_possible_value()
+        .expect("no skipped variants");
+    if args.dialect_features.is_empty() {
+        return name.get_name().to_string();
+    }
+    let features: Vec<String> = args
+        .dialect_features
+        .iter()
+        .map(|t| {
+            let prefix = if t.enabled { "" } else { "no-" };
+            format!("{prefix}{}", t.feature)
+        })
+        .collect();
+    format!("{}:{}", name.get_name(), features.join(","))
+}
+
+// ---------------------------------------------------------------------------
+// Dry run
+// ---------------------------------------------------------------------------
+
+/// Validate the scripts of every scheduled group and print the plan. The
+/// runtime estimate scales medians from a previous run written to --output,
+/// linearly in n when that run used a different n.
+fn dry_run(
+    args: &RunArgs,
+    schedule: &[ScheduleEntry],
+    sizes: &[(Size, usize)],
+    scripts_dir: &Path,
+) -> Result<()> {
+    let languages = starlark_languages(args);
+    let pythons = pythons(args);
+    let mut problems = 0;
+    let mut checked = HashSet::new();
+    let mut check = |g: GroupEngine, stem: &str| {
+        let label = group_label(args, g);
+        if !checked.insert((label.clone(), stem.to_string())) {
+            return;
+        }
+        let result = match g.engine {
+            EngineName::Starlark => {
+                let language = &languages[g.config];
+                let path = script_path(args, scripts_dir, stem, "starlark");
+                std::fs::read_to_string(&path)
+                    .with_context(|| format!("cannot read {}", path.display()))
+                    .and_then(|body| {
+                        let load_dir = path.parent().unwrap_or(Path::new("."));
+                        starlark_engine::prepare(&body, load_dir, language, None, false, false)
+                            .map(|_| ())
+                    })
+            }
+            EngineName::Python => {
+                let path = script_path(args, scripts_dir, stem, "python");
+                let binary = &pythons[g.config].1;
+                if !path.is_file() {
+                    Err(anyhow::anyhow!(
+                        "Python script not found: {}",
+                        path.display()
+                    ))
+                } else if args.docker_image.is_none() && find_executable(binary).is_none() {
+                    Err(anyhow::anyhow!(
+                        "python interpreter {binary:?} not found on PATH"
+                    ))
+                } else {
+                    Ok(())
+                }
+            }
+            EngineName::Custom => {
+                let workload = Workload {
+                    name: stem,
+                    scripts_dir,
+                    registered: custom_workload(args, stem),
+                };
+                let registered = &args.custom_engines[g.config];
+                let mut engine = registered.engine.lock().expect("engine lock poisoned");
+                engine.prepare(&workload)
+            }
+        };
+        if let Err(e) = &result {
+            error!("{label}/{stem}: {e:#}");
+            problems += 1;
+        }
+    };
+    for entry in schedule {
+        for &g in &entry.engines {
+            check(g, entry.stem);
+        }
+    }
+
+    let previous = match &args.output {
+        Some(path) if path.is_file() => {
+            Some((path, results::load_medians(std::slice::from_ref(path))?))
+        }
+        _ => None,
+    };
+    let estimate = |engine: &str, stem: &str, n: usize| -> Option<f64> {
+        let (_, medians) = previous.as_ref()?;
+        medians
+            .iter()
+            .filter_map(|(size, by_engine)| Some((size.n, *by_engine.get(engine)?.get(stem)?)))
+            .min_by(|a, b| {
+                let dist = |m: u64| (m.max(1) as f64 / n.max(1) as f64).ln().abs();
+                dist(a.0).total_cmp(&dist(b.0))
+            })
+            .map(|(m, median)| median * n as f64 / m.max(1) as f64)
+    };
+
+    let warmup = if args.auto_warmup {
+        format!("≤{}", args.max_warmup)
+    } else {
+        args.warmup.to_string()
+    };
+    let (iters, max_iters) = match args.target_ci {
+        Some(_) => (
+            format!("{MIN_ADAPTIVE_ITERS}-{}", args.max_iters),
+            args.max_iters,
+        ),
+        None if args.min_time.is_some() => (format!("≥{}", args.iters), args.iters),
+        None => (args.iters.to_string(), args.iters),
+    };
+    let min_time_ns = args.min_time.map_or(0.0, |t| t.as_nanos() as f64);
+    let max_warmup = if args.auto_warmup {
+        args.max_warmup
+    } else {
+        args.warmup
+    };
+
+    println!(
+        "{:>4}  {:<16} {:<20} {:<4} {:>9} {:>6}  {:>8}  {:>10}",
+        "#", "engine", "workload", "size", "n", "seed", "warmup", "iters"
+    );
+    let (mut total_ns, mut unknown, mut groups) = (0.0, 0, 0);
+    for entry in schedule {
+        let ScheduleEntry {
+            position,
+            size_idx,
+            stem,
+            seed,
+            ..
+        } = entry;
+        let (size, n) = (sizes[*size_idx].0.to_string(), &sizes[*size_idx].1);
+        for &g in &entry.engines {
+            let label = group_label(args, g);
+            match estimate(&label, stem, *n) {
+                Some(ns) => {
+                    total_ns +=
+                        ns * f64::from(max_warmup) + (ns * f64::from(max_iters)).max(min_time_ns)
+                }
+                None => unknown += 1,
+            }
+            // --iter-cooldown sleeps once per measurement round.
+            let rounds = match g.engine {
+                EngineName::Python if !args.interleave => 1,
+                _ => max_iters,
+            };
+            total_ns += args.iter_cooldown.unwrap_or(0) as f64 * 1e6 * f64::from(rounds);
+            groups += 1;
+            println!(
+                "{position:>4}  {label:<16} {stem:<20} {size:<4} {n:>9} {seed:>6}  {warmup:>8}  {iters:>10}"
+            );
+        }
+    }
+
+    let gaps = schedule.len().saturating_sub(1) as f64;
+    total_ns += args.cooldown.unwrap_or(0) as f64 * 1e6 * gaps;
+
+    println!("{groups} group(s)");
+    match &previous {
+        Some((path, _)) => {
+            let bound = if args.target_ci.is_some() || args.auto_warmup {
+                "at most "
+            } else {
+                ""
+            };
+            print!(
+                "estimated runtime: {bound}~{:.0}s (from {})",
+                total_ns / 1e9,
+                path.display()
+            );
+            if unknown > 0 {
+                print!(", plus {unknown} group(s) without previous results");
+            }
+            println!();
+        }
+        None => println!("estimated runtime: unknown (no previous results in --output)"),
+    }
+
+    if problems > 0 {
+        bail!("dry run found {problems} problem(s)");
+    }
+    Ok(())
+}
+
+/// One measurement group: a workload at a given problem size.
+struct Cell<'a> {
+    stem: &'static str,
+    /// Size label for records; `n` may differ from the preset with --n.
+    size: &'a Size,
+    n: usize,
+    seed: u64,
+    /// Re-measurement attempt (--max-stddev-pct), 0 for the first.
+    attempt: u32,
+    /// First attempt of this invocation; above 0 when resuming a group.
+    attempt_base: u32,
+    /// Index in the --shuffle schedule; None when not shuffled.
+    position: Option<usize>,
+}
+
+/// Error raised by an engine when a single iteration exceeds --timeout.
+#[derive(Debug)]
+struct TimedOut;
+
+impl std::fmt::Display for TimedOut {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("iteration exceeded --timeout")
+    }
+}
+
+impl std::error::Error for TimedOut {}
+
+/// Error for an outcome of a --fail-on class; `main` exits with its code.
+#[derive(Debug)]
+struct Failure {
+    class: FailureClass,
+    message: String,
+}
+
+impl std::fmt::Display for Failure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} ({})", self.message, self.class)
+    }
+}
+
+impl std::error::Error for Failure {}
+
+/// Fail with `message` if `class` is in the --fail-on `policy`, otherwise
+/// only warn.
+fn fail_on(policy: &[FailureClass], class: FailureClass, message: String) -> Result<()> {
+    if policy.contains(&class) {
+        return Err(Failure { class, message }.into());
+    }
+    warn!("{message}");
+    Ok(())
+}
+
+/// Fisher–Yates shuffle driven by a SplitMix64 stream, so a given seed
+/// always yields the same schedule.
+fn shuffle<T>(items: &mut [T], seed: u64) {
+    let mut state = seed;
+    let mut next = || {
+        state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    };
+    for i in (1..items.len()).rev() {
+        let j = (next() % (i as u64 + 1)) as usize;
+        items.swap(i, j);
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Iteration control
+// ---------------------------------------------------------------------------
+
+/// Minimum measurement iterations before --target-ci may stop a group.
+const MIN_ADAPTIVE_ITERS: usize = 5;
+
+/// Whether the target confidence interval has been reached.
+fn ci_converged(args: &RunArgs, measured: &[u64]) -> bool {
+    match args.target_ci {
+        Some(target) => {
+            measured.len() >= MIN_ADAPTIVE_ITERS
+                && stats::ci_pct(measured).is_some_and(|ci| ci <= target)
+        }
+        None => false,
+    }
+}
+
+/// Whether enough measurement iterations have been collected for this group.
+fn enough_samples(args: &RunArgs, measured: &[u64]) -> bool {
+    match args.target_ci {
+        Some(_) => measured.len() >= args.max_iters as usize || ci_converged(args, measured),
+        None => {
+            measured.len() >= args.iters as usize
+                && args
+                    .min_time
+                    .is_none_or(|t| measured.iter().sum::<u64>() as u128 >= t.as_nanos())
+        }
+    }
+}
+
+/// Size of the next measurement batch for engines that run several
+/// iterations per invocation (Python). Adaptive mode doubles the sample
+/// count each round, so the number of subprocesses stays logarithmic;
+/// --min-time extrapolates from the mean so far.
+fn next_batch(args: &RunArgs, measured: &[u64]) -> u32 {
+    if let Some(min_time) = args.min_time
+        && measured.len() >= args.iters as usize
+    {
+        let total: u64 = measured.iter().sum();
+        let mean = (total / measured.len().max(1) as u64).max(1);
+        let missing = (min_time.as_nanos() as u64).saturating_sub(total);
+        return missing.div_ceil(mean).clamp(1, u64::from(u32::MAX)) as u32;
+    }
+    let measured = measured.len();
+    match args.target_ci {
+        Some(_) => {
+            let want = if measured == 0 {
+                MIN_ADAPTIVE_ITERS
+            } else {
+                measured
+            };
+            want.min(args.max_iters as usize - measured) as u32
+        }
+        None => args.iters - measured as u32,
+    }
+}
+
+/// Window size for --auto-warmup steady-state detection.
+const WARMUP_WINDOW: usize = 5;
+
+/// Tracks whether a measurement group is still warming up, either for a
+/// fixed --warmup count or until steady state is detected (--auto-warmup).
+struct Warmup {
+    auto: bool,
+    limit: usize,
+    tol_pct: f64,
+    samples: Vec<u64>,
+    settled: bool,
+}
+
+impl Warmup {
+    fn new(args: &RunArgs) -> Self {
+        Warmup {
+            auto: args.auto_warmup,
+            limit: if args.auto_warmup {
+                args.max_warmup
+            } else {
+                args.warmup
+            } as usize,
+            tol_pct: args.noise_threshold,
+            samples: Vec::new(),
+            settled: false,
+        }
+    }
+
+    /// Whether the next iteration is a warmup iteration.
+    fn active(&self) -> bool {
+        !self.settled && self.samples.len() < self.limit
+    }
+
+    /// Record the eval time of a warmup iteration.
+    fn record(&mut self, eval_ns: u64, label: &str) {
+        self.samples.push(eval_ns);
+        if self.auto {
+            self.settled =
+                stats::steady_state(&self.samples, WARMUP_WINDOW, self.tol_pct).is_some();
+            if !self.settled && self.samples.len() >= self.limit {
+                warn_unsettled(label, self.limit);
+            }
+        }
+    }
+}
+
+fn warn_unsettled(label: &str, limit: usize) {
+    warn!("{label}: no steady state after {limit} warmup iterations; measuring anyway");
+}
+
+// ---------------------------------------------------------------------------
+// Summary records
+// ---------------------------------------------------------------------------
+
+/// t-statistic above which an upward eval-time trend is reported as drift.
+const DRIFT_T_THRESHOLD: f64 = 2.0;
+
+/// Whether a group's CV exceeds the --max-stddev-pct budget.
+fn over_budget(args: &RunArgs, cv_pct: f64) -> bool {
+    args.max_stddev_pct.is_some_and(|max| cv_pct > max)
+}
+
+/// Summary fields that are not derived from the eval times.
+#[derive(Default)]
+struct GroupOutcome {
+    /// `result` of the last measurement iteration.
+    result: Option<i64>,
+    /// --bytecode-stats: instructions one call executes (Starlark).
+    bytecode_instructions: Option<u64>,
+    /// Whether the group's script came from the script cache (Starlark).
+    script_cache_hit: Option<bool>,
+    engine_version: Option<String>,
+    /// The interpreter that ran the group (Python).
+    interpreter: Option<python_engine::Interpreter>,
+    /// The --eval-config the group ran in (Starlark).
+    eval_config: Option<String>,
+    /// The cgroup's memory high-water mark over the group, warmup included.
+    cgroup_memory_peak_kb: Option<u64>,
+    /// --cpu-freq: the frequencies sampled while the group measured.
+    cpu_freq: Option<cpufreq::Stats>,
+    /// The threads split `n` with `run_parallel`.
+    run_parallel: bool,
+    /// The measurement iterations' collector pauses (Python).
+    python_gc_ns: Option<Vec<u64>>,
+}
+
+/// Watch the memory high-water mark of the bench's cgroup for one group,
+/// unless other groups run in it at the same time (--jobs, --interleave).
+fn watch_memory_peak(args: &RunArgs) -> Option<cgroup::PeakWatch> {
+    (args.jobs == 1 && !args.interleave)
+        .then(cgroup::PeakWatch::start)
+        .flatten()
+}
+
+/// Summarize the measurement (non-warmup) eval times of one group, warn on
+/// stderr if it is noisy, and print the summary record.
+fn emit_summary(
+    args: &RunArgs,
+    engine: &str,
+    cell: &Cell,
+    warmup_iters: usize,
+    eval_ns: &[u64],
+    outcome: GroupOutcome,
+    sys: &SysInfo,
+) -> Result<Option<SummaryRecord>> {
+    if eval_ns.is_empty() {
+        return Ok(None);
+    }
+    let (stem, n) = (cell.stem, cell.n);
+
+    let cv_pct = stats::cv_pct(eval_ns);
+    let noisy = cv_pct > args.noise_threshold;
+    if noisy {
+        warn!(
+            "noisy measurements for {engine}/{stem}/{}: CV {cv_pct:.1}% exceeds {:.1}%",
+            cell.size, args.noise_threshold
+        );
+        warn!("results may be unreliable; see \"Reducing measurement noise\" in README.md");
+    }
+
+    let trend = stats::linear_trend(eval_ns);
+    let drifting = trend.as_ref().is_some_and(|t| t.t > DRIFT_T_THRESHOLD);
+    if drifting {
+        warn!(
+            "upward drift for {engine}/{stem}/{}: eval time grows {:.0} ns per iteration",
+            cell.size,
+            trend.as_ref().map_or(0.0, |t| t.slope)
+        );
+    }
+
+    if is_python(engine)
+        && args.python_threads > 1
+        && outcome
+            .interpreter
+            .as_ref()
+            .is_some_and(|i| !i.gil_disabled)
+    {
+        warn!(
+            "{engine}/{stem}/{}: not a free-threaded build, so the {} threads take turns on the GIL",
+            cell.size, args.python_threads
+        );
+    }
+
+    let freq_unstable = outcome.cpu_freq.is_some_and(|f| {
+        f.spread_pct() > args.freq_threshold || f.throttle_events.is_some_and(|e| e > 0)
+    });
+    if let Some(f) = outcome.cpu_freq.filter(|_| freq_unstable) {
+        warn!(
+            "CPU frequency varied for {engine}/{stem}/{}: {}-{} MHz{}",
+            cell.size,
+            f.min_mhz,
+            f.max_mhz,
+            match f.throttle_events {
+                Some(e) if e > 0 => format!(", {e} thermal throttling event(s)"),
+                _ => String::new(),
+            }
+        );
+    }
+
+    let threads = Some(if is_python(engine) {
+        args.python_threads
+    } else {
+        args.threads
+    })
+    .filter(|&k| k > 1);
+    let record = SummaryRecord {
+        schema_version: SCHEMA_VERSION,
+        run_id: sys.run_id.clone(),
+        kind: "summary",
+        engine: engine.into(),
+        engine_version: outcome.engine_version,
+        python_implementation: outcome
+            .interpreter
+            .as_ref()
+            .map(|i| i.implementation.clone()),
+        python_version: outcome.interpreter.as_ref().map(|i| i.version.clone()),
+        python_gil_disabled: outcome.interpreter.as_ref().map(|i| i.gil_disabled),
+        workload: stem.into(),
+        size: cell.size.to_string(),
+        n,
+        target_iter_ns: target_iter_ns(args),
+        seed: cell.seed,
+        params: param_map(&args.param),
+        attempt: cell.attempt,
+        position: cell.position,
+        iters: eval_ns.len(),
+        warmup_iters,
+        clock: clock_label(args),
+        median_eval_ns: stats::median(eval_ns) as u64,
+        median_items_per_sec: stats::per_sec(
+            work_items(n, threads.unwrap_or(1), outcome.run_parallel),
+            stats::median(eval_ns),
+        ),
+        mean_eval_ns: stats::mean(eval_ns) as u64,
+        stddev_eval_ns: stats::stddev(eval_ns) as u64,
+        min_eval_ns: eval_ns.iter().copied().min().unwrap_or(0),
+        max_eval_ns: eval_ns.iter().copied().max().unwrap_or(0),
+        median_python_gc_ns: outcome.python_gc_ns.map(|ns| stats::median(&ns) as u64),
+        result: outcome.result,
+        bytecode_instructions: outcome.bytecode_instructions,
+        script_cache: outcome
+            .script_cache_hit
+            .map(|hit| if hit { "hit" } else { "miss" }.to_string()),
+        cgroup_memory_peak_kb: outcome.cgroup_memory_peak_kb,
+        cpu_freq: outcome.cpu_freq,
+        freq_unstable,
+        run_parallel: outcome.run_parallel,
+        overhead_subtracted: args.subtract_overhead
+            && args.phase == Phase::Run
+            && !is_python(engine),
+        cv_pct,
+        noisy,
+        ci_pct: stats::ci_pct(eval_ns),
+        trend_ns_per_iter: trend.as_ref().map(|t| t.slope),
+        drifting,
+        rejected: over_budget(args, cv_pct) && cell.attempt - cell.attempt_base < args.remeasure,
+        converged: args.target_ci.map(|_| ci_converged(args, eval_ns)),
+        dialect: Some(dialect_label(args)).filter(|_| !is_python(engine)),
+        starlark_libs: starlark_libs(args).filter(|_| !is_python(engine)),
+        eval_config: outcome.eval_config,
+        input: input_shape(args, cell.stem).map(|s| s.to_string()),
+        module_mode: Some(module_mode(args).to_string()).filter(|_| !is_python(engine)),
+        phase: Some(args.phase.to_string()).filter(|_| args.phase != Phase::Run),
+        max_callstack: Some(max_callstack(args)).filter(|_| !is_python(engine)),
+        thread_stack_mib: args.starlark_thread_stack.filter(|_| !is_python(engine)),
+        threads,
+        container_image: args.docker_image.clone().filter(|_| is_python(engine)),
+        python_args: python_args(args).filter(|_| is_python(engine)),
+        python_no_gc: args.python_no_gc && is_python(engine),
+        python_worker: args.python_worker && is_python(engine),
+        python_sandbox: args.python_sandbox && is_python(engine),
+        python_harness: pyperf_label(args).filter(|_| is_python(engine)),
+        cooldown_ms: args.cooldown,
+        iter_cooldown_ms: args.iter_cooldown,
+        cache_state: cache_state(args),
+        timeout_secs: timeout_secs(args).filter(|_| !is_python(engine)),
+        cpu_model: sys.cpu_model.clone(),
+        os: sys.os.clone(),
+        rustc: sys.rustc.clone(),
+        allocator: Some(allocator::NAME.to_string()).filter(|_| !is_python(engine)),
+        priority: sys.priority.clone(),
+        env: sys.env.clone(),
+    };
+    output::emit(&record)?;
+    Ok(Some(record))
+}
+
+/// Emit a ratio record for every non-baseline summary against the python
+/// summary of the same group. None is emitted for an engine measured with
+/// --timeout against one measured without it, as its hook slows Starlark.
+fn emit_ratios(summaries: &[SummaryRecord]) -> Result<()> {
+    let Some(base) = summaries.iter().find(|s| is_python(&s.engine)) else {
+        return Ok(());
+    };
+    for s in summaries.iter().filter(|s| s.engine != base.engine) {
+        if base.median_eval_ns == 0 {
+            continue;
+        }
+        if s.timeout_secs.is_some() != base.timeout_secs.is_some() {
+            debug!(
+                "no {}/{} ratio for {}/{}: only one of them ran with --timeout",
+                s.engine, base.engine, s.workload, s.size
+            );
+            continue;
+        }
+        let record = RatioRecord {
+            schema_version: SCHEMA_VERSION,
+            run_id: Some(s.run_id.clone()),
+            kind: "ratio",
+            engine: s.engine.clone(),
+            baseline_engine: base.engine.clone(),
+            workload: s.workload.clone(),
+            size: s.size.clone(),
+            n: s.n,
+            seed: Some(s.seed),
+            median_eval_ns: s.median_eval_ns,
+            baseline_median_eval_ns: base.median_eval_ns,
+            baseline_n: (base.n != s.n).then_some(base.n),
+            ratio: (s.median_eval_ns as f64 / s.n as f64)
+                / (base.median_eval_ns as f64 / base.n as f64),
+        };
+        output::emit(&record)?;
+    }
+    Ok(())
+}
+
+/// Whether the engines of one comparison computed the same result; logs
+/// and emits a mismatch record for each disagreeing engine otherwise. They
+/// are checked against `expected` where the workload has it (registered
+/// ones), and against the first engine otherwise.
+/// Summaries without a result (from runs resumed across versions) are
+/// ignored, as are those at another N than the first (--target-iter-time)
+/// unless checked against `expected`.
+fn check_results(summaries: &[SummaryRecord], expected: Option<&ExpectedResult>) -> Result<bool> {
+    let with_result: Vec<_> = summaries
+        .iter()
+        .filter_map(|s| Some((s, s.result?)))
+        .collect();
+    let Some(&(first, first_result)) = with_result.first() else {
+        return Ok(true);
+    };
+    let mut agree = true;
+    for &(s, result) in &with_result {
+        let (baseline_engine, baseline_result) = match expected {
+            Some(expected) => ("expected", expected(s.n, s.seed)),
+            None if s.n == first.n => (first.engine.as_str(), first_result),
+            None => continue,
+        };
+        if result == baseline_result {
+            continue;
+        }
+        error!(
+            "checksum mismatch for {}/{} n={} seed={}: {baseline_engine}={baseline_result} but {}={result}; \
+             one of the scripts computes something else, so their timings do not compare \
+             (bench selftest --size {} --seed {} checks every workload)",
+            s.workload, s.size, s.n, s.seed, s.engine, s.size, s.seed
+        );
+        output::emit(&MismatchRecord {
+            schema_version: SCHEMA_VERSION,
+            run_id: s.run_id.clone(),
+            kind: "mismatch",
+            engine: s.engine.clone(),
+            baseline_engine: baseline_engine.into(),
+            workload: s.workload.clone(),
+            size: s.size.clone(),
+            n: s.n,
+            seed: s.seed,
+            result,
+            baseline_result,
+        })?;
+        agree = false;
+    }
+    Ok(agree)
+}
+
+// ---------------------------------------------------------------------------
+// Measurement groups
+// ---------------------------------------------------------------------------
+
+/// Parsed and frozen Starlark scripts, shared across sizes, seeds and
+/// workers so each distinct script is parsed once per invocation. Entries
+/// are keyed by content, so workloads whose scripts are identical share one.
+#[derive(Default)]
+struct ScriptCache {
+    /// Content hash of each workload's script.
+    stems: HashMap<&'static str, u64>,
+    /// Per --eval-config, by content hash.
+    prepared: HashMap<u64, Vec<starlark_engine::PreparedScript>>,
+}
+
+impl ScriptCache {
+    fn get(&self, stem: &str, config: usize) -> &starlark_engine::PreparedScript {
+        &self.prepared[&self.stems[stem]][config]
+    }
+}
+
+/// How an engine's runs map onto a group's iterations.
+#[derive(Clone, Copy, PartialEq)]
+enum Pace {
+    /// One iteration per run, warmed up until [`Warmup`] is done: Starlark,
+    /// custom engines and a --python-worker.
+    Steps,
+    /// A batch of iterations per Python subprocess, after one fixed warmup
+    /// subprocess.
+    Batches,
+    /// A single run taking all of the group's measurements, its warmups
+    /// included (pyperf).
+    Once,
+}
+
+/// A round of iterations for [`Measure::run`].
+struct Round {
+    /// Iterations to run; always 1 with [`Pace::Steps`].
+    count: u32,
+    /// Whether they are warmup iterations.
+    warmup: bool,
+    /// Back-to-back calls per timed sample (--min-sample-ns), for engines
+    /// calibrated [`Measure::in_process`].
+    calls: u32,
+}
+
+/// One engine's side of a [`Group`]: it runs the iterations and reports what
+/// it measured of them. The group paces the rounds, numbers the iterations
+/// and fills in the fields every record shares.
+trait Measure {
+    fn pace(&self) -> Pace {
+        Pace::Steps
+    }
+
+    /// Whether `run` is called in the bench's own process, so the group
+    /// calibrates --min-sample-ns for it; Python's harness does its own.
+    fn in_process(&self) -> bool {
+        false
+    }
+
+    /// The eval time of a call outside any iteration, recording nothing.
+    fn probe(&mut self) -> Result<Duration>;
+
+    /// Run `round`, returning a record per iteration with the engine's own
+    /// fields set. A batch can return warmup iterations before the measured
+    /// ones (--auto-warmup, pyperf).
+    fn run(&mut self, round: &Round) -> Result<Vec<BenchRecord>>;
+
+    /// The engine's own summary fields, once the group stops measuring.
+    fn outcome(&mut self, measured: &[u64]) -> Result<GroupOutcome>;
+
+    /// Called once the summary is kept, after the records are written.
+    fn accepted(&self) {}
+
+    /// What the latest run to time out last wrote to stderr.
+    fn stderr_tail(&self) -> Option<String> {
+        None
+    }
+}
+
+/// One engine's measurement group in progress: warmed up once, then measured
+/// in rounds until it has enough samples. Rounds of several groups can be
+/// interleaved (--interleave).
+struct Group<'a> {
+    args: &'a RunArgs,
+    cell: &'a Cell<'a>,
+    sys: &'a SysInfo,
+    engine: Box<dyn Measure + 'a>,
+    engine_label: String,
+    group: String,
+    /// Set while the group warms up.
+    warming_up: bool,
+    warmup_iters: usize,
+    measured: Vec<u64>,
+    /// The collector pauses of `measured`, where the harness reports them.
+    measured_gc: Vec<u64>,
+    /// Result of the latest measurement iteration.
+    result: Option<i64>,
+    /// Calls per timed sample (--min-sample-ns), chosen when warming up.
+    calls: u32,
+    /// The memory high-water mark from the group's start, where it is the
+    /// group's own.
+    memory_peak: Option<cgroup::PeakWatch>,
+    /// --cpu-freq, from the first measurement on.
+    freq: Option<cpufreq::Sampler>,
+    /// Iteration records, written once the group stops measuring.
+    records: output::Held,
+}
+
+impl<'a> Group<'a> {
+    /// `prepare_times` are reported by the Starlark group only when its
+    /// script was parsed for this group rather than taken from `scripts`.
+    fn new(
+        engine: GroupEngine,
+        args: &'a RunArgs,
+        cell: &'a Cell<'a>,
+        scripts_dir: &Path,
+        sys: &'a SysInfo,
+        scripts: &'a ScriptCache,
+        prepare_times: Option<starlark_engine::PrepareTimes>,
+    ) -> Result<Self> {
+        let engine_label = group_label(args, engine);
+        let group = format!("{engine_label}/{}/{}", cell.stem, cell.size);
+        let config = engine.config;
+        let (measure, memory_peak): (Box<dyn Measure + 'a>, _) = match engine.engine {
+            EngineName::Starlark => (
+                Box::new(StarlarkMeasure::new(
+                    args,
+                    cell,
+                    sys,
+                    config,
+                    scripts.get(cell.stem, config),
+                    prepare_times,
+                    group.clone(),
+                )),
+                watch_memory_peak(args),
+            ),
+            // With --cgroup, each subprocess reports the peak of its own.
+            EngineName::Python => (
+                Box::new(PythonMeasure::new(
+                    args,
+                    cell,
+                    scripts_dir,
+                    config,
+                    group.clone(),
+                )?),
+                watch_memory_peak(args).filter(|_| args.cgroup.is_none()),
+            ),
+            EngineName::Custom => (
+                Box::new(CustomMeasure::new(args, cell, scripts_dir, config)?),
+                watch_memory_peak(args),
+            ),
+        };
+        Ok(Group {
+            args,
+            cell,
+            sys,
+            engine: measure,
+            engine_label,
+            group,
+            warming_up: false,
+            warmup_iters: 0,
+            measured: Vec::with_capacity(args.iters as usize),
+            measured_gc: Vec::new(),
+            result: None,
+            calls: 1,
+            memory_peak,
+            freq: None,
+            records: output::Held::default(),
+        })
+    }
+
+    /// Warm up: one iteration at a time until [`Warmup`] is done, or a
+    /// single fixed batch. A batch with --auto-warmup detects its own warmup
+    /// prefix instead, and pyperf warms up in each of its runs.
+    fn warm_up(&mut self) -> Result<()> {
+        let args = self.args;
+        self.warming_up = true;
+        match self.engine.pace() {
+            Pace::Steps => {
+                if let Some(min_ns) = args.min_sample_ns
+                    && self.engine.in_process()
+                {
+                    self.calls = calls_per_sample(min_ns, self.engine.probe()?);
+                    debug!("{}: {} call(s) per sample", self.group, self.calls);
+                }
+                let mut warmup = Warmup::new(args);
+                while warmup.active() {
+                    let eval_ns = self.round(1, true)?;
+                    warmup.record(eval_ns, &self.group);
+                }
+            }
+            Pace::Batches if !args.auto_warmup && args.warmup > 0 => {
+                self.round(args.warmup, true)?;
+            }
+            Pace::Batches | Pace::Once => {}
+        }
+        self.warming_up = false;
+        Ok(())
+    }
+
+    /// The eval time of an untimed call, recording nothing.
+    fn probe(&mut self) -> Result<Duration> {
+        self.engine.probe()
+    }
+
+    /// Measure the next round of up to `count` iterations.
+    fn measure(&mut self, count: u32) -> Result<()> {
+        if self.args.cpu_freq && self.freq.is_none() {
+            self.freq = Some(cpufreq::Sampler::start());
+        }
+        match self.engine.pace() {
+            Pace::Steps => {
+                for _ in 0..count {
+                    if enough_samples(self.args, &self.measured) {
+                        break;
+                    }
+                    self.round(1, false)?;
+                }
+            }
+            Pace::Batches | Pace::Once => {
+                self.round(count, false)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Round size when the group runs on its own: one iteration at a time,
+    /// or a batch per Python subprocess.
+    fn batch(&self) -> u32 {
+        match self.engine.pace() {
+            Pace::Steps => 1,
+            Pace::Batches | Pace::Once => next_batch(self.args, &self.measured),
+        }
+    }
+
+    fn done(&self) -> bool {
+        match self.engine.pace() {
+            // pyperf decides how many values it takes.
+            Pace::Once => !self.measured.is_empty(),
+            Pace::Steps | Pace::Batches => enough_samples(self.args, &self.measured),
+        }
+    }
+
+    /// Run one round on the engine and hold its records, returning the eval
+    /// time of the last.
+    fn round(&mut self, count: u32, warmup: bool) -> Result<u64> {
+        let done = if warmup {
+            self.warmup_iters
+        } else {
+            self.measured.len()
+        };
+        progress::status(match (self.engine.pace(), warmup) {
+            (Pace::Steps, true) => format!("{} warmup {}", self.group, done + 1),
+            (Pace::Steps, false) => format!("{} iter {}", self.group, done + 1),
+            (Pace::Batches, _) => format!(
+                "{} {} {}..{}",
+                self.group,
+                if warmup { "warmup" } else { "iters" },
+                done + 1,
+                done + count as usize
+            ),
+            (Pace::Once, _) => format!("{} pyperf", self.group),
+        });
+        let round = Round {
+            count,
+            warmup,
+            calls: self.calls,
+        };
+        let mut last = 0;
+        for record in self.engine.run(&round)? {
+            last = record.eval_ns;
+            self.hold(record);
+        }
+        Ok(last)
+    }
+
+    /// Number the iteration's record, fill in the fields every engine's
+    /// records share, and hold it.
+    fn hold(&mut self, record: BenchRecord) {
+        let (args, cell, sys) = (self.args, self.cell, self.sys);
+        let iter = if record.warmup {
+            self.warmup_iters += 1;
+            self.warmup_iters - 1
+        } else {
+            self.measured.push(record.eval_ns);
+            self.measured_gc.extend(record.python_gc_ns);
+            self.result = Some(record.result);
+            self.measured.len() - 1
+        };
+        let items = work_items(cell.n, record.threads.unwrap_or(1), record.run_parallel);
+        self.records.push(BenchRecord {
+            schema_version: SCHEMA_VERSION,
+            run_id: sys.run_id.clone(),
+            engine: self.engine_label.clone(),
+            workload: cell.stem.into(),
+            size: cell.size.to_string(),
+            n: cell.n,
+            target_iter_ns: target_iter_ns(args),
+            seed: cell.seed,
+            params: param_map(&args.param),
+            iter: iter as u32,
+            attempt: cell.attempt,
+            position: cell.position,
+            clock: clock_label(args),
+            items_per_sec: stats::per_sec(items, record.eval_ns as f64),
+            input: input_shape(args, cell.stem).map(|s| s.to_string()),
+            cooldown_ms: args.cooldown,
+            iter_cooldown_ms: args.iter_cooldown,
+            cache_state: cache_state(args),
+            cpu_model: sys.cpu_model.clone(),
+            os: sys.os.clone(),
+            rustc: sys.rustc.clone(),
+            priority: sys.priority.clone(),
+            env: sys.env.clone(),
+            ..record
+        });
+    }
+
+    fn finish(mut self) -> Result<Option<SummaryRecord>> {
+        let outcome = self.engine.outcome(&self.measured)?;
+        self.records.write()?;
+        let outcome = GroupOutcome {
+            result: self.result,
+            cgroup_memory_peak_kb: self
+                .memory_peak
+                .as_mut()
+                .and_then(cgroup::PeakWatch::peak_kb)
+                .or(outcome.cgroup_memory_peak_kb),
+            cpu_freq: self.freq.take().and_then(cpufreq::Sampler::stop),
+            python_gc_ns: Some(std::mem::take(&mut self.measured_gc)).filter(|ns| !ns.is_empty()),
+            ..outcome
+        };
+        let summary = emit_summary(
+            self.args,
+            &self.engine_label,
+            self.cell,
+            self.warmup_iters,
+            &self.measured,
+            outcome,
+            self.sys,
+        )?;
+        // Attempts rejected by --max-stddev-pct are measured again.
+        if summary.as_ref().is_some_and(|s| !s.rejected) {
+            self.engine.accepted();
+        }
+        Ok(summary)
+    }
+
+    /// Write the iteration records held back while measuring.
+    fn write_records(&mut self) -> Result<()> {
+        self.records.write()
+    }
+
+    /// Record for a group abandoned after an iteration hit --timeout.
+    fn timeout_record(&self) -> TimeoutRecord {
+        let (args, cell) = (self.args, self.cell);
+        TimeoutRecord {
+            schema_version: SCHEMA_VERSION,
+            run_id: self.sys.run_id.clone(),
+            kind: "timeout",
+            engine: self.engine_label.clone(),
+            workload: cell.stem.into(),
+            size: cell.size.to_string(),
+            n: cell.n,
+            seed: cell.seed,
+            params: param_map(&args.param),
+            attempt: cell.attempt,
+            position: cell.position,
+            warmup: self.warming_up,
+            completed_iters: self.measured.len(),
+            timeout_secs: timeout_secs(args).unwrap_or(0.0),
+            stderr_tail: self.engine.stderr_tail(),
+        }
+    }
+}
+
+/// Warm up and measure `groups` to completion: one after the other, or
+/// alternating single iterations between them (ABAB…) with --interleave.
+/// Returns which groups hit --timeout; those are abandoned where they stopped.
+fn measure_groups(args: &RunArgs, groups: &mut [Group]) -> Result<Vec<bool>> {
+    let mut timed_out = vec![false; groups.len()];
+    if !args.interleave {
+        for (g, t) in groups.iter_mut().zip(&mut timed_out) {
+            *t = hit_timeout(g.warm_up())?;
+            while !*t && !g.done() {
+                let batch = g.batch();
+                before_round(args)?;
+                *t = hit_timeout(g.measure(batch))?;
+            }
+        }
+        return Ok(timed_out);
+    }
+    for (g, t) in groups.iter_mut().zip(&mut timed_out) {
+        *t = hit_timeout(g.warm_up())?;
+    }
+    loop {
+        let mut active = false;
+        for (g, t) in groups.iter_mut().zip(&mut timed_out) {
+            if !*t && !g.done() {
+                before_round(args)?;
+                *t = hit_timeout(g.measure(1))?;
+                active = true;
+            }
+        }
+        if !active {
+            return Ok(timed_out);
+        }
+    }
+}
+
+/// Prepare the machine for the next measurement round: --iter-cooldown,
+/// then --drop-fs-caches.
+fn before_round(args: &RunArgs) -> Result<()> {
+    cool_down(args.iter_cooldown);
+    if args.drop_fs_caches {
+        cache::drop_fs()?;
+    }
+    Ok(())
+}
+
+/// Sleep for `ms` milliseconds (--cooldown, --iter-cooldown), if set.
+fn cool_down(ms: Option<u64>) {
+    if let Some(ms) = ms {
+        progress::status(format!("cooling down {ms} ms"));
+        std::thread::sleep(Duration::from_millis(ms));
+    }
+}
+
+/// Whether `r` failed because an iteration exceeded --timeout.
+fn hit_timeout(r: Result<()>) -> Result<bool> {
+    match r {
+        Ok(()) => Ok(false),
+        Err(e) if e.is::<TimedOut>() => Ok(true),
+        Err(e) => Err(e),
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Starlark benchmark loop
+// ---------------------------------------------------------------------------
+
+fn emit_frozen_heap(
+    args: &RunArgs,
+    sys: &SysInfo,
+    stem: &str,
+    config: usize,
+    prepared: &starlark_engine::PreparedScript,
+) -> Result<()> {
+    let heap = starlark_engine::frozen_heap(prepared);
+    debug!(
+        "{stem}: frozen heap holds {} values in {} bytes",
+        heap.values, heap.bytes
+    );
+    output::emit(&FrozenHeapRecord {
+        schema_version: SCHEMA_VERSION,
+        run_id: sys.run_id.clone(),
+        kind: "frozen_heap",
+        engine: "starlark".into(),
+        engine_version: starlark_engine::VERSION.to_string(),
+        workload: stem.into(),
+        bytes: heap.bytes,
+        values: heap.values,
+        types: heap
+            .types
+            .into_iter()
+            .map(|(name, (count, bytes))| (name, HeapTypeCount { count, bytes }))
+            .collect(),
+        loaded_bytes: heap.loaded_bytes,
+        dialect: Some(dialect_label(args)),
+        eval_config: args.eval_config.get(config).map(EvalConfig::to_string),
+    })
+}
+
+/// Parse and freeze the script for `stem` in each of `languages` unless the
+/// same script is already cached. Returns its setup times in each if it was
+/// parsed now.
+fn prepare_starlark(
+    scripts: &mut ScriptCache,
+    stem: &'static str,
+    path: &Path,
+    languages: &[starlark_engine::Language],
+    timeout: Option<Duration>,
+    profiled: bool,
+    typecheck: bool,
+) -> Result<Option<Vec<starlark_engine::PrepareTimes>>> {
+    if scripts.stems.contains_key(stem) {
+        return Ok(None);
+    }
+    debug!("starlark script: {}", path.display());
+    let script_body =
+        std::fs::read_to_string(path).with_context(|| format!("cannot read {}", path.display()))?;
+    let hash = {
+        let mut hasher = std::hash::DefaultHasher::new();
+        script_body.hash(&mut hasher);
+        hasher.finish()
+    };
+    scripts.stems.insert(stem, hash);
+    if scripts.prepared.contains_key(&hash) {
+        debug!("{stem}: same script as an earlier workload, not parsed again");
+        return Ok(None);
+    }
+    // Parse once per language, freeze the module, extract the `run` function.
+    let load_dir = path.parent().unwrap_or(Path::new("."));
+    let mut prepared = Vec::with_capacity(languages.len());
+    for language in languages {
+        let script = starlark_engine::prepare(
+            &script_body,
+            load_dir,
+            language,
+            timeout,
+            profiled,
+            typecheck,
+        )
+        .with_context(|| format!("in {}", path.display()))?;
+        let times = script.times;
+        debug!(
+            "{stem}: parsed in {} ns, evaluated in {} ns, froze in {} ns",
+            times.parse_ns, times.module_eval_ns, times.freeze_ns
+        );
+        if let (Some(ns), Some(errors)) = (times.typecheck_ns, times.typecheck_errors) {
+            debug!("{stem}: typechecked in {ns} ns, {errors} type errors");
+        }
+        if let (Some(ns), Some(modules)) = (times.load_ns, times.loaded_modules) {
+            debug!("{stem}: loaded {modules} module(s) in {ns} ns");
+        }
+        prepared.push(script);
+    }
+    let times = prepared.iter().map(|script| script.times).collect();
+    scripts.prepared.insert(hash, prepared);
+    Ok(Some(times))
+}
+
+/// Host-side measurements around an in-process call (Starlark and custom
+/// engines): --cache-state cold, --perf-counters, --energy, and the bench
+/// process's memory and its cgroup's CPU time.
+struct InProcess<'a> {
+    args: &'a RunArgs,
+    /// --cache-state cold: written through before every iteration.
+    evictor: Option<cache::Evictor>,
+}
+
+impl<'a> InProcess<'a> {
+    fn new(args: &'a RunArgs) -> Self {
+        InProcess {
+            args,
+            evictor: (args.cache_state == CacheState::Cold)
+                .then(|| cache::Evictor::new(cache::evict_bytes())),
+        }
+    }
+
+    /// Call `f` for one iteration, returning what it returned and a record
+    /// of what the host measured around it.
+    fn time<T>(&mut self, f: impl FnOnce() -> Result<T>) -> Result<(T, BenchRecord)> {
+        let args = self.args;
+        if let Some(evictor) = &mut self.evictor {
+            evictor.evict();
+        }
+        // Parallel workers share the host cgroup, so its CPU time is only
+        // attributable to this iteration when running serially.
+        let usage_before = (args.jobs == 1).then(cgroup::host_usage).flatten();
+        // On Linux, inherited by the --threads evaluators, which have exited
+        // by the time they are read; on macOS, the whole process.
+        let counters = if args.perf_counters {
+            let counters = perf::Counters::current()?;
+            let before = counters.read()?;
+            Some((counters, before))
+        } else {
+            None
+        };
+        let energy_before = args.energy.then(energy::read).transpose()?;
+        // The peak is the whole process's, so like the cgroup's CPU time it
+        // only belongs to this iteration when running serially.
+        let peak_reset = args.jobs == 1 && reset_peak_rss();
+        let wall_start = std::time::Instant::now();
+        let r = f()?;
+        let counters = match counters {
+            Some((counters, before)) => Some(counters.read()?.since(&before)),
+            None => None,
+        };
+        let energy_j = match energy_before {
+            Some(before) => Some(energy::read()?.joules_since(&before)),
+            None => None,
+        };
+        let rss = process_rss_kb();
+        // The kernel's peak comes from approximate per-CPU counters and can
+        // trail the exact VmRSS slightly, which it cannot really be below.
+        let max_rss_kb = peak_reset.then(peak_rss_kb).flatten().map(|kb| kb.max(rss));
+        let allocator_stats = allocator::stats();
+        let wall_ns = wall_start.elapsed().as_nanos() as u64;
+        let cgroup = usage_before
+            .zip(cgroup::host_usage())
+            .map(|(before, after)| after.since(&before));
+        let host = BenchRecord {
+            wall_ns,
+            rss_kb: rss,
+            rss_note: Some("host-process VmRSS; includes Rust runtime".into()),
+            max_rss_kb,
+            cgroup,
+            counters,
+            energy_j,
+            allocator_stats,
+            allocator: Some(allocator::NAME.to_string()),
+            ..BenchRecord::default()
+        };
+        Ok((r, host))
+    }
+}
+
+struct StarlarkMeasure<'a> {
+    args: &'a RunArgs,
+    cell: &'a Cell<'a>,
+    prepared: &'a starlark_engine::PreparedScript,
+    prepare_times: Option<starlark_engine::PrepareTimes>,
+    /// Whether an iteration has run, which reports `prepare_times`.
+    started: bool,
+    /// The calibrated overhead of the group's language.
+    overhead: Option<calibrate::Overhead>,
+    /// The --eval-config the group runs in, as given.
+    eval_config: Option<String>,
+    /// In reuse-module mode, one Module shared by all iterations.
+    shared_module: Option<starlark::environment::Module>,
+    /// The --input value, converted to Starlark values on every call.
+    input: Option<serde_json::Value>,
+    group: String,
+    host: InProcess<'a>,
+}
+
+impl<'a> StarlarkMeasure<'a> {
+    fn new(
+        args: &'a RunArgs,
+        cell: &'a Cell<'a>,
+        sys: &'a SysInfo,
+        config: usize,
+        prepared: &'a starlark_engine::PreparedScript,
+        prepare_times: Option<starlark_engine::PrepareTimes>,
+        group: String,
+    ) -> Self {
+        let shared_module = if module_mode(args) == ModuleMode::Reuse {
+            let m = starlark::environment::Module::new();
+            m.import_public_symbols(starlark_engine::frozen(prepared));
+            Some(m)
+        } else {
+            None
+        };
+        StarlarkMeasure {
+            args,
+            cell,
+            prepared,
+            prepare_times,
+            started: false,
+            overhead: sys.overheads.get(config).copied(),
+            eval_config: args.eval_config.get(config).map(EvalConfig::to_string),
+            shared_module,
+            input: input_shape(args, cell.stem).map(|shape| input::build(shape, cell.n, cell.seed)),
+            group,
+            host: InProcess::new(args),
+        }
+    }
+
+    /// Whether --threads calls `run_parallel`.
+    fn run_parallel(&self) -> bool {
+        self.args.threads > 1 && starlark_engine::defines_run_parallel(self.prepared)
+    }
+
+    /// Run one iteration of `calls` calls.
+    fn iteration(&mut self, warmup: bool, calls: u32) -> Result<BenchRecord> {
+        let (args, cell, prepared, n) = (self.args, self.cell, self.prepared, self.cell.n);
+        let call_options = starlark_engine::CallOptions {
+            profile: heap_profile(args),
+            gc: args.starlark_gc,
+            calls,
+            parallel: None,
+        };
+        let mut thread_eval_ns = None;
+        let mut cold_times = None;
+        let (shared_module, input) = (self.shared_module.as_ref(), self.input.as_ref());
+        let (r, host) = self.host.time(|| {
+            if args.phase != Phase::Run {
+                starlark_engine::reparse(prepared, args.phase == Phase::Compile)
+            } else if let Some(module) = shared_module {
+                starlark_engine::call_run_reuse(
+                    prepared,
+                    module,
+                    n,
+                    cell.seed,
+                    &args.param,
+                    input,
+                    call_options,
+                )
+            } else if args.starlark_cold {
+                let fresh = starlark_engine::recompile(prepared)?;
+                cold_times = Some(fresh.times);
+                starlark_engine::call_run(&fresh, n, cell.seed, &args.param, input, call_options)
+            } else if args.threads > 1 {
+                let concurrent = starlark_engine::call_run_concurrent(
+                    prepared,
+                    n,
+                    cell.seed,
+                    &args.param,
+                    input,
+                    args.threads,
+                    args.starlark_thread_stack
+                        .map(|mib| mib as usize * 1024 * 1024),
+                )?;
+                let durs = concurrent.thread_eval_durs.iter();
+                thread_eval_ns = Some(durs.map(|d| d.as_nanos() as u64).collect());
+                Ok(concurrent.run)
+            } else {
+                starlark_engine::call_run(prepared, n, cell.seed, &args.param, input, call_options)
+            }
+        })?;
+
+        let times = cold_times.or(self.prepare_times.filter(|_| !self.started));
+        self.started = true;
+        let calls = u64::from(calls);
+        let clocks = ClockTimes {
+            wall_ns: r.eval_dur.as_nanos() as u64,
+            cpu_ns: r.process_cpu_dur.map(|d| d.as_nanos() as u64),
+            thread_ns: r.cpu_dur.map(|d| d.as_nanos() as u64),
+        }
+        .per_call(calls);
+        let mut call_ns = match args.clock {
+            Clock::Wall => Some(clocks.wall_ns),
+            Clock::Cpu => clocks.cpu_ns,
+            Clock::Thread => clocks.thread_ns,
+        }
+        .with_context(|| format!("cannot read the {} clock", args.clock))?;
+        let overhead_subtracted_ns = self
+            .overhead
+            .filter(|_| args.subtract_overhead && args.phase == Phase::Run)
+            .map(|o| o.call_ns.min(call_ns));
+        call_ns -= overhead_subtracted_ns.unwrap_or(0);
+        // Cold iterations time the whole script, not just the call.
+        let eval_ns = match cold_times {
+            Some(t) => {
+                t.parse_ns + t.load_ns.unwrap_or(0) + t.module_eval_ns + t.freeze_ns + call_ns
+            }
+            None => call_ns,
+        };
+        Ok(BenchRecord {
+            engine_version: Some(starlark_engine::VERSION.to_string()),
+            warmup,
+            parse_ns: times.map(|t| t.parse_ns),
+            module_eval_ns: times.map(|t| t.module_eval_ns),
+            freeze_ns: times.map(|t| t.freeze_ns),
+            typecheck_ns: times.and_then(|t| t.typecheck_ns),
+            typecheck_errors: times.and_then(|t| t.typecheck_errors),
+            load_ns: times.and_then(|t| t.load_ns),
+            loaded_modules: times.and_then(|t| t.loaded_modules),
+            marshal_ns: r.marshal_dur.map(|d| d.as_nanos() as u64),
+            eval_ns,
+            clocks: args.record_clocks.then_some(clocks),
+            call_ns: cold_times.map(|_| call_ns),
+            total_ns: eval_ns,
+            result: r.result,
+            cpu_ns: r.cpu_dur.map(|d| d.as_nanos() as u64 / calls),
+            voluntary_ctx_switches: r.rusage.map(|u| u.voluntary_switches),
+            involuntary_ctx_switches: r.rusage.map(|u| u.involuntary_switches),
+            major_page_faults: r.rusage.map(|u| u.major_faults),
+            minor_page_faults: r.rusage.map(|u| u.minor_faults),
+            heap_allocated_bytes: r.heap.as_ref().map(|h| h.allocated_bytes),
+            mem: args.mem.map(|m| m.to_string()),
+            peak_alloc_bytes: args.mem.and(r.heap.as_ref()).map(|h| h.allocated_bytes),
+            heap_allocations: r.heap.as_ref().map(|h| h.allocations),
+            rust_allocations: r.rust_alloc.map(|c| c.allocations / calls),
+            rust_allocated_bytes: r.rust_alloc.map(|c| c.bytes / calls),
+            overhead: self.overhead,
+            overhead_subtracted_ns,
+            heap_retained_bytes: r.heap.as_ref().and_then(|h| h.retained_bytes),
+            heap_bytes_before: Some(r.heap_bytes_before),
+            heap_bytes_after: Some(r.heap_bytes_after),
+            gc_ns: r.gc.as_ref().map(|g| g.gc_dur.as_nanos() as u64),
+            gc_count: r.gc.as_ref().map(|g| u64::from(g.collected)),
+            heap_bytes_after_gc: r.gc.as_ref().map(|g| g.bytes_after),
+            dialect: Some(dialect_label(args)),
+            starlark_libs: starlark_libs(args),
+            eval_config: self.eval_config.clone(),
+            module_mode: Some(module_mode(args).to_string()),
+            phase: Some(args.phase.to_string()).filter(|_| args.phase != Phase::Run),
+            source_bytes: (args.phase != Phase::Run)
+                .then(|| starlark_engine::source_bytes(prepared)),
+            max_callstack: Some(max_callstack(args)),
+            thread_stack_mib: args.starlark_thread_stack,
+            threads: Some(args.threads).filter(|&k| k > 1),
+            thread_eval_ns,
+            run_parallel: self.run_parallel(),
+            calls_per_sample: args.min_sample_ns.map(|_| calls as u32),
+            timeout_secs: timeout_secs(args),
+            ..host
+        })
+    }
+
+    /// Run the --profile profilers, each on one more call of `run`. A failed
+    /// profile only warns; the group's records are written by now.
+    fn profile(&self) {
+        let (args, cell) = (self.args, self.cell);
+        if args.profile.is_empty() {
+            return;
+        }
+        if let Err(e) = std::fs::create_dir_all(&args.profile_dir) {
+            warn!("cannot create {}: {e}", args.profile_dir.display());
+            return;
+        }
+        for &kind in &args.profile {
+            let path = args.profile_dir.join(format!(
+                "{}-{}-n{}-seed{}.{}",
+                cell.stem,
+                cell.size,
+                cell.n,
+                cell.seed,
+                kind.suffix()
+            ));
+            progress::status(format!("{} profiling", self.group));
+            match starlark_engine::profile_run(
+                self.prepared,
+                cell.n,
+                cell.seed,
+                &args.param,
+                self.input.as_ref(),
+                kind,
+                &path,
+            ) {
+                Ok(()) => info!("{}: wrote {}", self.group, path.display()),
+                Err(e) => warn!("{}: profiling failed: {e:#}", self.group),
+            }
+        }
+    }
+
+    /// --bytecode-stats: count the instructions of one more call of `run`.
+    fn bytecode_instructions(&self, measured: &[u64]) -> Option<u64> {
+        if !self.args.bytecode_stats || measured.is_empty() {
+            return None;
+        }
+        progress::status(format!("{} counting instructions", self.group));
+        let cell = self.cell;
+        starlark_engine::count_instructions(
+            self.prepared,
+            cell.n,
+            cell.seed,
+            &self.args.param,
+            self.input.as_ref(),
+        )
+        .inspect_err(|e| warn!("{}: counting instructions failed: {e:#}", self.group))
+        .ok()
+    }
+}
+
+impl Measure for StarlarkMeasure<'_> {
+    fn in_process(&self) -> bool {
+        true
+    }
+
+    fn probe(&mut self) -> Result<Duration> {
+        let call_options = starlark_engine::CallOptions {
+            profile: false,
+            gc: false,
+            calls: 1,
+            parallel: None,
+        };
+        let r = starlark_engine::call_run(
+            self.prepared,
+            self.cell.n,
+            self.cell.seed,
+            &self.args.param,
+            self.input.as_ref(),
+            call_options,
+        )?;
+        Ok(r.eval_dur)
+    }
+
+    fn run(&mut self, round: &Round) -> Result<Vec<BenchRecord>> {
+        Ok(vec![self.iteration(round.warmup, round.calls)?])
+    }
+
+    fn outcome(&mut self, measured: &[u64]) -> Result<GroupOutcome> {
+        Ok(GroupOutcome {
+            bytecode_instructions: self.bytecode_instructions(measured),
+            script_cache_hit: Some(self.prepare_times.is_none()),
+            engine_version: Some(starlark_engine::VERSION.to_string()),
+            eval_config: self.eval_config.clone(),
+            run_parallel: self.run_parallel(),
+            ..GroupOutcome::default()
+        })
+    }
+
+    fn accepted(&self) {
+        self.profile();
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Python benchmark loop
+// ---------------------------------------------------------------------------
+
+struct PythonMeasure<'a> {
+    args: &'a RunArgs,
+    cell: &'a Cell<'a>,
+    /// The interpreter binary, one of [`pythons`].
+    python: String,
+    path: PathBuf,
+    /// For a registered workload, the directory of `_harness.py`.
+    pythonpath: Option<PathBuf>,
+    /// The --input value as JSON, piped to every subprocess.
+    input: Option<String>,
+    group: String,
+    /// Failed runs retried before the latest subprocess succeeded.
+    retry: u32,
+    /// `sys.version` and the interpreter as the latest subprocess reported
+    /// them.
+    version: Option<String>,
+    interpreter: Option<python_engine::Interpreter>,
+    /// The harness's threads split `n` with `run_parallel`.
+    run_parallel: bool,
+    /// With --python-worker, the group's interpreter, started by the first
+    /// iteration and again after a failure.
+    worker: Option<python_engine::Worker>,
+    /// What the latest subprocess to time out last wrote to stderr.
+    stderr_tail: Option<String>,
+    /// With --cgroup, the highest memory.peak of its subprocesses' cgroups.
+    leaf_memory_peak_kb: Option<u64>,
+}
+
+impl<'a> PythonMeasure<'a> {
+    fn new(
+        args: &'a RunArgs,
+        cell: &'a Cell<'a>,
+        scripts_dir: &Path,
+        config: usize,
+        group: String,
+    ) -> Result<Self> {
+        let path = script_path(args, scripts_dir, cell.stem, "python");
+        if !path.exists() {
+            bail!("Python script not found: {}", path.display());
+        }
+        debug!("python script: {}", path.display());
+        Ok(PythonMeasure {
+            args,
+            cell,
+            python: pythons(args).swap_remove(config).1,
+            path,
+            pythonpath: custom_workload(args, cell.stem).map(|_| scripts_dir.join("python")),
+            input: input_shape(args, cell.stem)
+                .map(|shape| input::build(shape, cell.n, cell.seed).to_string()),
+            group,
+            retry: 0,
+            version: None,
+            interpreter: None,
+            worker: None,
+            stderr_tail: None,
+            leaf_memory_peak_kb: None,
+            run_parallel: false,
+        })
+    }
+
+    /// One batch in a fresh subprocess. With --auto-warmup every subprocess
+    /// is a fresh interpreter, so each measured batch runs up to
+    /// --max-warmup extra iterations and its warmup prefix is detected after
+    /// the fact.
+    fn batch(&mut self, round: &Round) -> Result<Vec<BenchRecord>> {
+        let args = self.args;
+        let extra = if args.auto_warmup && !round.warmup {
+            args.max_warmup
+        } else {
+            0
+        };
+        let mr = self.spawn(round.count + extra)?;
+        if mr.iters.is_empty() {
+            bail!("Python script {} reported no timings", self.path.display());
+        }
+        let skip = if extra > 0 {
+            let ns: Vec<u64> = mr
+                .iters
+                .iter()
+                .map(|ir| ir.eval_dur.as_nanos() as u64)
+                .collect();
+            let prefix = &ns[..(extra as usize).min(ns.len())];
+            stats::steady_state(prefix, WARMUP_WINDOW, args.noise_threshold).unwrap_or_else(|| {
+                warn_unsettled(&self.group, prefix.len());
+                prefix.len()
+            })
+        } else {
+            0
+        };
+        let (warm, rest) = mr.iters.split_at(skip);
+        let rest = &rest[..rest.len().min(round.count as usize)];
+        let mut records = self.records(&mr, warm, true);
+        records.extend(self.records(&mr, rest, round.warmup));
+        Ok(records)
+    }
+
+    /// Let pyperf take all of the group's measurements in one run, whose
+    /// warmups are recorded as warmup iterations.
+    fn pyperf(&mut self) -> Result<Vec<BenchRecord>> {
+        let args = self.args;
+        let python_args = python_args(args).unwrap_or_default();
+        let (pr, warmups) = self.retrying(|g| {
+            python_engine::run_pyperf(
+                g.launch(&python_args),
+                &g.path,
+                g.cell.n,
+                g.cell.seed,
+                &args.param,
+                g.input.as_deref(),
+            )
+        })?;
+        let (warm, rest) = pr.iters.split_at(warmups);
+        if rest.is_empty() {
+            bail!("pyperf reported no values for {}", self.path.display());
+        }
+        let mut records = self.records(&pr, warm, true);
+        records.extend(self.records(&pr, rest, false));
+        Ok(records)
+    }
+
+    /// Run one subprocess, retrying failures up to --retries times. Timeouts
+    /// are not retried.
+    fn spawn(&mut self, iter_count: u32) -> Result<python_engine::RunResult> {
+        let args = self.args;
+        let python_args = python_args(args).unwrap_or_default();
+        self.retrying(|g| {
+            python_engine::run(
+                g.launch(&python_args),
+                &g.path,
+                g.cell.n,
+                g.cell.seed,
+                &args.param,
+                g.input.as_deref(),
+                iter_count,
+                args.timeout,
+            )
+        })
+    }
+
+    /// Keep the stderr tail of a timed-out subprocess for the timeout record.
+    fn keep_stderr_tail<T>(&mut self, r: &Result<T>) {
+        if let Err(e) = r
+            && let Some(tail) = e.downcast_ref::<python_engine::StderrTail>()
+        {
+            self.stderr_tail = Some(tail.0.clone());
+        }
+    }
+
+    /// Call `f` until it succeeds, up to --retries more times, recording
+    /// the retries. Timeouts are not retried.
+    fn retrying<T>(&mut self, mut f: impl FnMut(&Self) -> Result<T>) -> Result<T> {
+        let args = self.args;
+        for retry in 0.. {
+            let r = f(self);
+            self.keep_stderr_tail(&r);
+            match r {
+                Err(e) if retry < args.retries && !e.is::<TimedOut>() => {
+                    warn!(
+                        "{}: subprocess failed, retrying ({} of {}): {e:#}",
+                        self.group,
+                        retry + 1,
+                        args.retries
+                    )
+                }
+                r => {
+                    self.retry = retry;
+                    return r;
+                }
+            }
+        }
+        unreachable!()
+    }
+
+    fn launch<'s>(&'s self, python_args: &'s [String]) -> python_engine::Launch<'s> {
+        python_engine::Launch {
+            binary: &self.python,
+            args: python_args,
+            no_gc: self.args.python_no_gc,
+            tracemalloc: self.args.mem == Some(MemMode::Tracemalloc),
+            cold: self.args.python_cold,
+            threads: self.args.python_threads,
+            sandbox: self.args.python_sandbox,
+            perf: self.args.perf_counters,
+            energy: self.args.energy,
+            min_sample_ns: self.args.min_sample_ns,
+            evict_bytes: (self.args.cache_state == CacheState::Cold).then(cache::evict_bytes),
+            clock: self.args.clock,
+            record_clocks: self.args.record_clocks,
+            pythonpath: self.pythonpath.as_deref(),
+            image: self.args.docker_image.as_deref(),
+        }
+    }
+
+    /// Run the next iteration on the --python-worker, starting it first if
+    /// needed. A failed worker is replaced by a fresh one up to --retries
+    /// times, which repeats the import and top level; timeouts are not
+    /// retried.
+    fn step(&mut self) -> Result<python_engine::RunResult> {
+        let args = self.args;
+        for retry in 0.. {
+            let worker = match self.worker.take() {
+                Some(worker) => Ok(worker),
+                None => {
+                    let python_args = python_args(args).unwrap_or_default();
+                    python_engine::Worker::start(
+                        self.launch(&python_args),
+                        &self.path,
+                        self.cell.n,
+                        self.cell.seed,
+                        &args.param,
+                        self.input.as_deref(),
+                        args.timeout,
+                    )
+                }
+            };
+            let r = worker.and_then(|w| self.worker.insert(w).iterate(args.timeout));
+            self.keep_stderr_tail(&r);
+            match r {
+                Err(e) if retry < args.retries && !e.is::<TimedOut>() => {
+                    self.worker = None;
+                    warn!(
+                        "{}: worker failed, restarting it ({} of {}): {e:#}",
+                        self.group,
+                        retry + 1,
+                        args.retries
+                    )
+                }
+                r => {
+                    self.retry = retry;
+                    return r;
+                }
+            }
+        }
+        unreachable!()
+    }
+
+    /// Records for (a slice of) the iterations of a Python run.
+    fn records(
+        &mut self,
+        pr: &python_engine::RunResult,
+        iters: &[python_engine::IterResult],
+        warmup: bool,
+    ) -> Vec<BenchRecord> {
+        let leaf_peak_kb = pr.cgroup.and_then(|u| u.memory_peak_kb);
+        self.leaf_memory_peak_kb = self.leaf_memory_peak_kb.max(leaf_peak_kb);
+        self.run_parallel = pr.parallel;
+        self.version = pr.version.clone();
+        self.interpreter = pr.interpreter.clone();
+        let args = self.args;
+        let per_iter_wall_ns =
+            pr.total_dur.as_nanos() as u64 / u64::from(pr.iters.len().max(1) as u32);
+        let process = pr.process.map(|u| u.per_iter(pr.iters.len()));
+        iters
+            .iter()
+            .map(|ir| BenchRecord {
+                engine_version: pr.version.clone(),
+                python_implementation: pr.interpreter.as_ref().map(|i| i.implementation.clone()),
+                python_version: pr.interpreter.as_ref().map(|i| i.version.clone()),
+                python_gil_disabled: pr.interpreter.as_ref().map(|i| i.gil_disabled),
+                warmup,
+                retry: self.retry,
+                parse_ns: ir.cold.map(|t| t.compile_dur.as_nanos() as u64),
+                module_eval_ns: ir.cold.map(|t| t.module_eval_dur.as_nanos() as u64),
+                startup_ns: pr.startup_dur.map(|d| d.as_nanos() as u64),
+                import_ns: pr.import_dur.map(|d| d.as_nanos() as u64),
+                marshal_ns: ir.marshal_dur.map(|d| d.as_nanos() as u64),
+                eval_ns: ir.eval_dur.as_nanos() as u64,
+                clocks: ir.clocks,
+                call_ns: ir.cold.map(|t| t.call_dur.as_nanos() as u64),
+                wall_ns: per_iter_wall_ns,
+                total_ns: per_iter_wall_ns,
+                result: ir.result,
+                rss_kb: ir.rss_kb.unwrap_or(pr.rss_kb),
+                rss_note: Some(
+                    if ir.rss_kb.is_some() {
+                        "VmRSS after the iteration; subprocess only"
+                    } else {
+                        pr.rss_note
+                    }
+                    .into(),
+                ),
+                cpu_ns: ir.cpu_dur.map(|d| d.as_nanos() as u64),
+                cpu_user_ns: process.map(|u| u.user.as_nanos() as u64),
+                cpu_sys_ns: process.map(|u| u.sys.as_nanos() as u64),
+                max_rss_kb: process.map(|u| u.max_rss_kb),
+                voluntary_ctx_switches: process.map(|u| u.rusage.voluntary_switches),
+                involuntary_ctx_switches: process.map(|u| u.rusage.involuntary_switches),
+                major_page_faults: process.map(|u| u.rusage.major_faults),
+                minor_page_faults: process.map(|u| u.rusage.minor_faults),
+                cgroup: pr.cgroup.map(|u| u.per_iter(pr.iters.len())),
+                counters: pr.counters.map(|c| c.per_iter(pr.iters.len())),
+                energy_j: pr.energy_j.map(|j| j / pr.iters.len().max(1) as f64),
+                mem: args.mem.map(|m| m.to_string()),
+                peak_alloc_bytes: ir.peak_alloc_bytes,
+                python_gc_ns: ir.gc_dur.map(|d| d.as_nanos() as u64),
+                python_gc_collections: ir.gc_collections,
+                threads: Some(args.python_threads).filter(|&k| k > 1),
+                thread_eval_ns: ir
+                    .thread_eval_durs
+                    .as_ref()
+                    .map(|durs| durs.iter().map(|d| d.as_nanos() as u64).collect()),
+                run_parallel: pr.parallel,
+                calls_per_sample: pr.calls_per_sample,
+                container_image: args.docker_image.clone(),
+                python_args: python_args(args),
+                python_no_gc: args.python_no_gc,
+                python_worker: args.python_worker,
+                python_sandbox: args.python_sandbox,
+                python_harness: pyperf_label(args),
+                ..BenchRecord::default()
+            })
+            .collect()
+    }
+}
+
+impl Measure for PythonMeasure<'_> {
+    fn pace(&self) -> Pace {
+        if self.args.python_harness == PythonHarness::Pyperf {
+            Pace::Once
+        } else if self.args.python_worker {
+            Pace::Steps
+        } else {
+            Pace::Batches
+        }
+    }
+
+    /// The faster of two iterations in a subprocess of their own, so the
+    /// first call's cold start does not count.
+    fn probe(&mut self) -> Result<Duration> {
+        let args = self.args;
+        progress::status(format!("{} probe n={}", self.group, self.cell.n));
+        let python_args = python_args(args).unwrap_or_default();
+        let pr = self.retrying(|g| {
+            python_engine::run(
+                g.launch(&python_args),
+                &g.path,
+                g.cell.n,
+                g.cell.seed,
+                &args.param,
+                g.input.as_deref(),
+                2,
+                args.timeout,
+            )
+        })?;
+        pr.iters
+            .iter()
+            .map(|ir| ir.eval_dur)
+            .min()
+            .with_context(|| format!("Python script {} reported no timings", self.path.display()))
+    }
+
+    fn run(&mut self, round: &Round) -> Result<Vec<BenchRecord>> {
+        match self.pace() {
+            Pace::Steps => {
+                let wr = self.step()?;
+                Ok(self.records(&wr, &wr.iters, round.warmup))
+            }
+            Pace::Batches => self.batch(round),
+            Pace::Once => self.pyperf(),
+        }
+    }
+
+    fn outcome(&mut self, _measured: &[u64]) -> Result<GroupOutcome> {
+        if let Some(worker) = self.worker.take() {
+            worker.close()?;
+        }
+        Ok(GroupOutcome {
+            engine_version: self.version.clone(),
+            interpreter: self.interpreter.clone(),
+            cgroup_memory_peak_kb: self.leaf_memory_peak_kb,
+            run_parallel: self.run_parallel,
+            ..GroupOutcome::default()
+        })
+    }
+
+    fn stderr_tail(&self) -> Option<String> {
+        self.stderr_tail.clone()
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Custom engine loop
+// ---------------------------------------------------------------------------
+
+/// A group measuring an [`Engine`] registered with [`Runner::custom_engine`],
+/// one sample per call of [`Engine::run_iter`], or per --min-sample-ns batch
+/// of them. It holds the engine, so groups of the same engine wait for each
+/// other (--jobs).
+struct CustomMeasure<'a> {
+    args: &'a RunArgs,
+    cell: &'a Cell<'a>,
+    engine: std::sync::MutexGuard<'a, Box<dyn Engine>>,
+    params: BTreeMap<String, serde_json::Value>,
+    input: Option<serde_json::Value>,
+    host: InProcess<'a>,
+}
+
+impl<'a> CustomMeasure<'a> {
+    fn new(
+        args: &'a RunArgs,
+        cell: &'a Cell<'a>,
+        scripts_dir: &Path,
+        config: usize,
+    ) -> Result<Self> {
+        let registered = &args.custom_engines[config];
+        let mut engine = registered.engine.lock().expect("engine lock poisoned");
+        let workload = Workload {
+            name: cell.stem,
+            scripts_dir,
+            registered: custom_workload(args, cell.stem),
+        };
+        engine
+            .prepare(&workload)
+            .with_context(|| format!("{} cannot prepare {}", registered.name, cell.stem))?;
+        Ok(CustomMeasure {
+            args,
+            cell,
+            engine,
+            params: param_map(&args.param),
+            input: input_shape(args, cell.stem).map(|shape| input::build(shape, cell.n, cell.seed)),
+            host: InProcess::new(args),
+        })
+    }
+
+    fn call(&mut self) -> Result<Iteration> {
+        let call = Call {
+            n: self.cell.n,
+            seed: self.cell.seed,
+            params: &self.params,
+            input: self.input.as_ref(),
+        };
+        self.engine.run_iter(&call)
+    }
+}
+
+impl Measure for CustomMeasure<'_> {
+    fn in_process(&self) -> bool {
+        true
+    }
+
+    fn probe(&mut self) -> Result<Duration> {
+        Ok(self.call()?.eval)
+    }
+
+    fn run(&mut self, round: &Round) -> Result<Vec<BenchRecord>> {
+        let call = Call {
+            n: self.cell.n,
+            seed: self.cell.seed,
+            params: &self.params,
+            input: self.input.as_ref(),
+        };
+        let engine = &mut self.engine;
+        let ((eval, result), host) = self.host.time(|| {
+            let mut eval = Duration::ZERO;
+            let mut result = 0;
+            for _ in 0..round.calls {
+                let it = engine.run_iter(&call)?;
+                eval += it.eval;
+                result = it.result;
+            }
+            Ok((eval / round.calls, result))
+        })?;
+        let eval_ns = eval.as_nanos() as u64;
+        Ok(vec![BenchRecord {
+            engine_version: self.engine.metadata().version,
+            warmup: round.warmup,
+            eval_ns,
+            total_ns: eval_ns,
+            result,
+            calls_per_sample: self.args.min_sample_ns.map(|_| round.calls),
+            ..host
+        }])
+    }
+
+    fn outcome(&mut self, _measured: &[u64]) -> Result<GroupOutcome> {
+        Ok(GroupOutcome {
+            engine_version: self.engine.metadata().version,
+            ..GroupOutcome::default()
+        })
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::runner::ScratchDir;
+    use super::*;
+
+    #[test]
+    fn sweep_geometric() {
+        assert_eq!(
+            parse_sweep("1000:8000:x2").unwrap().0,
+            [1000, 2000, 4000, 8000]
+        );
+        assert_eq!(parse_sweep("1000:3000:x1.5").unwrap().0, [1000, 1500, 2250]);
+        assert_eq!(parse_sweep("1:3:x1.1").unwrap().0, [1, 2, 3]);
+    }
+
+    #[test]
+    fn sweep_linear() {
+        assert_eq!(parse_sweep("10:30:10").unwrap().0, [10, 20, 30]);
+        assert_eq!(parse_sweep(" 5:5:3 ").unwrap().0, [5]);
+    }
+
+    #[test]
+    fn sweep_rejects() {
+        for s in [
+            "0:10:2", "10:5:1", "1:10:x1", "1:10:0", "1:10", "a:10:2", "1:10:xy",
+        ] {
+            assert!(parse_sweep(s).is_err(), "{s:?}");
+        }
+    }
+
+    #[test]
+    fn cpu_list() {
+        assert_eq!(parse_cpu_list("0-3,6").unwrap().0, [0, 1, 2, 3, 6]);
+        assert_eq!(parse_cpu_list("3, 1,1-2").unwrap().0, [1, 2, 3]);
+        for s in ["3-1", "a", "", "1,,2", "-1"] {
+            assert!(parse_cpu_list(s).is_err(), "{s:?}");
+        }
+    }
+
+    #[test]
+    fn param_values() {
+        let value = |s: &str| parse_param(s).unwrap().value;
+        assert_eq!(value("depth=3"), serde_json::json!(3));
+        assert_eq!(value("ratio=0.5"), serde_json::json!(0.5));
+        assert_eq!(value("flag=true"), serde_json::json!(true));
+        assert_eq!(value("name=abc"), serde_json::json!("abc"));
+        assert_eq!(value("name=\"3\""), serde_json::json!("3"));
+        assert_eq!(value("list=[1]"), serde_json::json!("[1]"));
+        assert_eq!(value("empty="), serde_json::json!(""));
+        assert_eq!(parse_param("_x1=1").unwrap().name, "_x1");
+    }
+
+    #[test]
+    fn param_rejects() {
+        for s in ["noequals", "3a=1", "a-b=1", "=1", "n=1", "seed=2"] {
+            assert!(parse_param(s).is_err(), "{s:?}");
+        }
+    }
+
+    /// The error from applying a --config file holding `toml` to `bench run`
+    /// with `flags`.
+    fn config_error(toml: &str, flags: &[&str]) -> String {
+        let dir = ScratchDir::new().unwrap();
+        let path = dir.0.join("suite.toml");
+        std::fs::write(&path, toml).unwrap();
+        let argv = ["bench", "run", "--config", path.to_str().unwrap()];
+        let matches = Cli::command()
+            .try_get_matches_from(argv.iter().chain(flags))
+            .unwrap();
+        let matches = matches.subcommand_matches("run").unwrap();
+        let mut args = RunArgs::from_arg_matches(matches).unwrap();
+        complete_run_args(&mut args, matches)
+            .expect_err("a conflict")
+            .to_string()
+    }
+
+    #[test]
+    fn config_n_conflicts_with_n_sweep() {
+        let e = config_error("n = [1000]", &["--n-sweep", "10:30:10"]);
+        assert_eq!(e, "--n-sweep cannot be used with --n (from --config)");
+    }
+
+    #[test]
+    fn config_n_conflicts_with_target_iter_time() {
+        let e = config_error("n = [1000]", &["--target-iter-time", "1"]);
+        assert_eq!(
+            e,
+            "--target-iter-time cannot be used with --n (from --config)"
+        );
+    }
+
+    #[test]
+    fn config_python_conflicts_with_pythons() {
+        let e = config_error("python = \"python3\"\npythons = [\"python3.12\"]", &[]);
+        assert_eq!(
+            e,
+            "--pythons (from --config) cannot be used with --python (from --config)"
+        );
+    }
+}
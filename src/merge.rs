@@ -0,0 +1,78 @@
+//! Merge subcommand.
+
+use std::collections::HashSet;
+use std::io::Write;
+
+use anyhow::{Context, Result, bail};
+use serde_json::Value;
+use tracing::{info, warn};
+
+use super::{MergeArgs, SCHEMA_VERSION};
+
+pub fn run(args: &MergeArgs) -> Result<()> {
+    if args.inputs.contains(&args.output) {
+        bail!("output {} is also an input", args.output.display());
+    }
+
+    // Run ids from previous inputs; a run present in several inputs
+    // (e.g. a copied file) is only kept from the first one.
+    let mut seen_runs: HashSet<String> = HashSet::new();
+    let mut versions: HashSet<u64> = HashSet::new();
+    let mut lines = Vec::new();
+    let mut skipped = 0usize;
+
+    for path in &args.inputs {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("cannot read {}", path.display()))?;
+        let mut file_runs = HashSet::new();
+        for (lineno, line) in text.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let rec: Value = serde_json::from_str(line)
+                .with_context(|| format!("{}:{}: not a JSON record", path.display(), lineno + 1))?;
+            let version = rec
+                .get("schema_version")
+                .and_then(Value::as_u64)
+                .unwrap_or(0);
+            if version > u64::from(SCHEMA_VERSION) {
+                bail!(
+                    "{}:{}: schema_version {version} is newer than supported ({SCHEMA_VERSION})",
+                    path.display(),
+                    lineno + 1
+                );
+            }
+            versions.insert(version);
+
+            // Records without a run id predate run ids; keep them all.
+            if let Some(run_id) = rec.get("run_id").and_then(Value::as_str) {
+                if seen_runs.contains(run_id) {
+                    skipped += 1;
+                    continue;
+                }
+                file_runs.insert(run_id.to_string());
+            }
+            lines.push(line.to_string());
+        }
+        seen_runs.extend(file_runs);
+    }
+
+    if versions.len() > 1 {
+        let mut v: Vec<_> = versions.into_iter().collect();
+        v.sort_unstable();
+        warn!("inputs mix schema versions {v:?}; fields may be missing in older records");
+    }
+
+    let mut out = std::fs::File::create(&args.output)
+        .with_context(|| format!("cannot create {}", args.output.display()))?;
+    for line in &lines {
+        writeln!(out, "{line}")?;
+    }
+    info!(
+        "merged {} records from {} files into {} ({skipped} duplicate-run records skipped)",
+        lines.len(),
+        args.inputs.len(),
+        args.output.display()
+    );
+    Ok(())
+}
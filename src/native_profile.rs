@@ -0,0 +1,77 @@
+//! Native sampling profiler (--profile native, --features pprof).
+
+use std::path::Path;
+#[cfg(all(feature = "pprof", any(target_os = "linux", target_os = "macos")))]
+use std::time::{Duration, Instant};
+
+#[cfg(all(feature = "pprof", any(target_os = "linux", target_os = "macos")))]
+use anyhow::Context;
+use anyhow::Result;
+
+/// Samples per second, prime so that they do not fall into step with
+/// periodic work.
+#[cfg(all(feature = "pprof", any(target_os = "linux", target_os = "macos")))]
+const FREQUENCY: i32 = 997;
+
+/// How long the profiled calls are repeated for at least, so that a
+/// workload of a millisecond still collects a thousand samples.
+#[cfg(all(feature = "pprof", any(target_os = "linux", target_os = "macos")))]
+const MIN_DURATION: Duration = Duration::from_secs(1);
+
+/// Fail where this build cannot profile.
+pub fn check() -> Result<()> {
+    if cfg!(all(
+        feature = "pprof",
+        any(target_os = "linux", target_os = "macos")
+    )) {
+        Ok(())
+    } else {
+        anyhow::bail!("--profile native needs a Linux or macOS build with `--features pprof`")
+    }
+}
+
+/// Sample the process's stacks while `call` runs, once and then again
+/// until [`MIN_DURATION`] has passed, and write them to `path` as a
+/// pprof protobuf and beside it, with the extension `svg`, as a
+/// flamegraph.
+#[cfg(all(feature = "pprof", any(target_os = "linux", target_os = "macos")))]
+pub fn record(path: &Path, mut call: impl FnMut() -> Result<()>) -> Result<()> {
+    use pprof::protos::Message;
+
+    let guard = pprof::ProfilerGuardBuilder::default()
+        .frequency(FREQUENCY)
+        // Unwinding through these while they hold locks can deadlock.
+        .blocklist(&["libc", "libgcc", "pthread", "vdso"])
+        .build()
+        .context("cannot start the sampling profiler")?;
+    let start = Instant::now();
+    call()?;
+    while start.elapsed() < MIN_DURATION {
+        call()?;
+    }
+    let report = guard
+        .report()
+        .build()
+        .context("cannot collect the samples")?;
+    drop(guard);
+
+    let mut encoded = Vec::new();
+    report
+        .pprof()
+        .context("cannot build the pprof profile")?
+        .encode(&mut encoded)
+        .context("cannot encode the pprof profile")?;
+    std::fs::write(path, encoded)
+        .with_context(|| format!("cannot write profile {}", path.display()))?;
+    let svg = path.with_extension("svg");
+    let file = std::fs::File::create(&svg)
+        .with_context(|| format!("cannot write profile {}", svg.display()))?;
+    report
+        .flamegraph(file)
+        .with_context(|| format!("cannot write profile {}", svg.display()))
+}
+
+#[cfg(not(all(feature = "pprof", any(target_os = "linux", target_os = "macos"))))]
+pub fn record(_path: &Path, _call: impl FnMut() -> Result<()>) -> Result<()> {
+    check()
+}
@@ -0,0 +1,174 @@
+//! Record output.
+
+use std::io::{LineWriter, Write};
+use std::path::Path;
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Instant, UNIX_EPOCH};
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+
+use super::BenchRecord;
+
+/// Where records go; stdout until `init` or `init_records`.
+enum Sink {
+    Writer(Box<dyn Write + Send>),
+    /// A function taking each iteration record (for [`crate::Runner`]);
+    /// the other records are dropped.
+    Records(Box<RecordSink>),
+}
+
+type RecordSink = dyn FnMut(BenchRecord) -> Result<()> + Send;
+
+static SINK: Mutex<Option<Sink>> = Mutex::new(None);
+
+/// When the records began: `init` or `init_records`, or else the first
+/// record.
+static START: Mutex<Option<Instant>> = Mutex::new(None);
+
+/// When a record was made.
+#[derive(Clone, Copy, Serialize)]
+pub struct Stamp {
+    /// Wall-clock time, for matching against system logs.
+    pub ts_unix_ms: u64,
+    /// Monotonic time since the records began, immune to clock changes.
+    pub elapsed_ms_since_start: u64,
+}
+
+impl Stamp {
+    pub fn now() -> Stamp {
+        let start = *start().get_or_insert_with(Instant::now);
+        Stamp {
+            ts_unix_ms: UNIX_EPOCH.elapsed().map_or(0, |d| d.as_millis() as u64),
+            elapsed_ms_since_start: start.elapsed().as_millis() as u64,
+        }
+    }
+}
+
+/// A record with its stamp appended.
+#[derive(Serialize)]
+struct Stamped<'a, T> {
+    #[serde(flatten)]
+    record: &'a T,
+    #[serde(flatten)]
+    stamp: Stamp,
+}
+
+/// Records held back while a group measures: serializing and writing
+/// them between iterations would land in the next iteration's timing
+/// (cache misses, allocation, a write syscall), which is noticeable next
+/// to Starlark calls of a few microseconds. Each keeps the time it was
+/// made.
+#[derive(Default)]
+pub struct Held {
+    records: Vec<(Stamp, BenchRecord)>,
+}
+
+impl Held {
+    pub fn push(&mut self, record: BenchRecord) {
+        self.records.push((Stamp::now(), record));
+    }
+
+    /// Write the held records in order and forget them.
+    pub fn write(&mut self) -> Result<()> {
+        for (stamp, record) in self.records.drain(..) {
+            emit_record(record, stamp)?;
+        }
+        Ok(())
+    }
+}
+
+/// Direct records to `path`, or stdout when None. With `append`, records
+/// go after the existing contents of `path` instead of replacing them.
+/// Defaults to stdout if never called.
+pub fn init(path: Option<&Path>, append: bool) -> Result<()> {
+    let writer: Box<dyn Write + Send> = match path {
+        Some(p) if append => {
+            // Drop the partial last line an interrupted run may have left.
+            let text = std::fs::read(p).with_context(|| format!("cannot read {}", p.display()))?;
+            let complete = text.iter().rposition(|&b| b == b'\n').map_or(0, |i| i + 1);
+            let file = std::fs::OpenOptions::new()
+                .append(true)
+                .open(p)
+                .with_context(|| format!("cannot open {}", p.display()))?;
+            file.set_len(complete as u64)?;
+            Box::new(LineWriter::new(file))
+        }
+        Some(p) => Box::new(LineWriter::new(
+            std::fs::File::create(p).with_context(|| format!("cannot create {}", p.display()))?,
+        )),
+        None => Box::new(std::io::stdout()),
+    };
+    *sink() = Some(Sink::Writer(writer));
+    start().get_or_insert_with(Instant::now);
+    Ok(())
+}
+
+/// Hand every iteration record, stamped, to `records` instead, and drop
+/// the others; an error from it fails the write. The records begin now.
+pub fn init_records(records: impl FnMut(BenchRecord) -> Result<()> + Send + 'static) {
+    *sink() = Some(Sink::Records(Box::new(records)));
+    *start() = Some(Instant::now());
+}
+
+/// Drop the sink, so that records go to stdout again and begin anew.
+pub fn reset() {
+    *sink() = None;
+    *start() = None;
+}
+
+fn sink() -> MutexGuard<'static, Option<Sink>> {
+    SINK.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+fn start() -> MutexGuard<'static, Option<Instant>> {
+    START.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+fn write_line(line: &str) -> Result<()> {
+    match sink().get_or_insert_with(|| Sink::Writer(Box::new(std::io::stdout()))) {
+        Sink::Writer(out) => writeln!(out, "{line}")?,
+        Sink::Records(_) => {}
+    }
+    Ok(())
+}
+
+/// Write an iteration record made at `stamp`; a record sink takes it as
+/// it is.
+fn emit_record(mut record: BenchRecord, stamp: Stamp) -> Result<()> {
+    match sink().get_or_insert_with(|| Sink::Writer(Box::new(std::io::stdout()))) {
+        Sink::Writer(out) => {
+            let line = serde_json::to_string(&Stamped {
+                record: &record,
+                stamp,
+            })?;
+            writeln!(out, "{line}")?
+        }
+        Sink::Records(records) => {
+            record.stamp = Some(stamp);
+            records(record)?
+        }
+    }
+    Ok(())
+}
+
+/// Write one record as a JSON line, stamped with the current time.
+pub fn emit<T: Serialize>(record: &T) -> Result<()> {
+    emit_at(record, Stamp::now())
+}
+
+fn emit_at<T: Serialize>(record: &T, stamp: Stamp) -> Result<()> {
+    write_line(&serde_json::to_string(&Stamped { record, stamp })?)
+}
+
+/// Write a line that is already a serialized record, keeping its stamps.
+pub fn emit_line(line: &str) -> Result<()> {
+    write_line(line)
+}
+
+pub fn flush() -> Result<()> {
+    if let Some(Sink::Writer(out)) = &mut *sink() {
+        out.flush()?;
+    }
+    Ok(())
+}
@@ -0,0 +1,293 @@
+//! Hardware performance counters (--perf-counters).
+
+#[cfg(not(all(feature = "perf", any(target_os = "linux", target_os = "macos"))))]
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+/// Hardware events counted over a measured span. On Linux they are
+/// user space only, and where the kernel had to multiplex the PMU the
+/// values are scaled up from the time each counter actually ran; macOS
+/// counts user and kernel time, and has no miss counts.
+#[derive(Clone, Copy, Default, Serialize, Deserialize)]
+pub struct Counts {
+    pub instructions: u64,
+    pub cycles: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub branch_misses: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cache_misses: Option<u64>,
+}
+
+impl Counts {
+    /// Events counted since `before`.
+    pub fn since(&self, before: &Counts) -> Counts {
+        let since = |now: Option<u64>, then: Option<u64>| Some(now?.saturating_sub(then?));
+        Counts {
+            instructions: self.instructions.saturating_sub(before.instructions),
+            cycles: self.cycles.saturating_sub(before.cycles),
+            branch_misses: since(self.branch_misses, before.branch_misses),
+            cache_misses: since(self.cache_misses, before.cache_misses),
+        }
+    }
+
+    /// Events divided evenly over `iters` iterations.
+    pub fn per_iter(&self, iters: usize) -> Counts {
+        let iters = iters.max(1) as u64;
+        Counts {
+            instructions: self.instructions / iters,
+            cycles: self.cycles / iters,
+            branch_misses: self.branch_misses.map(|n| n / iters),
+            cache_misses: self.cache_misses.map(|n| n / iters),
+        }
+    }
+}
+
+#[cfg(all(feature = "perf", any(target_os = "linux", target_os = "macos")))]
+pub use sys::Counters;
+#[cfg(all(feature = "perf", target_os = "macos"))]
+pub use sys::exited;
+
+/// The counts of the exited, not yet reaped child `pid`; only macOS
+/// reads them this way.
+#[cfg(all(target_os = "macos", not(feature = "perf")))]
+pub fn exited(_pid: u32) -> Option<Counts> {
+    None
+}
+
+#[cfg(all(feature = "perf", target_os = "linux"))]
+mod sys {
+    use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+
+    use anyhow::{Context, Result};
+
+    use super::Counts;
+
+    /// The first published layout of `struct perf_event_attr`
+    /// (PERF_ATTR_SIZE_VER0); the kernel zero-fills the rest.
+    #[repr(C)]
+    #[derive(Default)]
+    struct Attr {
+        kind: u32,
+        size: u32,
+        config: u64,
+        sample_period: u64,
+        sample_type: u64,
+        read_format: u64,
+        flags: u64,
+        wakeup_events: u32,
+        bp_type: u32,
+        config1: u64,
+    }
+
+    const PERF_TYPE_HARDWARE: u32 = 0;
+    const PERF_COUNT_HW_CPU_CYCLES: u64 = 0;
+    const PERF_COUNT_HW_INSTRUCTIONS: u64 = 1;
+    const PERF_COUNT_HW_CACHE_MISSES: u64 = 3;
+    const PERF_COUNT_HW_BRANCH_MISSES: u64 = 5;
+    const PERF_FORMAT_TOTAL_TIME_ENABLED: u64 = 1;
+    const PERF_FORMAT_TOTAL_TIME_RUNNING: u64 = 2;
+    const FLAG_DISABLED: u64 = 1 << 0;
+    const FLAG_INHERIT: u64 = 1 << 1;
+    const FLAG_EXCLUDE_KERNEL: u64 = 1 << 5;
+    const FLAG_EXCLUDE_HV: u64 = 1 << 6;
+    const FLAG_ENABLE_ON_EXEC: u64 = 1 << 12;
+    const PERF_FLAG_FD_CLOEXEC: libc::c_ulong = 1 << 3;
+
+    /// In the field order of [`Counts`].
+    const EVENTS: [u64; 4] = [
+        PERF_COUNT_HW_INSTRUCTIONS,
+        PERF_COUNT_HW_CPU_CYCLES,
+        PERF_COUNT_HW_BRANCH_MISSES,
+        PERF_COUNT_HW_CACHE_MISSES,
+    ];
+
+    /// One counter per event, each counting a task and the threads and
+    /// processes it starts after they were opened.
+    pub struct Counters {
+        fds: Vec<OwnedFd>,
+    }
+
+    impl Counters {
+        /// Count the calling thread from now on.
+        pub fn current() -> Result<Counters> {
+            Counters::open(0, 0)
+        }
+
+        /// Count the running process `pid` from now on.
+        pub fn process(pid: u32) -> Result<Counters> {
+            Counters::open(pid as libc::pid_t, 0)
+        }
+
+        /// Count the next process the calling thread starts, from the
+        /// moment it execs; the thread itself is not counted. The counts
+        /// are complete once that process has been waited for.
+        pub fn next_exec() -> Result<Option<Counters>> {
+            Counters::open(0, FLAG_DISABLED | FLAG_ENABLE_ON_EXEC).map(Some)
+        }
+
+        fn open(pid: libc::pid_t, flags: u64) -> Result<Counters> {
+            let fds = EVENTS
+                .iter()
+                .map(|&config| {
+                    let attr = Attr {
+                        kind: PERF_TYPE_HARDWARE,
+                        size: std::mem::size_of::<Attr>() as u32,
+                        config,
+                        read_format: PERF_FORMAT_TOTAL_TIME_ENABLED
+                            | PERF_FORMAT_TOTAL_TIME_RUNNING,
+                        flags: flags | FLAG_INHERIT | FLAG_EXCLUDE_KERNEL | FLAG_EXCLUDE_HV,
+                        ..Attr::default()
+                    };
+                    // SAFETY: attr outlives the call, and a returned fd
+                    // is owned by nothing else.
+                    let fd = unsafe {
+                        libc::syscall(
+                            libc::SYS_perf_event_open,
+                            &attr as *const Attr,
+                            pid,
+                            -1,
+                            -1,
+                            PERF_FLAG_FD_CLOEXEC,
+                        )
+                    };
+                    if fd < 0 {
+                        return Err(std::io::Error::last_os_error()).context(
+                            "cannot open hardware performance counters \
+                             (see /proc/sys/kernel/perf_event_paranoid)",
+                        );
+                    }
+                    Ok(unsafe { OwnedFd::from_raw_fd(fd as i32) })
+                })
+                .collect::<Result<_>>()?;
+            Ok(Counters { fds })
+        }
+
+        /// The counts so far.
+        pub fn read(&self) -> Result<Counts> {
+            let mut values = [0; 4];
+            for (fd, value) in self.fds.iter().zip(&mut values) {
+                // The count, and the time enabled and running.
+                let mut buf = [0u64; 3];
+                // SAFETY: buf is valid for its size in bytes.
+                let n = unsafe {
+                    libc::read(
+                        fd.as_raw_fd(),
+                        buf.as_mut_ptr().cast(),
+                        std::mem::size_of_val(&buf),
+                    )
+                };
+                if n != std::mem::size_of_val(&buf) as isize {
+                    return Err(std::io::Error::last_os_error())
+                        .context("cannot read a performance counter");
+                }
+                let [count, enabled, running] = buf;
+                *value = if running == 0 || running >= enabled {
+                    count
+                } else {
+                    (count as f64 * enabled as f64 / running as f64) as u64
+                };
+            }
+            let [instructions, cycles, branch_misses, cache_misses] = values;
+            Ok(Counts {
+                instructions,
+                cycles,
+                branch_misses: Some(branch_misses),
+                cache_misses: Some(cache_misses),
+            })
+        }
+    }
+}
+
+#[cfg(all(feature = "perf", target_os = "macos"))]
+mod sys {
+    use anyhow::{Context, Result};
+
+    use super::Counts;
+
+    /// The instructions and cycles XNU keeps for every process, read
+    /// with `proc_pid_rusage`, which needs no privileges. They cover
+    /// all of its threads.
+    pub struct Counters {
+        pid: libc::pid_t,
+    }
+
+    impl Counters {
+        /// Count this process, all of its threads.
+        pub fn current() -> Result<Counters> {
+            // SAFETY: getpid cannot fail.
+            Counters::process(unsafe { libc::getpid() } as u32)
+        }
+
+        /// Count the running process `pid`.
+        pub fn process(pid: u32) -> Result<Counters> {
+            let counters = Counters {
+                pid: pid as libc::pid_t,
+            };
+            counters.read()?;
+            Ok(counters)
+        }
+
+        /// None: the child's counts are read once it has exited, see
+        /// [`exited`].
+        pub fn next_exec() -> Result<Option<Counters>> {
+            Ok(None)
+        }
+
+        /// The counts so far.
+        pub fn read(&self) -> Result<Counts> {
+            usage(self.pid)
+                .map_err(anyhow::Error::from)
+                .context("cannot read the process's instruction and cycle counts")
+        }
+    }
+
+    /// The counts of the exited child `pid`, which has not been reaped.
+    pub fn exited(pid: u32) -> Option<Counts> {
+        usage(pid as libc::pid_t).ok()
+    }
+
+    fn usage(pid: libc::pid_t) -> std::io::Result<Counts> {
+        // SAFETY: rusage_info_v4 is plain data, filled in by the call.
+        let mut info: libc::rusage_info_v4 = unsafe { std::mem::zeroed() };
+        let buffer = (&mut info as *mut libc::rusage_info_v4).cast();
+        // SAFETY: buffer points to a rusage_info_v4, as the flavor says.
+        if unsafe { libc::proc_pid_rusage(pid, libc::RUSAGE_INFO_V4, buffer) } != 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        Ok(Counts {
+            instructions: info.ri_instructions,
+            cycles: info.ri_cycles,
+            branch_misses: None,
+            cache_misses: None,
+        })
+    }
+}
+
+/// Stands in for the counters in builds without them; --perf-counters
+/// is rejected before any is opened.
+#[cfg(not(all(feature = "perf", any(target_os = "linux", target_os = "macos"))))]
+pub struct Counters;
+
+#[cfg(not(all(feature = "perf", any(target_os = "linux", target_os = "macos"))))]
+impl Counters {
+    pub fn current() -> Result<Counters> {
+        unavailable()
+    }
+
+    pub fn process(_pid: u32) -> Result<Counters> {
+        unavailable()
+    }
+
+    pub fn next_exec() -> Result<Option<Counters>> {
+        unavailable()
+    }
+
+    pub fn read(&self) -> Result<Counts> {
+        unavailable()
+    }
+}
+
+#[cfg(not(all(feature = "perf", any(target_os = "linux", target_os = "macos"))))]
+fn unavailable<T>() -> Result<T> {
+    anyhow::bail!("--perf-counters needs a Linux or macOS build with `--features perf`")
+}
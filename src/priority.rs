@@ -0,0 +1,86 @@
+//! Scheduling priority.
+
+use anyhow::{Result, bail};
+
+/// What the process needs to be allowed a higher priority.
+#[cfg(target_os = "linux")]
+const PRIVILEGE_HINT: &str =
+    "run as root, grant CAP_SYS_NICE, or raise the limit in /etc/security/limits.conf";
+
+/// Set the nice value of the calling thread, inherited by the threads and
+/// subprocesses it starts afterwards. Returns false where unsupported.
+#[cfg(target_os = "linux")]
+pub fn set_nice(nice: i32) -> Result<bool> {
+    // SAFETY: plain syscall; `who` 0 is the calling thread.
+    if unsafe { libc::setpriority(libc::PRIO_PROCESS, 0, nice) } != 0 {
+        bail!(
+            "cannot set nice {nice}: {} ({PRIVILEGE_HINT}, e.g. RLIMIT_NICE)",
+            std::io::Error::last_os_error()
+        );
+    }
+    Ok(true)
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn set_nice(_nice: i32) -> Result<bool> {
+    Ok(false)
+}
+
+/// Switch the calling thread to SCHED_FIFO at `priority`; inherited like
+/// the nice value. Returns false where unsupported.
+#[cfg(target_os = "linux")]
+pub fn set_realtime(priority: u32) -> Result<bool> {
+    let param = libc::sched_param {
+        sched_priority: priority as i32,
+    };
+    // SAFETY: `param` is a valid sched_param; pid 0 is the calling thread.
+    if unsafe { libc::sched_setscheduler(0, libc::SCHED_FIFO, &param) } != 0 {
+        bail!(
+            "cannot switch to SCHED_FIFO priority {priority}: {} ({PRIVILEGE_HINT}, e.g. RLIMIT_RTPRIO)",
+            std::io::Error::last_os_error()
+        );
+    }
+    Ok(true)
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn set_realtime(_priority: u32) -> Result<bool> {
+    Ok(false)
+}
+
+/// Policy and priority of the calling thread, e.g. "normal nice=0".
+#[cfg(target_os = "linux")]
+pub fn current() -> Option<String> {
+    // SAFETY: plain syscalls on the calling thread; `param` is a valid
+    // out-pointer.
+    unsafe {
+        let policy = libc::sched_getscheduler(0);
+        let name = match policy {
+            libc::SCHED_FIFO => "fifo",
+            libc::SCHED_RR => "rr",
+            libc::SCHED_BATCH => "batch",
+            libc::SCHED_IDLE => "idle",
+            libc::SCHED_OTHER => "normal",
+            _ => return None,
+        };
+        if matches!(policy, libc::SCHED_FIFO | libc::SCHED_RR) {
+            let mut param: libc::sched_param = std::mem::zeroed();
+            if libc::sched_getparam(0, &mut param) != 0 {
+                return None;
+            }
+            return Some(format!("{name} priority={}", param.sched_priority));
+        }
+        // getpriority returns -1 both for nice -1 and on error.
+        *libc::__errno_location() = 0;
+        let nice = libc::getpriority(libc::PRIO_PROCESS, 0);
+        if nice == -1 && *libc::__errno_location() != 0 {
+            return None;
+        }
+        Some(format!("{name} nice={nice}"))
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn current() -> Option<String> {
+    None
+}
@@ -0,0 +1,57 @@
+//! Progress display.
+
+use std::io::Write;
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use indicatif::{ProgressBar, ProgressDrawTarget, ProgressStyle};
+
+static BAR: OnceLock<ProgressBar> = OnceLock::new();
+
+/// Show a bar over `groups` schedule entries on stderr. Stays hidden when
+/// `enabled` is false; callers check for a terminal.
+pub fn init(groups: usize, enabled: bool) {
+    let bar = if enabled {
+        ProgressBar::with_draw_target(Some(groups as u64), ProgressDrawTarget::stderr())
+    } else {
+        ProgressBar::hidden()
+    };
+    bar.set_style(
+        ProgressStyle::with_template(
+            "{elapsed_precise} [{bar:30}] {pos}/{len} groups, ETA {eta} {wide_msg}",
+        )
+        .expect("valid progress template")
+        .progress_chars("=> "),
+    );
+    bar.enable_steady_tick(Duration::from_millis(250));
+    let _ = BAR.set(bar);
+}
+
+/// Describe what is running now, e.g. "starlark/arithmetic/M iter 3/10".
+pub fn status(msg: String) {
+    if let Some(bar) = BAR.get() {
+        bar.set_message(msg);
+    }
+}
+
+/// One schedule entry finished.
+pub fn inc() {
+    if let Some(bar) = BAR.get() {
+        bar.inc(1);
+    }
+}
+
+/// Write log output to stderr without tearing the bar.
+pub fn write_stderr(buf: &[u8]) -> std::io::Result<()> {
+    let write = || std::io::stderr().write_all(buf);
+    match BAR.get() {
+        Some(bar) => bar.suspend(write),
+        None => write(),
+    }
+}
+
+pub fn finish() {
+    if let Some(bar) = BAR.get() {
+        bar.finish_and_clear();
+    }
+}
@@ -0,0 +1,74 @@
+//! Interpreter provisioning (--provision-python).
+
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use anyhow::{Context, Result, bail};
+use tracing::{debug, info};
+
+/// --python-cache-dir, or `starlark-bench/python` under
+/// `$XDG_CACHE_HOME`, `~/Library/Caches` on macOS or `~/.cache`.
+pub fn cache_dir(explicit: Option<&Path>) -> Result<PathBuf> {
+    if let Some(dir) = explicit {
+        return Ok(dir.to_path_buf());
+    }
+    let base = std::env::var_os("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .filter(|dir| dir.is_absolute())
+        .or_else(|| {
+            let home = PathBuf::from(std::env::var_os("HOME")?);
+            Some(if cfg!(target_os = "macos") {
+                home.join("Library").join("Caches")
+            } else {
+                home.join(".cache")
+            })
+        })
+        .context("cannot find a cache directory; pass --python-cache-dir")?;
+    Ok(base.join("starlark-bench").join("python"))
+}
+
+/// `uv` working on the interpreters in `dir` only.
+fn uv(dir: &Path) -> Command {
+    let mut cmd = Command::new("uv");
+    cmd.env("UV_PYTHON_INSTALL_DIR", dir)
+        .env("UV_PYTHON_PREFERENCE", "only-managed");
+    cmd
+}
+
+/// The interpreter for `version` already installed in `dir`, if any.
+fn find(version: &str, dir: &Path) -> Result<Option<PathBuf>> {
+    let out = uv(dir)
+        .args(["python", "find", "--no-project", version])
+        .env("UV_PYTHON_DOWNLOADS", "never")
+        .stderr(Stdio::null())
+        .output()
+        .context("failed to run uv (see https://docs.astral.sh/uv/ to install it)")?;
+    let path = String::from_utf8_lossy(&out.stdout).trim().to_string();
+    Ok((out.status.success() && !path.is_empty()).then(|| PathBuf::from(path)))
+}
+
+/// Install CPython `version` into `dir` with uv, unless it is there
+/// already, and return the interpreter's path. A partial version like
+/// "3.12" is satisfied by any installed 3.12.x; records carry the exact
+/// `python_version`.
+pub fn python(version: &str, dir: &Path) -> Result<PathBuf> {
+    if let Some(path) = find(version, dir)? {
+        debug!("python {version} is cached at {}", path.display());
+        return Ok(path);
+    }
+    info!("installing python {version} into {} with uv", dir.display());
+    let status = uv(dir)
+        .args(["python", "install", version])
+        .stdout(Stdio::null())
+        .status()
+        .context("failed to run uv (see https://docs.astral.sh/uv/ to install it)")?;
+    if !status.success() {
+        bail!("uv python install {version} failed ({status})");
+    }
+    find(version, dir)?.with_context(|| {
+        format!(
+            "uv installed python {version}, but cannot find it in {}",
+            dir.display()
+        )
+    })
+}
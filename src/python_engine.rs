@@ -0,0 +1,1480 @@
+//! Python engine (subprocess).
+
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, ChildStdin, Command, ExitStatus, Output as ProcessOutput, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context, Result, bail};
+use serde::Deserialize;
+use tracing::{debug, info};
+
+use super::{Clock, ClockTimes, Param, Rusage, TimedOut, cgroup, energy, param_map, perf};
+
+#[derive(Deserialize)]
+struct Output {
+    timings_ns: Vec<u64>,
+    result: i64,
+    rss_kb: u64,
+    /// With --input, per-iteration time spent decoding it.
+    #[serde(default)]
+    marshal_ns: Vec<u64>,
+    /// With tracemalloc, each iteration's peak traced allocation.
+    #[serde(default)]
+    peak_alloc_bytes: Vec<u64>,
+    /// RSS after each iteration, where the harness can read it.
+    #[serde(default)]
+    current_rss_kb: Vec<u64>,
+    /// With `Launch::cold`, the phases summed in each of `timings_ns`.
+    #[serde(default)]
+    compile_ns: Vec<u64>,
+    #[serde(default)]
+    module_eval_ns: Vec<u64>,
+    #[serde(default)]
+    call_ns: Vec<u64>,
+    /// With `Launch::threads` above 1, each call's eval time per
+    /// iteration.
+    #[serde(default)]
+    thread_eval_ns: Vec<Vec<u64>>,
+    /// Process CPU time of each iteration's call; missing from older
+    /// harnesses.
+    #[serde(default)]
+    cpu_ns: Vec<u64>,
+    /// With `Launch::min_sample_ns`, the calls each of `timings_ns` and
+    /// `cpu_ns` covers.
+    #[serde(default)]
+    calls_per_sample: Option<u32>,
+    /// With `Launch::record_clocks`, each iteration on every clock.
+    #[serde(default)]
+    clocks: Vec<ClockTimes>,
+    /// The cyclic collector's pauses in each of `timings_ns`, and the
+    /// collections; missing from older harnesses.
+    #[serde(default)]
+    gc_ns: Vec<u64>,
+    #[serde(default)]
+    gc_collections: Vec<u64>,
+    /// With `Launch::threads` above 1, whether they called the script's
+    /// `run_parallel`.
+    #[serde(default)]
+    parallel: bool,
+    /// `sys.version` and the interpreter; missing from older harnesses.
+    #[serde(default)]
+    version: Option<String>,
+    #[serde(default)]
+    interpreter: Option<Interpreter>,
+    /// Wall-clock time the harness started loading, and the time from
+    /// then to the first iteration; missing from older harnesses.
+    #[serde(default)]
+    started_unix_ns: Option<u64>,
+    #[serde(default)]
+    import_ns: Option<u64>,
+}
+
+/// The interpreter a script ran in, as `_harness.py` describes it.
+#[derive(Clone, Deserialize)]
+pub struct Interpreter {
+    /// `sys.implementation.name`, e.g. "cpython" or "pypy".
+    pub implementation: String,
+    /// The language version from `sys.version_info`, e.g. "3.13.0" or
+    /// "3.14.0rc1".
+    pub version: String,
+    /// Whether it is a free-threaded build (`Py_GIL_DISABLED`).
+    pub gil_disabled: bool,
+}
+
+pub struct IterResult {
+    pub eval_dur: Duration,
+    pub result: i64,
+    pub marshal_dur: Option<Duration>,
+    /// Bytes, with `Launch::tracemalloc`.
+    pub peak_alloc_bytes: Option<u64>,
+    /// RSS right after the iteration (KiB), where the harness can read
+    /// it (Linux).
+    pub rss_kb: Option<u64>,
+    /// With `Launch::cold`, what `eval_dur` is made of.
+    pub cold: Option<ColdTimes>,
+    /// With `Launch::threads` above 1, each call's eval time; `eval_dur`
+    /// spans from the first call's start to the last call's end.
+    pub thread_eval_durs: Option<Vec<Duration>>,
+    /// Process CPU time of the call, all threads together.
+    pub cpu_dur: Option<Duration>,
+    /// With `Launch::record_clocks`, the call on every clock.
+    pub clocks: Option<ClockTimes>,
+    /// Time the cyclic collector paused the call for, part of
+    /// `eval_dur`, and the collections that ran.
+    pub gc_dur: Option<Duration>,
+    pub gc_collections: Option<u64>,
+}
+
+/// The phases of a cold iteration (--python-cold).
+#[derive(Clone, Copy)]
+pub struct ColdTimes {
+    /// Compiling the script's source to a code object.
+    pub compile_dur: Duration,
+    /// Executing its top level in a fresh module.
+    pub module_eval_dur: Duration,
+    /// The `run` call alone.
+    pub call_dur: Duration,
+}
+
+impl ColdTimes {
+    fn from_ns(compile_ns: u64, module_eval_ns: u64, call_ns: u64) -> ColdTimes {
+        ColdTimes {
+            compile_dur: Duration::from_nanos(compile_ns),
+            module_eval_dur: Duration::from_nanos(module_eval_ns),
+            call_dur: Duration::from_nanos(call_ns),
+        }
+    }
+}
+
+pub struct RunResult {
+    /// Per-iteration timings (measured inside Python).
+    pub iters: Vec<IterResult>,
+    /// Total subprocess wall time.
+    pub total_dur: Duration,
+    /// Max RSS reported by Python (KiB), and how it was measured; records
+    /// prefer each iteration's own `rss_kb`.
+    pub rss_kb: u64,
+    pub rss_note: &'static str,
+    /// The interpreter's `sys.version` and description, if the script
+    /// reported them.
+    pub version: Option<String>,
+    pub interpreter: Option<Interpreter>,
+    /// From spawning the subprocess until the harness started loading:
+    /// interpreter startup (and container start-up with --docker-image).
+    pub startup_dur: Option<Duration>,
+    /// From then until the first iteration: imports and module top level.
+    pub import_dur: Option<Duration>,
+    /// Usage of the subprocess's own cgroup (--cgroup).
+    pub cgroup: Option<cgroup::Usage>,
+    /// What the kernel accounted to the subprocess (Linux, not with
+    /// --docker-image or the pyperf harness).
+    pub process: Option<ProcessUsage>,
+    /// Its hardware events, with `Launch::perf` (not with --docker-image
+    /// or the pyperf harness either).
+    pub counters: Option<perf::Counts>,
+    /// Joules the CPU packages used meanwhile, with `Launch::energy`
+    /// (not with the pyperf harness).
+    pub energy_j: Option<f64>,
+    /// With `Launch::min_sample_ns`, the calls each iteration timed
+    /// together; `eval_dur` and `cpu_dur` are per call.
+    pub calls_per_sample: Option<u32>,
+    /// The threads split `n` with the script's `run_parallel`.
+    pub parallel: bool,
+}
+
+/// CPU time, peak RSS, context switches and page faults of a
+/// subprocess, read by the bench from the kernel rather than reported
+/// by the harness.
+#[derive(Clone, Copy)]
+pub struct ProcessUsage {
+    pub user: Duration,
+    pub sys: Duration,
+    pub max_rss_kb: u64,
+    pub rusage: Rusage,
+}
+
+impl ProcessUsage {
+    /// CPU time and counts since `before`; the peak is kept as is.
+    pub fn since(&self, before: &ProcessUsage) -> ProcessUsage {
+        ProcessUsage {
+            user: self.user.saturating_sub(before.user),
+            sys: self.sys.saturating_sub(before.sys),
+            max_rss_kb: self.max_rss_kb,
+            rusage: self.rusage.since(&before.rusage),
+        }
+    }
+
+    /// CPU time and counts divided evenly over `iters` iterations.
+    pub fn per_iter(&self, iters: usize) -> ProcessUsage {
+        ProcessUsage {
+            user: self.user / iters.max(1) as u32,
+            sys: self.sys / iters.max(1) as u32,
+            max_rss_kb: self.max_rss_kb,
+            rusage: self.rusage.per_iter(iters),
+        }
+    }
+}
+
+/// What [`wait`] learns about an exited child besides its status.
+#[derive(Default)]
+struct Exit {
+    /// Its rusage (Linux and macOS).
+    usage: Option<ProcessUsage>,
+    /// macOS, built with the perf feature: its instructions and cycles,
+    /// read before it was reaped.
+    counts: Option<perf::Counts>,
+}
+
+/// Wait for `child` to exit, or with `block` false check whether it has.
+/// On Linux the child is reaped with `wait4`, which also returns its
+/// rusage (including that of any children it waited for).
+#[cfg(target_os = "linux")]
+fn wait(child: &mut Child, block: bool) -> std::io::Result<Option<(ExitStatus, Exit)>> {
+    let flags = if block { 0 } else { libc::WNOHANG };
+    Ok(wait4(child, flags)?.map(|(status, usage)| {
+        let exit = Exit {
+            usage: Some(usage),
+            counts: None,
+        };
+        (status, exit)
+    }))
+}
+
+/// On macOS the child is first waited for without reaping it, so that
+/// its counters can still be read from the zombie, and then reaped with
+/// `wait4` as on Linux.
+#[cfg(target_os = "macos")]
+fn wait(child: &mut Child, block: bool) -> std::io::Result<Option<(ExitStatus, Exit)>> {
+    let flags = libc::WEXITED | libc::WNOWAIT | if block { 0 } else { libc::WNOHANG };
+    // SAFETY: siginfo_t is plain data, filled in by the call.
+    let mut info: libc::siginfo_t = unsafe { std::mem::zeroed() };
+    // SAFETY: info is valid for the duration of the call.
+    while unsafe { libc::waitid(libc::P_PID, child.id(), &mut info, flags) } != 0 {
+        let err = std::io::Error::last_os_error();
+        if err.kind() != std::io::ErrorKind::Interrupted {
+            return Err(err);
+        }
+    }
+    // With WNOHANG and the child still running, si_pid stays 0.
+    if info.si_pid == 0 {
+        return Ok(None);
+    }
+    let counts = perf::exited(child.id());
+    let Some((status, usage)) = wait4(child, 0)? else {
+        unreachable!("a blocking wait4 returns once the child has exited");
+    };
+    let exit = Exit {
+        usage: Some(usage),
+        counts,
+    };
+    Ok(Some((status, exit)))
+}
+
+/// Reap `child` with `wait4`; None if it is still running and `flags`
+/// has WNOHANG.
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+fn wait4(child: &Child, flags: libc::c_int) -> std::io::Result<Option<(ExitStatus, ProcessUsage)>> {
+    use std::os::unix::process::ExitStatusExt;
+
+    let mut status = 0;
+    // SAFETY: rusage is plain data, filled in by the kernel.
+    let mut usage: libc::rusage = unsafe { std::mem::zeroed() };
+    loop {
+        // SAFETY: both pointers are valid for the duration of the call.
+        match unsafe { libc::wait4(child.id() as libc::pid_t, &mut status, flags, &mut usage) } {
+            0 => return Ok(None),
+            -1 => {
+                let err = std::io::Error::last_os_error();
+                if err.kind() != std::io::ErrorKind::Interrupted {
+                    return Err(err);
+                }
+            }
+            _ => break,
+        }
+    }
+    let time = |t: libc::timeval| {
+        Duration::from_secs(t.tv_sec as u64) + Duration::from_micros(t.tv_usec as u64)
+    };
+    let usage = ProcessUsage {
+        user: time(usage.ru_utime),
+        sys: time(usage.ru_stime),
+        max_rss_kb: super::maxrss_kb(usage.ru_maxrss),
+        rusage: Rusage::from_raw(&usage),
+    };
+    Ok(Some((ExitStatus::from_raw(status), usage)))
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+fn wait(child: &mut Child, block: bool) -> std::io::Result<Option<(ExitStatus, Exit)>> {
+    if block {
+        return child.wait().map(|status| Some((status, Exit::default())));
+    }
+    Ok(child.try_wait()?.map(|status| (status, Exit::default())))
+}
+
+/// CPU time so far, peak RSS, page faults and context switches of the
+/// running process `pid`, from /proc/<pid>/stat (CPU time in clock
+/// ticks) and /proc/<pid>/status, whose switches are the main thread's.
+#[cfg(target_os = "linux")]
+fn running_usage(pid: u32) -> Option<ProcessUsage> {
+    let stat = std::fs::read_to_string(format!("/proc/{pid}/stat")).ok()?;
+    // Fields after the command name, which may contain spaces, start
+    // with the third (state); minflt and majflt are the 10th and 12th,
+    // utime and stime the 14th and 15th.
+    let fields: Vec<&str> = stat.rsplit_once(')')?.1.split_whitespace().collect();
+    // SAFETY: sysconf has no preconditions.
+    let tick = unsafe { libc::sysconf(libc::_SC_CLK_TCK) };
+    let ticks = |i: usize| -> Option<Duration> {
+        let ticks: u64 = fields.get(i)?.parse().ok()?;
+        Some(Duration::from_nanos(
+            ticks * 1_000_000_000 / tick.max(1) as u64,
+        ))
+    };
+    let count = |i: usize| -> Option<u64> { fields.get(i)?.parse().ok() };
+    let status = std::fs::read_to_string(format!("/proc/{pid}/status")).ok()?;
+    let status_field = |name: &str| -> Option<u64> {
+        status
+            .lines()
+            .find_map(|line| line.strip_prefix(name)?.strip_prefix(':'))?
+            .split_whitespace()
+            .next()?
+            .parse()
+            .ok()
+    };
+    Some(ProcessUsage {
+        user: ticks(11)?,
+        sys: ticks(12)?,
+        max_rss_kb: status_field("VmHWM")?,
+        rusage: Rusage {
+            voluntary_switches: status_field("voluntary_ctxt_switches")?,
+            involuntary_switches: status_field("nonvoluntary_ctxt_switches")?,
+            major_faults: count(9)?,
+            minor_faults: count(7)?,
+        },
+    })
+}
+
+#[cfg(not(target_os = "linux"))]
+fn running_usage(_pid: u32) -> Option<ProcessUsage> {
+    None
+}
+
+/// Lines of stderr kept for [`StderrTail`].
+const STDERR_TAIL_LINES: usize = 20;
+
+/// The last lines a subprocess wrote to stderr before it was killed,
+/// attached as context to its [`TimedOut`] error for the timeout record.
+#[derive(Debug)]
+pub struct StderrTail(pub String);
+
+impl std::fmt::Display for StderrTail {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "stderr ended with:\n{}", self.0)
+    }
+}
+
+/// A timeout of a subprocess that wrote `stderr`.
+fn timed_out(stderr: &[u8]) -> anyhow::Error {
+    let text = String::from_utf8_lossy(stderr);
+    let lines: Vec<&str> = text.trim_end().lines().collect();
+    let tail = lines[lines.len().saturating_sub(STDERR_TAIL_LINES)..].join("\n");
+    let err = anyhow::Error::new(TimedOut);
+    if tail.is_empty() {
+        err
+    } else {
+        err.context(StderrTail(tail))
+    }
+}
+
+/// Read `pipe` to the end on a thread of its own, logging each line at
+/// debug level (-v) as it arrives, prefixed with `label`.
+fn log_stderr(
+    mut pipe: impl Read + Send + 'static,
+    label: String,
+) -> JoinHandle<std::io::Result<Vec<u8>>> {
+    std::thread::spawn(move || {
+        let mut buf = Vec::new();
+        let mut chunk = [0; 4096];
+        let mut line_start = 0;
+        loop {
+            let read = pipe.read(&mut chunk)?;
+            if read == 0 {
+                break;
+            }
+            buf.extend_from_slice(&chunk[..read]);
+            while let Some(end) = buf[line_start..].iter().position(|&b| b == b'\n') {
+                let line = &buf[line_start..line_start + end];
+                debug!("{label}: {}", String::from_utf8_lossy(line));
+                line_start += end + 1;
+            }
+        }
+        if line_start < buf.len() {
+            debug!("{label}: {}", String::from_utf8_lossy(&buf[line_start..]));
+        }
+        Ok(buf)
+    })
+}
+
+/// How subprocesses of `script_path` are named in the log.
+fn log_label(script_path: &Path) -> String {
+    script_path.file_name().map_or_else(
+        || script_path.display().to_string(),
+        |name| name.to_string_lossy().into_owned(),
+    )
+}
+
+/// Run `cmd` to completion, writing `input` to its stdin, and killing it
+/// once `limit` (if any) has elapsed; the flag says whether it was. With
+/// a limit the child is polled every millisecond, so that is only used
+/// with --timeout. stderr is logged under `label` as it arrives.
+fn output_with_limit(
+    cmd: &mut Command,
+    input: Option<&str>,
+    limit: Option<Duration>,
+    label: String,
+) -> std::io::Result<(ProcessOutput, bool, Exit)> {
+    let stdin = if input.is_some() {
+        Stdio::piped()
+    } else {
+        Stdio::null()
+    };
+    let mut child = cmd
+        .stdin(stdin)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    let feed = child.stdin.take().zip(input).map(|(mut pipe, input)| {
+        let input = input.to_owned();
+        // Written from a thread of its own, so that a child that stops
+        // reading cannot block the drains below.
+        std::thread::spawn(move || pipe.write_all(input.as_bytes()))
+    });
+    let drain = |mut pipe: Box<dyn Read + Send>| {
+        std::thread::spawn(move || {
+            let mut buf = Vec::new();
+            pipe.read_to_end(&mut buf).map(|_| buf)
+        })
+    };
+    let stdout = drain(Box::new(child.stdout.take().expect("piped stdout")));
+    let stderr = log_stderr(child.stderr.take().expect("piped stderr"), label);
+
+    let start = Instant::now();
+    let mut killed = false;
+    let (status, exit) = loop {
+        let Some(limit) = limit else {
+            break wait(&mut child, true)?.expect("blocking wait");
+        };
+        if let Some(exited) = wait(&mut child, false)? {
+            break exited;
+        }
+        if start.elapsed() >= limit {
+            child.kill()?;
+            killed = true;
+            break wait(&mut child, true)?.expect("blocking wait");
+        }
+        std::thread::sleep(Duration::from_millis(1));
+    };
+    if let Some(feed) = feed {
+        // A child that exits without reading all of its input closes the
+        // pipe; its exit status reports the failure.
+        let _ = feed.join().expect("pipe writer panicked");
+    }
+    let join = |h: std::thread::JoinHandle<std::io::Result<Vec<u8>>>| {
+        h.join().expect("pipe reader panicked")
+    };
+    let output = ProcessOutput {
+        status,
+        stdout: join(stdout)?,
+        stderr: join(stderr)?,
+    };
+    Ok((output, killed, exit))
+}
+
+/// Where the script directory is mounted inside --docker-image containers.
+const CONTAINER_SCRIPTS: &str = "/bench";
+
+/// Set to "1" for `_harness.py` to disable the cyclic collector.
+const NO_GC_VAR: &str = "BENCH_PYTHON_NO_GC";
+
+/// Set to "1" for `_harness.py` to trace allocations with tracemalloc.
+const TRACEMALLOC_VAR: &str = "BENCH_PYTHON_TRACEMALLOC";
+
+/// Set to "1" for `_harness.py` to recompile the script every iteration.
+const COLD_VAR: &str = "BENCH_PYTHON_COLD";
+
+/// Set to the number of threads `_harness.py` calls `run` from.
+const THREADS_VAR: &str = "BENCH_PYTHON_THREADS";
+
+/// Set to the --min-sample-ns `_harness.py` batches calls of `run` up to.
+const MIN_SAMPLE_NS_VAR: &str = "BENCH_PYTHON_MIN_SAMPLE_NS";
+
+/// Set to the size of the buffer `_harness.py` writes through before
+/// every iteration (--cache-state cold).
+const EVICT_BYTES_VAR: &str = "BENCH_PYTHON_EVICT_BYTES";
+
+/// Set to "cpu" or "thread" for `_harness.py` to time iterations on that
+/// clock (--clock).
+const CLOCK_VAR: &str = "BENCH_PYTHON_CLOCK";
+
+/// Set to "1" for `_harness.py` to report every clock (--record-clocks).
+const RECORD_CLOCKS_VAR: &str = "BENCH_PYTHON_RECORD_CLOCKS";
+
+/// Set to "1" for `_harness.py` to serve iterations as a [`Worker`].
+const WORKER_VAR: &str = "BENCH_PYTHON_WORKER";
+
+/// How each subprocess starts the interpreter.
+#[derive(Clone, Copy)]
+pub struct Launch<'a> {
+    pub binary: &'a str,
+    /// Interpreter options, passed before the script (--python-args).
+    pub args: &'a [String],
+    /// Disable the cyclic collector in the harness (--python-no-gc).
+    pub no_gc: bool,
+    /// Report each iteration's peak traced allocation (--mem tracemalloc).
+    pub tracemalloc: bool,
+    /// Recompile and re-execute the script every iteration (--python-cold).
+    pub cold: bool,
+    /// Concurrent calls per iteration (--python-threads).
+    pub threads: u32,
+    /// Cut off the network and write access to the scripts
+    /// (--python-sandbox).
+    pub sandbox: bool,
+    /// Count hardware events (--perf-counters).
+    pub perf: bool,
+    /// Read the RAPL energy counters around it (--energy).
+    pub energy: bool,
+    /// Time run() in samples of several calls when one call is faster
+    /// than this (--min-sample-ns).
+    pub min_sample_ns: Option<u64>,
+    /// Write through a buffer of this many bytes before every iteration
+    /// (--cache-state cold).
+    pub evict_bytes: Option<usize>,
+    /// The clock `eval_ns` is read from (--clock).
+    pub clock: Clock,
+    /// Report the iteration on every clock (--record-clocks).
+    pub record_clocks: bool,
+    /// Put on PYTHONPATH, for scripts outside the directory of
+    /// `_harness.py` (registered workloads).
+    pub pythonpath: Option<&'a Path>,
+    /// The --docker-image to run it in.
+    pub image: Option<&'a str>,
+}
+
+/// Pull `image` unless it is already present, so that the download does
+/// not land in the first measurement.
+pub fn ensure_image(image: &str) -> Result<()> {
+    let present = Command::new("docker")
+        .args(["image", "inspect", image])
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .context("failed to run docker")?
+        .success();
+    if !present {
+        info!("pulling container image {image}");
+        let status = Command::new("docker")
+            .args(["pull", "--quiet", image])
+            .stdout(Stdio::null())
+            .status()
+            .context("failed to run docker")?;
+        if !status.success() {
+            bail!("docker pull {image} failed ({status})");
+        }
+    }
+    debug!("python runs in container image {image}");
+    Ok(())
+}
+
+/// A container name unique to this process and call.
+fn container_name() -> String {
+    static CONTAINERS: AtomicUsize = AtomicUsize::new(0);
+    format!(
+        "starlark-bench-{}-{}",
+        std::process::id(),
+        CONTAINERS.fetch_add(1, Ordering::Relaxed)
+    )
+}
+
+/// Killing the docker client leaves the container running.
+fn remove_container(name: &str) {
+    let _ = Command::new("docker")
+        .args(["rm", "--force", name])
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status();
+}
+
+/// The interpreter running `script_path`, in a container named
+/// `container` of `launch.image` if given, without the harness's
+/// positional arguments. `worker` starts the harness as a worker.
+fn command(
+    launch: Launch,
+    container: Option<&str>,
+    script_path: &Path,
+    interactive: bool,
+    worker: bool,
+) -> Result<Command> {
+    let mut vars: Vec<(&str, String)> = [
+        (launch.no_gc, NO_GC_VAR),
+        (launch.tracemalloc, TRACEMALLOC_VAR),
+        (launch.cold, COLD_VAR),
+        (launch.record_clocks, RECORD_CLOCKS_VAR),
+        (worker, WORKER_VAR),
+    ]
+    .into_iter()
+    .filter(|&(set, _)| set)
+    .map(|(_, var)| (var, "1".to_string()))
+    .collect();
+    if launch.threads > 1 {
+        vars.push((THREADS_VAR, launch.threads.to_string()));
+    }
+    if let Some(ns) = launch.min_sample_ns {
+        vars.push((MIN_SAMPLE_NS_VAR, ns.to_string()));
+    }
+    if let Some(bytes) = launch.evict_bytes {
+        vars.push((EVICT_BYTES_VAR, bytes.to_string()));
+    }
+    if launch.clock != Clock::Wall {
+        vars.push((CLOCK_VAR, launch.clock.to_string()));
+    }
+    if let (Some(image), Some(name)) = (launch.image, container) {
+        return container_command(image, name, launch, script_path, interactive, &vars);
+    }
+    let mut cmd = if launch.sandbox && cfg!(target_os = "macos") {
+        let mut cmd = Command::new("sandbox-exec");
+        cmd.arg("-p")
+            .arg(macos_profile(script_dir(script_path)?))
+            .arg(launch.binary);
+        cmd
+    } else {
+        Command::new(launch.binary)
+    };
+    cmd.args(launch.args).arg(script_path);
+    for (var, value) in vars {
+        cmd.env(var, value);
+    }
+    if let Some(dir) = launch.pythonpath {
+        let mut paths = vec![std::path::absolute(dir)?];
+        paths.extend(
+            std::env::var_os("PYTHONPATH")
+                .iter()
+                .flat_map(std::env::split_paths),
+        );
+        cmd.env("PYTHONPATH", std::env::join_paths(paths)?);
+    }
+    Ok(cmd)
+}
+
+/// Write `data` to the file at `path` with one `write`; safe to call
+/// between fork and exec.
+#[cfg(target_os = "linux")]
+fn write_raw(path: &std::ffi::CStr, data: &[u8]) -> std::io::Result<()> {
+    // SAFETY: open, write and close are async-signal-safe, and the
+    // pointers are valid for the calls.
+    unsafe {
+        let fd = libc::open(path.as_ptr(), libc::O_WRONLY | libc::O_CLOEXEC);
+        if fd < 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        let written = libc::write(fd, data.as_ptr().cast(), data.len());
+        let err = std::io::Error::last_os_error();
+        libc::close(fd);
+        if written == data.len() as isize {
+            Ok(())
+        } else {
+            Err(err)
+        }
+    }
+}
+
+/// Have `cmd` join `leaf` between fork and exec, so that everything the
+/// interpreter does is accounted to it.
+fn join_cgroup(cmd: &mut Command, leaf: &cgroup::Leaf) -> Result<()> {
+    #[cfg(target_os = "linux")]
+    {
+        use std::os::unix::process::CommandExt;
+
+        let procs = std::ffi::CString::new(leaf.procs_path().into_os_string().into_encoded_bytes())
+            .context("cgroup path contains a NUL byte")?;
+        // SAFETY: the closure only makes async-signal-safe syscalls and
+        // does not allocate.
+        unsafe {
+            cmd.pre_exec(move || write_raw(&procs, b"0"));
+        }
+    }
+    #[cfg(not(target_os = "linux"))]
+    let _ = (cmd, leaf);
+    Ok(())
+}
+
+/// The directory holding `script_path`, resolved, which --python-sandbox
+/// makes read-only.
+fn script_dir(script_path: &Path) -> Result<PathBuf> {
+    let dir = script_path.parent().unwrap_or(Path::new("."));
+    std::fs::canonicalize(dir).with_context(|| format!("cannot resolve {}", dir.display()))
+}
+
+/// A sandbox-exec profile that allows everything but network access and
+/// writes below `dir`.
+fn macos_profile(dir: PathBuf) -> String {
+    let dir = dir.display().to_string();
+    let dir = dir.replace('\\', "\\\\").replace('"', "\\\"");
+    format!(r#"(version 1)(allow default)(deny network*)(deny file-write* (subpath "{dir}"))"#)
+}
+
+/// Put `cmd` in the --python-sandbox on Linux: between fork and exec it
+/// enters new user, network and mount namespaces, keeping its own user
+/// and group, and bind-mounts the script's directory read-only over
+/// itself. The new network namespace has only a loopback device, which
+/// is down. Registered after [`join_cgroup`], which needs the host's
+/// namespaces. On macOS, [`command`] wraps the interpreter in
+/// sandbox-exec instead.
+fn sandbox(cmd: &mut Command, script_path: &Path) -> Result<()> {
+    #[cfg(target_os = "linux")]
+    {
+        use std::ffi::CString;
+        use std::os::unix::process::CommandExt;
+
+        let dir = script_dir(script_path)?;
+        let dir = CString::new(dir.into_os_string().into_encoded_bytes())
+            .context("script path contains a NUL byte")?;
+        // A read-only remount in a user namespace must keep the flags
+        // the mount already has.
+        // SAFETY: statvfs is plain data, filled in by the call.
+        let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+        if unsafe { libc::statvfs(dir.as_ptr(), &mut stat) } != 0 {
+            return Err(std::io::Error::last_os_error())
+                .context("cannot read the mount flags of the scripts directory");
+        }
+        let kept = stat.f_flag
+            & (libc::ST_NOSUID
+                | libc::ST_NODEV
+                | libc::ST_NOEXEC
+                | libc::ST_NOATIME
+                | libc::ST_NODIRATIME
+                | libc::ST_RELATIME);
+        // SAFETY: getuid and getgid cannot fail.
+        let (uid, gid) = unsafe { (libc::getuid(), libc::getgid()) };
+        let uid_map = format!("{uid} {uid} 1");
+        let gid_map = format!("{gid} {gid} 1");
+        // SAFETY: the closure only makes async-signal-safe syscalls and
+        // does not allocate.
+        unsafe {
+            cmd.pre_exec(move || {
+                let none = std::ptr::null();
+                let check = |ret: libc::c_int| {
+                    if ret == 0 {
+                        Ok(())
+                    } else {
+                        Err(std::io::Error::last_os_error())
+                    }
+                };
+                check(libc::unshare(
+                    libc::CLONE_NEWUSER | libc::CLONE_NEWNET | libc::CLONE_NEWNS,
+                ))?;
+                write_raw(c"/proc/self/setgroups", b"deny")?;
+                write_raw(c"/proc/self/uid_map", uid_map.as_bytes())?;
+                write_raw(c"/proc/self/gid_map", gid_map.as_bytes())?;
+                // Keep the mount below from propagating to the host.
+                check(libc::mount(
+                    none,
+                    c"/".as_ptr(),
+                    none,
+                    libc::MS_REC | libc::MS_PRIVATE,
+                    none.cast(),
+                ))?;
+                check(libc::mount(
+                    dir.as_ptr(),
+                    dir.as_ptr(),
+                    none,
+                    libc::MS_BIND | libc::MS_REC,
+                    none.cast(),
+                ))?;
+                check(libc::mount(
+                    none,
+                    dir.as_ptr(),
+                    none,
+                    libc::MS_REMOUNT | libc::MS_BIND | libc::MS_RDONLY | kept,
+                    none.cast(),
+                ))
+            });
+        }
+    }
+    #[cfg(not(target_os = "linux"))]
+    let _ = (cmd, script_path);
+    Ok(())
+}
+
+/// `docker run` invoking `launch.binary` on the script inside `image`,
+/// keeping stdin open for `interactive` and setting each of `vars` to
+/// its value. The container gets the CPUs the calling thread may use, since it
+/// is started by the Docker daemon and does not inherit the affinity.
+fn container_command(
+    image: &str,
+    name: &str,
+    launch: Launch,
+    script_path: &Path,
+    interactive: bool,
+    vars: &[(&str, String)],
+) -> Result<Command> {
+    let dir = script_path.parent().unwrap_or(Path::new("."));
+    let dir =
+        std::fs::canonicalize(dir).with_context(|| format!("cannot resolve {}", dir.display()))?;
+    let file = script_path
+        .file_name()
+        .context("script path has no file name")?;
+    let cpus = super::affinity::available()
+        .iter()
+        .map(|c| c.to_string())
+        .collect::<Vec<_>>()
+        .join(",");
+    let mut cmd = Command::new("docker");
+    cmd.args(["run", "--rm", "--network", "none", "--name", name]);
+    if interactive {
+        cmd.arg("--interactive");
+    }
+    for (var, value) in vars {
+        cmd.arg("--env").arg(format!("{var}={value}"));
+    }
+    cmd.arg("--cpuset-cpus")
+        .arg(cpus)
+        .arg("--volume")
+        .arg(format!("{}:{CONTAINER_SCRIPTS}:ro", dir.display()))
+        .arg(image)
+        .arg(launch.binary)
+        .args(launch.args)
+        .arg(Path::new(CONTAINER_SCRIPTS).join(file));
+    Ok(cmd)
+}
+
+/// Spawn CPython, run the workload `iter_count` times inside a single
+/// process, and collect per-iteration timings reported by the script.
+/// With a `timeout`, the subprocess is killed once it has run for
+/// `iter_count` times the per-iteration limit. With a `launch.image`,
+/// CPython runs in a fresh container of it. `input` is --input data as
+/// JSON, written to the script's stdin.
+#[allow(clippy::too_many_arguments)]
+pub fn run(
+    launch: Launch,
+    script_path: &Path,
+    n: usize,
+    seed: u64,
+    params: &[Param],
+    input: Option<&str>,
+    iter_count: u32,
+    timeout: Option<Duration>,
+) -> Result<RunResult> {
+    let container = launch.image.map(|_| container_name());
+    let mut cmd = command(
+        launch,
+        container.as_deref(),
+        script_path,
+        input.is_some(),
+        false,
+    )?;
+    cmd.arg(n.to_string())
+        .arg(seed.to_string())
+        .arg(iter_count.to_string());
+    // Only passed when present, so scripts with their own simpler harness
+    // keep working.
+    if !params.is_empty() || input.is_some() {
+        cmd.arg(serde_json::to_string(&param_map(params))?);
+    }
+    if input.is_some() {
+        cmd.arg("stdin");
+    }
+    let leaf = cgroup::leaf()?;
+    if let Some(leaf) = &leaf {
+        join_cgroup(&mut cmd, leaf)?;
+    }
+    if launch.sandbox {
+        sandbox(&mut cmd, script_path)?;
+    }
+    debug!("spawning {cmd:?}");
+    let spawned = SystemTime::now();
+    let wall_start = Instant::now();
+    let limit = timeout.map(|limit| limit * iter_count.max(1));
+    // Opened on this thread, which spawns the subprocess.
+    let counters = (launch.perf && container.is_none())
+        .then(perf::Counters::next_exec)
+        .transpose()?
+        .flatten();
+    let energy_before = launch.energy.then(energy::read).transpose()?;
+    let output = output_with_limit(&mut cmd, input, limit, log_label(script_path));
+    let total_dur = wall_start.elapsed();
+    let energy_j = match energy_before {
+        Some(before) => Some(energy::read()?.joules_since(&before)),
+        None => None,
+    };
+    let (output, killed, exit) =
+        output.with_context(|| format!("failed to spawn {}", cmd.get_program().display()))?;
+    if killed {
+        if let Some(name) = &container {
+            remove_container(name);
+        }
+        return Err(timed_out(&output.stderr));
+    }
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        bail!(
+            "Python script {} failed (exit {}):\n{}",
+            script_path.display(),
+            output.status,
+            stderr
+        );
+    }
+
+    let stdout = String::from_utf8(output.stdout).context("Python stdout is not valid UTF-8")?;
+    let parsed: Output = serde_json::from_str(stdout.trim())
+        .with_context(|| format!("failed to parse Python JSON output: {stdout}"))?;
+
+    let calls = u64::from(parsed.calls_per_sample.unwrap_or(1));
+    let iters = parsed
+        .timings_ns
+        .iter()
+        .enumerate()
+        .map(|(i, &ns)| IterResult {
+            eval_dur: Duration::from_nanos(ns / calls),
+            result: parsed.result,
+            marshal_dur: parsed.marshal_ns.get(i).map(|&ns| Duration::from_nanos(ns)),
+            peak_alloc_bytes: parsed.peak_alloc_bytes.get(i).copied(),
+            rss_kb: parsed.current_rss_kb.get(i).copied(),
+            cold: match (
+                parsed.compile_ns.get(i),
+                parsed.module_eval_ns.get(i),
+                parsed.call_ns.get(i),
+            ) {
+                (Some(&c), Some(&m), Some(&r)) => Some(ColdTimes::from_ns(c, m, r)),
+                _ => None,
+            },
+            thread_eval_durs: parsed
+                .thread_eval_ns
+                .get(i)
+                .map(|ns| ns.iter().map(|&ns| Duration::from_nanos(ns)).collect()),
+            cpu_dur: parsed
+                .cpu_ns
+                .get(i)
+                .map(|&ns| Duration::from_nanos(ns / calls)),
+            clocks: parsed.clocks.get(i).map(|c| c.per_call(calls)),
+            gc_dur: parsed
+                .gc_ns
+                .get(i)
+                .map(|&ns| Duration::from_nanos(ns / calls)),
+            gc_collections: parsed.gc_collections.get(i).map(|&c| c / calls),
+        })
+        .collect();
+
+    Ok(RunResult {
+        iters,
+        total_dur,
+        rss_kb: parsed.rss_kb,
+        rss_note: "getrusage maxrss; subprocess only",
+        version: parsed.version,
+        interpreter: parsed.interpreter,
+        // Containers share the host's clock, so this holds with --docker-image.
+        startup_dur: parsed.started_unix_ns.and_then(|ns| {
+            let started = UNIX_EPOCH + Duration::from_nanos(ns);
+            started.duration_since(spawned).ok()
+        }),
+        import_dur: parsed.import_ns.map(Duration::from_nanos),
+        cgroup: leaf.map(|l| l.usage()).transpose()?,
+        // With --docker-image the child is the docker CLI.
+        process: exit.usage.filter(|_| container.is_none()),
+        counters: match counters {
+            Some(counters) => Some(counters.read()?),
+            None => exit.counts.filter(|_| launch.perf && container.is_none()),
+        },
+        energy_j,
+        calls_per_sample: parsed.calls_per_sample,
+        parallel: parsed.parallel,
+    })
+}
+
+/// pyperf's JSON, as far as the bench reads it.
+#[derive(Deserialize)]
+struct PyperfSuite {
+    /// Metadata common to all benchmarks.
+    #[serde(default)]
+    metadata: serde_json::Map<String, serde_json::Value>,
+    benchmarks: Vec<PyperfBenchmark>,
+}
+
+#[derive(Deserialize)]
+struct PyperfBenchmark {
+    /// Metadata common to all runs.
+    #[serde(default)]
+    metadata: serde_json::Map<String, serde_json::Value>,
+    runs: Vec<PyperfRun>,
+}
+
+/// One pyperf worker process. Values are seconds per call, each the mean
+/// of a calibrated number of loops; the calibration run has warmups only.
+#[derive(Deserialize)]
+struct PyperfRun {
+    #[serde(default)]
+    metadata: serde_json::Map<String, serde_json::Value>,
+    #[serde(default)]
+    warmups: Vec<(u64, f64)>,
+    #[serde(default)]
+    values: Vec<f64>,
+}
+
+/// A path under the temporary directory unique to this process and call.
+fn temp_path(suffix: &str) -> PathBuf {
+    static FILES: AtomicUsize = AtomicUsize::new(0);
+    std::env::temp_dir().join(format!(
+        "starlark-bench-{}-{}{suffix}",
+        std::process::id(),
+        FILES.fetch_add(1, Ordering::Relaxed)
+    ))
+}
+
+/// Run the workload under pyperf (--python-harness pyperf), through
+/// `_pyperf.py` next to the script, and convert pyperf's runs into
+/// iterations: all of their warmups, then all of their values. Returns
+/// them with the number of warmups. `input` is --input data as JSON,
+/// handed over in a temporary file.
+pub fn run_pyperf(
+    launch: Launch,
+    script_path: &Path,
+    n: usize,
+    seed: u64,
+    params: &[Param],
+    input: Option<&str>,
+) -> Result<(RunResult, usize)> {
+    let runner = script_path.with_file_name("_pyperf.py");
+    let output_path = temp_path(".json");
+    let input_path = input
+        .map(|text| {
+            let path = temp_path(".json");
+            std::fs::write(&path, text)
+                .with_context(|| format!("cannot write {}", path.display()))
+                .map(|_| path)
+        })
+        .transpose()?;
+    let mut cmd = command(launch, None, &runner, false, false)?;
+    cmd.arg("--quiet").arg("--output").arg(&output_path);
+    // pyperf clears the environment of its worker processes.
+    if launch.no_gc {
+        cmd.arg(format!("--inherit-environ={NO_GC_VAR}"));
+    }
+    cmd.arg(script_path)
+        .arg(n.to_string())
+        .arg(seed.to_string())
+        .arg(serde_json::to_string(&param_map(params))?);
+    if let Some(path) = &input_path {
+        cmd.arg(path);
+    }
+    let leaf = cgroup::leaf()?;
+    if let Some(leaf) = &leaf {
+        join_cgroup(&mut cmd, leaf)?;
+    }
+    if launch.sandbox {
+        sandbox(&mut cmd, script_path)?;
+    }
+    debug!("spawning {cmd:?}");
+    let wall_start = Instant::now();
+    let output = output_with_limit(&mut cmd, None, None, log_label(script_path));
+    let total_dur = wall_start.elapsed();
+    if let Some(path) = &input_path {
+        let _ = std::fs::remove_file(path);
+    }
+    let (output, _, _) =
+        output.with_context(|| format!("failed to spawn {}", cmd.get_program().display()))?;
+    let text = std::fs::read_to_string(&output_path);
+    let _ = std::fs::remove_file(&output_path);
+    if !output.status.success() {
+        bail!(
+            "pyperf run of {} failed (exit {}):\n{}",
+            script_path.display(),
+            output.status,
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+    let text = text.with_context(|| format!("pyperf wrote no {}", output_path.display()))?;
+    let suite: PyperfSuite =
+        serde_json::from_str(&text).context("failed to parse pyperf JSON output")?;
+    let bench = suite
+        .benchmarks
+        .first()
+        .context("pyperf JSON output has no benchmark")?;
+    let metadata = |key: &str| {
+        bench
+            .metadata
+            .get(key)
+            .or_else(|| suite.metadata.get(key))
+            .and_then(|v| v.as_str())
+    };
+    let result = metadata("bench_result")
+        .and_then(|r| r.parse().ok())
+        .context("pyperf JSON output has no bench_result")?;
+    let iter = |secs: f64| IterResult {
+        eval_dur: Duration::from_secs_f64(secs),
+        result,
+        marshal_dur: None,
+        peak_alloc_bytes: None,
+        rss_kb: None,
+        cold: None,
+        thread_eval_durs: None,
+        cpu_dur: None,
+        clocks: None,
+        gc_dur: None,
+        gc_collections: None,
+    };
+    let mut iters: Vec<IterResult> = bench
+        .runs
+        .iter()
+        .flat_map(|run| run.warmups.iter().map(|&(_, secs)| iter(secs)))
+        .collect();
+    let warmups = iters.len();
+    iters.extend(
+        bench
+            .runs
+            .iter()
+            .flat_map(|run| run.values.iter().map(|&secs| iter(secs))),
+    );
+    // Recorded by pyperf's workers on Linux, in bytes.
+    let rss_kb = bench
+        .runs
+        .iter()
+        .filter_map(|run| run.metadata.get("mem_max_rss")?.as_u64())
+        .max()
+        .unwrap_or(0)
+        / 1024;
+    Ok((
+        RunResult {
+            iters,
+            total_dur,
+            rss_kb,
+            rss_note: "pyperf mem_max_rss; largest worker process",
+            version: metadata("bench_version").map(str::to_string),
+            interpreter: metadata("bench_interpreter").and_then(|i| serde_json::from_str(i).ok()),
+            startup_dur: None,
+            import_dur: None,
+            cgroup: leaf.map(|l| l.usage()).transpose()?,
+            // Covers pyperf's calibration and all of its worker
+            // processes, so it cannot be divided over the values.
+            process: None,
+            counters: None,
+            energy_j: None,
+            calls_per_sample: None,
+            parallel: false,
+        },
+        warmups,
+    ))
+}
+
+/// The worker's first message.
+#[derive(Deserialize)]
+struct Hello {
+    version: String,
+    interpreter: Interpreter,
+    started_unix_ns: u64,
+    import_ns: u64,
+}
+
+/// The worker's answer to one iteration request.
+#[derive(Deserialize)]
+struct Reply {
+    eval_ns: u64,
+    result: i64,
+    rss_kb: u64,
+    #[serde(default)]
+    marshal_ns: Option<u64>,
+    #[serde(default)]
+    peak_alloc_bytes: Option<u64>,
+    #[serde(default)]
+    current_rss_kb: Option<u64>,
+    #[serde(default)]
+    compile_ns: Option<u64>,
+    #[serde(default)]
+    module_eval_ns: Option<u64>,
+    #[serde(default)]
+    call_ns: Option<u64>,
+    #[serde(default)]
+    thread_eval_ns: Option<Vec<u64>>,
+    #[serde(default)]
+    cpu_ns: Option<u64>,
+    #[serde(default)]
+    calls_per_sample: Option<u32>,
+    #[serde(default)]
+    parallel: bool,
+    #[serde(default)]
+    clocks: Option<ClockTimes>,
+    #[serde(default)]
+    gc_ns: Option<u64>,
+    #[serde(default)]
+    gc_collections: Option<u64>,
+}
+
+/// Read one length-prefixed message; None at the end of the stream.
+fn read_frame(r: &mut impl Read) -> std::io::Result<Option<Vec<u8>>> {
+    let mut len = [0; 4];
+    match r.read_exact(&mut len) {
+        Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+        r => r?,
+    }
+    let mut frame = vec![0; u32::from_be_bytes(len) as usize];
+    r.read_exact(&mut frame)?;
+    Ok(Some(frame))
+}
+
+/// A long-lived interpreter running the harness as a worker
+/// (--python-worker): it runs one iteration per request, so the caller
+/// decides when each runs, can time each out on its own and samples the
+/// interpreter's RSS in between.
+pub struct Worker {
+    child: Child,
+    stdin: ChildStdin,
+    /// Messages from its stdout, read on a thread of their own so that
+    /// waiting for one can time out; closed when the worker exits.
+    frames: Receiver<Vec<u8>>,
+    stderr: Option<JoinHandle<std::io::Result<Vec<u8>>>>,
+    container: Option<String>,
+    script: PathBuf,
+    leaf: Option<cgroup::Leaf>,
+    /// cgroup usage when the latest iteration ended.
+    usage: Option<cgroup::Usage>,
+    /// Its own usage then, from /proc (not with --docker-image).
+    process: Option<ProcessUsage>,
+    /// With `Launch::perf`, its counters and their counts then.
+    counters: Option<(perf::Counters, perf::Counts)>,
+    /// Read the energy counters around each iteration (`Launch::energy`).
+    energy: bool,
+    /// From its first message.
+    version: Option<String>,
+    interpreter: Option<Interpreter>,
+    startup_dur: Option<Duration>,
+    import_dur: Option<Duration>,
+    exited: bool,
+}
+
+impl Worker {
+    /// Start a worker for `script_path` and wait for it to report in,
+    /// for at most `timeout`. `input` is --input data as JSON, sent once.
+    pub fn start(
+        launch: Launch,
+        script_path: &Path,
+        n: usize,
+        seed: u64,
+        params: &[Param],
+        input: Option<&str>,
+        timeout: Option<Duration>,
+    ) -> Result<Worker> {
+        let container = launch.image.map(|_| container_name());
+        let mut cmd = command(launch, container.as_deref(), script_path, true, true)?;
+        cmd.arg(n.to_string()).arg(seed.to_string()).arg("0");
+        if !params.is_empty() {
+            cmd.arg(serde_json::to_string(&param_map(params))?);
+        }
+        let leaf = cgroup::leaf()?;
+        if let Some(leaf) = &leaf {
+            join_cgroup(&mut cmd, leaf)?;
+        }
+        if launch.sandbox {
+            sandbox(&mut cmd, script_path)?;
+        }
+        debug!("spawning worker {cmd:?}");
+        let spawned = SystemTime::now();
+        let mut child = cmd
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .with_context(|| format!("failed to spawn {}", cmd.get_program().display()))?;
+        let stdin = child.stdin.take().expect("piped stdin");
+        let mut stdout = child.stdout.take().expect("piped stdout");
+        let stderr = child.stderr.take().expect("piped stderr");
+        let (sender, frames) = mpsc::channel();
+        std::thread::spawn(move || {
+            while let Ok(Some(frame)) = read_frame(&mut stdout) {
+                if sender.send(frame).is_err() {
+                    break;
+                }
+            }
+        });
+        let stderr = log_stderr(stderr, log_label(script_path));
+        let mut worker = Worker {
+            child,
+            stdin,
+            frames,
+            stderr: Some(stderr),
+            container,
+            script: script_path.to_path_buf(),
+            leaf,
+            usage: None,
+            process: None,
+            counters: None,
+            energy: launch.energy,
+            version: None,
+            interpreter: None,
+            startup_dur: None,
+            import_dur: None,
+            exited: false,
+        };
+        let hello: Hello = worker.receive(timeout)?;
+        let started = UNIX_EPOCH + Duration::from_nanos(hello.started_unix_ns);
+        worker.startup_dur = started.duration_since(spawned).ok();
+        worker.import_dur = Some(Duration::from_nanos(hello.import_ns));
+        worker.version = Some(hello.version);
+        worker.interpreter = Some(hello.interpreter);
+        if let Some(text) = input {
+            worker.send(&serde_json::json!({"op": "input", "text": text}))?;
+        }
+        worker.usage = worker.leaf.as_ref().map(|l| l.usage()).transpose()?;
+        if worker.container.is_none() {
+            worker.process = running_usage(worker.child.id());
+            if launch.perf {
+                let counters = perf::Counters::process(worker.child.id())?;
+                let counts = counters.read()?;
+                worker.counters = Some((counters, counts));
+            }
+        }
+        Ok(worker)
+    }
+
+    /// Run one iteration, killing the worker if it takes longer than
+    /// `timeout`. The result has that one iteration, with the time the
+    /// request took as `total_dur`.
+    pub fn iterate(&mut self, timeout: Option<Duration>) -> Result<RunResult> {
+        let energy_before = self.energy.then(energy::read).transpose()?;
+        let start = Instant::now();
+        self.send(&serde_json::json!({"op": "iter"}))?;
+        let reply: Reply = self.receive(timeout)?;
+        let total_dur = start.elapsed();
+        let energy_j = match energy_before {
+            Some(before) => Some(energy::read()?.joules_since(&before)),
+            None => None,
+        };
+        let cgroup = match (&self.leaf, self.usage) {
+            (Some(leaf), Some(before)) => {
+                let now = leaf.usage()?;
+                self.usage = Some(now);
+                Some(now.since(&before))
+            }
+            _ => None,
+        };
+        let process = self.process.and_then(|before| {
+            let now = running_usage(self.child.id())?;
+            self.process = Some(now);
+            Some(now.since(&before))
+        });
+        let counters = match &mut self.counters {
+            Some((counters, before)) => {
+                let now = counters.read()?;
+                let counts = now.since(before);
+                *before = now;
+                Some(counts)
+            }
+            None => None,
+        };
+        let calls = u64::from(reply.calls_per_sample.unwrap_or(1));
+        Ok(RunResult {
+            iters: vec![IterResult {
+                eval_dur: Duration::from_nanos(reply.eval_ns / calls),
+                result: reply.result,
+                marshal_dur: reply.marshal_ns.map(Duration::from_nanos),
+                peak_alloc_bytes: reply.peak_alloc_bytes,
+                rss_kb: reply.current_rss_kb,
+                cold: match (reply.compile_ns, reply.module_eval_ns, reply.call_ns) {
+                    (Some(c), Some(m), Some(r)) => Some(ColdTimes::from_ns(c, m, r)),
+                    _ => None,
+                },
+                thread_eval_durs: reply
+                    .thread_eval_ns
+                    .map(|ns| ns.into_iter().map(Duration::from_nanos).collect()),
+                cpu_dur: reply.cpu_ns.map(|ns| Duration::from_nanos(ns / calls)),
+                clocks: reply.clocks.map(|c| c.per_call(calls)),
+                gc_dur: reply.gc_ns.map(|ns| Duration::from_nanos(ns / calls)),
+                gc_collections: reply.gc_collections.map(|c| c / calls),
+            }],
+            total_dur,
+            rss_kb: reply.rss_kb,
+            rss_note: "getrusage maxrss; worker only",
+            version: self.version.clone(),
+            interpreter: self.interpreter.clone(),
+            startup_dur: self.startup_dur,
+            import_dur: self.import_dur,
+            cgroup,
+            process,
+            counters,
+            energy_j,
+            calls_per_sample: reply.calls_per_sample,
+            parallel: reply.parallel,
+        })
+    }
+
+    /// Ask the worker to exit and wait for it.
+    pub fn close(mut self) -> Result<()> {
+        self.send(&serde_json::json!({"op": "exit"}))?;
+        let status = self.child.wait()?;
+        self.exited = true;
+        if !status.success() {
+            return Err(self.failure(status));
+        }
+        Ok(())
+    }
+
+    fn send(&mut self, message: &serde_json::Value) -> Result<()> {
+        let body = serde_json::to_vec(message)?;
+        let mut frame = (body.len() as u32).to_be_bytes().to_vec();
+        frame.extend(body);
+        // A worker that died closes the pipe; what it left on stderr
+        // says why.
+        if self.stdin.write_all(&frame).is_err() {
+            let status = self.child.wait()?;
+            self.exited = true;
+            return Err(self.failure(status));
+        }
+        Ok(())
+    }
+
+    /// Wait up to `timeout` for the next message.
+    fn receive<T: serde::de::DeserializeOwned>(&mut self, timeout: Option<Duration>) -> Result<T> {
+        let frame = match timeout {
+            Some(limit) => self.frames.recv_timeout(limit),
+            None => self.frames.recv().map_err(RecvTimeoutError::from),
+        };
+        let frame = match frame {
+            Ok(frame) => frame,
+            Err(RecvTimeoutError::Timeout) => {
+                self.kill();
+                return Err(timed_out(&self.take_stderr()));
+            }
+            Err(RecvTimeoutError::Disconnected) => {
+                let status = self.child.wait()?;
+                self.exited = true;
+                return Err(self.failure(status));
+            }
+        };
+        serde_json::from_slice(&frame).with_context(|| {
+            format!(
+                "failed to parse Python worker message: {}",
+                String::from_utf8_lossy(&frame)
+            )
+        })
+    }
+
+    /// The error for a worker that exited with `status`.
+    fn failure(&mut self, status: ExitStatus) -> anyhow::Error {
+        let stderr = self.take_stderr();
+        anyhow::anyhow!(
+            "Python worker for {} failed (exit {status}):\n{}",
+            self.script.display(),
+            String::from_utf8_lossy(&stderr)
+        )
+    }
+
+    /// Everything the worker wrote to stderr; call once it has exited.
+    fn take_stderr(&mut self) -> Vec<u8> {
+        self.stderr
+            .take()
+            .and_then(|h| h.join().ok()?.ok())
+            .unwrap_or_default()
+    }
+
+    fn kill(&mut self) {
+        if self.exited {
+            return;
+        }
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+        if let Some(name) = &self.container {
+            remove_container(name);
+        }
+        self.exited = true;
+    }
+}
+
+impl Drop for Worker {
+    /// Abandoned workers (timeouts, failed groups) are killed before
+    /// their cgroup is removed.
+    fn drop(&mut self) {
+        self.kill();
+    }
+}
@@ -0,0 +1,158 @@
+//! Remote execution.
+
+use std::io::{BufRead, BufReader};
+use std::process::{Command, Stdio};
+
+use anyhow::{Context, Result, bail};
+use clap::parser::ValueSource;
+use clap::{ArgAction, ArgMatches, CommandFactory};
+use tracing::{debug, info, warn};
+
+use super::{Cli, Failure, FailureClass, RunArgs, output, resolve_scripts_dir};
+
+/// Options that refer to the local machine, by argument id; they are
+/// dropped from the forwarded command line.
+const LOCAL_OPTIONS: &[&str] = &[
+    "remote",
+    "remote_bench",
+    "output",
+    "scripts_dir",
+    "config",
+    "no_progress",
+];
+
+/// Quote `s` for the remote shell, which ssh hands the command to.
+fn quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', r"'\''"))
+}
+
+fn ssh(host: &str) -> Command {
+    let mut cmd = Command::new("ssh");
+    cmd.args(["-o", "BatchMode=yes", host]);
+    cmd
+}
+
+/// Run `command` on `host` and return its stdout.
+fn ssh_output(host: &str, command: &str) -> Result<String> {
+    let out = ssh(host)
+        .arg(command)
+        .stderr(Stdio::inherit())
+        .output()
+        .context("failed to run ssh")?;
+    if !out.status.success() {
+        bail!("ssh {host} {command:?} failed ({})", out.status);
+    }
+    Ok(String::from_utf8_lossy(&out.stdout).trim().to_string())
+}
+
+fn scp(from: &std::path::Path, host: &str, to: &str) -> Result<()> {
+    let status = Command::new("scp")
+        .args(["-rq", "-o", "BatchMode=yes"])
+        .arg(from)
+        .arg(format!("{host}:{to}"))
+        .status()
+        .context("failed to run scp")?;
+    if !status.success() {
+        bail!("scp {} to {host}:{to} failed ({status})", from.display());
+    }
+    Ok(())
+}
+
+/// The run options given on the command line, as parsed into
+/// `matches`, minus the local ones. Values are passed as `--name=value`,
+/// so that none is taken for an option.
+pub fn forwarded_args(matches: &ArgMatches) -> Vec<String> {
+    let mut command = Cli::command();
+    command.build();
+    let run = command.find_subcommand("run").expect("the run subcommand");
+    let mut forwarded = Vec::new();
+    for arg in run.get_arguments() {
+        let id = arg.get_id().as_str();
+        if LOCAL_OPTIONS.contains(&id) || matches.value_source(id) != Some(ValueSource::CommandLine)
+        {
+            continue;
+        }
+        let flag = format!("--{}", arg.get_long().unwrap_or(id));
+        match arg.get_action() {
+            ArgAction::SetTrue | ArgAction::SetFalse => forwarded.push(flag),
+            ArgAction::Count => {
+                forwarded.extend((0..matches.get_count(id)).map(|_| flag.clone()));
+            }
+            _ => {
+                let values = matches.get_raw(id).into_iter().flatten();
+                forwarded.extend(values.map(|v| format!("{flag}={}", v.to_string_lossy())));
+            }
+        }
+    }
+    forwarded
+}
+
+/// Copy the scripts (and --config) to a temporary directory on `host`,
+/// run the benchmark there and stream its records into --output.
+pub fn run(args: &RunArgs, host: &str) -> Result<()> {
+    let scripts_dir = resolve_scripts_dir(args.scripts_dir.clone());
+    if !scripts_dir.is_dir() {
+        bail!(
+            "scripts directory {} not found; pass --scripts-dir",
+            scripts_dir.display()
+        );
+    }
+    let dir = ssh_output(host, "mktemp -d")?;
+    if dir.is_empty() {
+        bail!("mktemp on {host} printed no directory");
+    }
+    debug!("remote working directory: {host}:{dir}");
+    let result = run_in(args, host, &dir, &scripts_dir);
+    if let Err(e) = ssh_output(host, &format!("rm -rf {}", quote(&dir))) {
+        warn!("cannot remove {host}:{dir}: {e:#}");
+    }
+    result
+}
+
+fn run_in(args: &RunArgs, host: &str, dir: &str, scripts_dir: &std::path::Path) -> Result<()> {
+    let remote_scripts = format!("{dir}/scripts");
+    scp(scripts_dir, host, &remote_scripts)?;
+    let mut command = vec![quote(&args.remote_bench), "run".to_string()];
+    command.extend(args.forwarded.iter().map(|a| quote(a)));
+    command.extend(["--scripts-dir".to_string(), quote(&remote_scripts)]);
+    if let Some(config) = &args.config {
+        let remote_config = format!("{dir}/suite.toml");
+        scp(config, host, &remote_config)?;
+        command.extend(["--config".to_string(), quote(&remote_config)]);
+    }
+    // Records come back on stdout, even if the config names an output file.
+    command.extend(["--output", "/dev/stdout", "--no-progress"].map(String::from));
+    let command = command.join(" ");
+
+    info!("running on {host}");
+    debug!("remote command: {command}");
+    if !args.dry_run {
+        output::init(args.output.as_deref(), false)?;
+    }
+    let mut child = ssh(host)
+        .arg(&command)
+        .stdout(Stdio::piped())
+        .spawn()
+        .context("failed to run ssh")?;
+    let stdout = child.stdout.take().expect("piped stdout");
+    for line in BufReader::new(stdout).lines() {
+        let line = line.context("failed to read from ssh")?;
+        // The dry-run plan is for the terminal, not the results file.
+        if args.dry_run {
+            println!("{line}");
+        } else {
+            output::emit_line(&line)?;
+        }
+    }
+    let status = child.wait()?;
+    output::flush()?;
+    if !status.success() {
+        let message = format!("benchmark on {host} failed ({status})");
+        // Keep the remote's --fail-on exit code.
+        if let Some(class) = status.code().and_then(FailureClass::from_exit_code) {
+            return Err(Failure { class, message }.into());
+        }
+        bail!(message);
+    }
+    Ok(())
+}
@@ -0,0 +1,124 @@
+//! Report subcommand.
+
+use anyhow::Result;
+use serde::Serialize;
+
+use super::ReportArgs;
+use super::results::{Instrumented, Medians, check_instrumented, load};
+
+/// Per-engine composite score for one problem size.
+#[derive(Serialize)]
+struct ScoreRecord {
+    kind: &'static str,
+    engine: String,
+    size: String,
+    n: u64,
+    /// Number of workloads in the geometric mean.
+    workloads: usize,
+    /// Geometric mean over workloads of the median eval_ns.
+    geomean_eval_ns: f64,
+    /// Engine the ratio is computed against.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    baseline_engine: Option<String>,
+    /// Geometric mean of per-workload median ratios (engine / baseline),
+    /// over the workloads both engines ran. > 1 means slower.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ratio: Option<f64>,
+    /// Number of workloads in the ratio (those both engines ran).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ratio_workloads: Option<usize>,
+}
+
+fn geomean(xs: impl IntoIterator<Item = f64>) -> f64 {
+    let (sum, count) = xs
+        .into_iter()
+        .fold((0.0, 0usize), |(s, c), x| (s + x.ln(), c + 1));
+    if count == 0 {
+        return 0.0;
+    }
+    (sum / count as f64).exp()
+}
+
+fn scores(
+    args: &ReportArgs,
+    medians: &Medians,
+    instrumented: &Instrumented,
+) -> Result<Vec<ScoreRecord>> {
+    let mut out = Vec::new();
+    for (size, engines) in medians {
+        let baseline = engines.get(&args.baseline_engine);
+        for (engine, workloads) in engines {
+            let mut ratios = Vec::new();
+            if let Some(base) = baseline.filter(|_| *engine != args.baseline_engine) {
+                for (workload, &m) in workloads {
+                    let Some(&b) = base.get(workload) else {
+                        continue;
+                    };
+                    check_instrumented(
+                        instrumented,
+                        size,
+                        (engine, &args.baseline_engine),
+                        workload,
+                    )?;
+                    ratios.push(m / b);
+                }
+            }
+            let ratio = (!ratios.is_empty()).then(|| geomean(ratios.iter().copied()));
+            out.push(ScoreRecord {
+                kind: "score",
+                engine: engine.clone(),
+                size: size.size.clone(),
+                n: size.n,
+                workloads: workloads.len(),
+                geomean_eval_ns: geomean(workloads.values().copied()),
+                baseline_engine: ratio.map(|_| args.baseline_engine.clone()),
+                ratio,
+                ratio_workloads: ratio.map(|_| ratios.len()),
+            });
+        }
+    }
+    Ok(out)
+}
+
+pub fn run(args: &ReportArgs) -> Result<()> {
+    let (medians, instrumented) = load(&args.files)?;
+    let scores = scores(args, &medians, &instrumented)?;
+
+    if args.json {
+        for s in &scores {
+            println!("{}", serde_json::to_string(s)?);
+        }
+        return Ok(());
+    }
+
+    println!(
+        "{:<4} {:>8}  {:<16} {:>9}  {:>14}",
+        "size", "n", "engine", "workloads", "geomean (ms)"
+    );
+    for s in &scores {
+        println!(
+            "{:<4} {:>8}  {:<16} {:>9}  {:>14.3}",
+            s.size,
+            s.n,
+            s.engine,
+            s.workloads,
+            s.geomean_eval_ns / 1e6
+        );
+    }
+    println!();
+    for s in &scores {
+        let (Some(ratio), Some(count)) = (s.ratio, s.ratio_workloads) else {
+            continue;
+        };
+        let (factor, dir) = if ratio >= 1.0 {
+            (ratio, "slower")
+        } else {
+            (1.0 / ratio, "faster")
+        };
+        println!(
+            "size {} (n={}): {} is {factor:.2}x {dir} than {} (geometric mean over {} workloads)",
+            s.size, s.n, s.engine, args.baseline_engine, count
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,140 @@
+//! Reading results files.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::BufRead;
+use std::path::PathBuf;
+
+use anyhow::{Context, Result, bail};
+use serde_json::Value;
+
+use super::stats;
+
+/// (size, n) — ordered by n so S < M < L.
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct SizeKey {
+    pub n: u64,
+    pub size: String,
+}
+
+/// Median measurement eval_ns per size → engine → workload.
+pub type Medians = BTreeMap<SizeKey, BTreeMap<String, BTreeMap<String, f64>>>;
+
+/// (size, engine, workload) of the groups whose Starlark records were
+/// measured with --timeout's statement hook.
+pub type Instrumented = BTreeSet<(SizeKey, String, String)>;
+
+/// Read iteration records from `files` (or stdin) and compute per-group medians.
+pub fn load_medians(files: &[PathBuf]) -> Result<Medians> {
+    Ok(load(files)?.0)
+}
+
+/// As `load_medians`, also telling which groups ran with --timeout.
+/// Fails if a group pools runs with and without it.
+pub fn load(files: &[PathBuf]) -> Result<(Medians, Instrumented)> {
+    // Keyed per run and seed so that only the last re-measurement attempt
+    // (--max-stddev-pct) of each group is kept.
+    type GroupKey = (SizeKey, String, String, String, u64);
+    let mut groups: BTreeMap<GroupKey, (u64, Vec<u64>, bool)> = BTreeMap::new();
+    let mut add_line = |line: &str| {
+        let Ok(rec) = serde_json::from_str::<Value>(line) else {
+            return;
+        };
+        // Only per-iteration measurement records: no `kind`, not warmup.
+        if rec.get("kind").is_some() || rec["warmup"].as_bool().unwrap_or(false) {
+            return;
+        }
+        let (Some(engine), Some(workload), Some(size), Some(n), Some(eval_ns)) = (
+            rec["engine"].as_str(),
+            rec["workload"].as_str(),
+            rec["size"].as_str(),
+            rec["n"].as_u64(),
+            rec["eval_ns"].as_u64(),
+        ) else {
+            return;
+        };
+        let key = (
+            SizeKey {
+                n,
+                size: size.to_string(),
+            },
+            engine.to_string(),
+            workload.to_string(),
+            rec["run_id"].as_str().unwrap_or_default().to_string(),
+            rec["seed"].as_u64().unwrap_or(0),
+        );
+        let attempt = rec["attempt"].as_u64().unwrap_or(0);
+        let (kept, samples, instrumented) = groups.entry(key).or_default();
+        if attempt > *kept {
+            *kept = attempt;
+            samples.clear();
+        }
+        if attempt == *kept {
+            samples.push(eval_ns);
+            *instrumented = rec.get("timeout_secs").is_some();
+        }
+    };
+
+    if files.is_empty() {
+        for line in std::io::stdin().lock().lines() {
+            add_line(&line.context("failed to read stdin")?);
+        }
+    } else {
+        for path in files {
+            let text = std::fs::read_to_string(path)
+                .with_context(|| format!("cannot read {}", path.display()))?;
+            text.lines().for_each(&mut add_line);
+        }
+    }
+
+    // Samples, and whether each run pooled into them had --timeout.
+    type Pool = (Vec<u64>, BTreeSet<bool>);
+    let mut pooled: BTreeMap<(SizeKey, String, String), Pool> = BTreeMap::new();
+    for ((size, engine, workload, _, _), (_, samples, instrumented)) in groups {
+        let (all, kinds) = pooled.entry((size, engine, workload)).or_default();
+        all.extend(samples);
+        kinds.insert(instrumented);
+    }
+
+    let mut medians = Medians::new();
+    let mut timed_out = Instrumented::new();
+    for (key, (samples, kinds)) in pooled {
+        let (size, engine, workload) = &key;
+        if kinds.len() > 1 {
+            bail!(
+                "{engine}/{workload}/{} pools runs with and without --timeout, whose \
+                 statement hook slows Starlark; keep them in separate files",
+                size.size
+            );
+        }
+        medians
+            .entry(size.clone())
+            .or_default()
+            .entry(engine.clone())
+            .or_default()
+            .insert(workload.clone(), stats::median(&samples));
+        if kinds.contains(&true) {
+            timed_out.insert(key);
+        }
+    }
+    Ok((medians, timed_out))
+}
+
+/// Fail if exactly one of two groups put side by side ran with --timeout.
+pub fn check_instrumented(
+    instrumented: &Instrumented,
+    size: &SizeKey,
+    (engine, base_engine): (&str, &str),
+    workload: &str,
+) -> Result<()> {
+    let timed = |engine: &str| {
+        instrumented.contains(&(size.clone(), engine.to_string(), workload.to_string()))
+    };
+    if timed(engine) != timed(base_engine) {
+        bail!(
+            "{workload}/{}: only one of {engine} and {base_engine} was measured with \
+             --timeout, whose statement hook slows Starlark; compare runs without it",
+            size.size
+        );
+    }
+    Ok(())
+}
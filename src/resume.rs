@@ -0,0 +1,95 @@
+//! Resume.
+
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+use anyhow::{Context, Result, bail};
+use serde_json::Value;
+
+use super::{Size, SummaryRecord};
+
+/// (engine, workload, size, n, seed) of one measurement group.
+pub type GroupKey = (String, String, String, usize, u64);
+
+pub fn group_key(engine: &str, stem: &str, size: &Size, n: usize, seed: u64) -> GroupKey {
+    (
+        engine.to_string(),
+        stem.to_string(),
+        size.to_string(),
+        n,
+        seed,
+    )
+}
+
+/// What an interrupted run left in its output file.
+pub struct Resumed {
+    /// Groups with an accepted summary or a timeout record.
+    pub done: HashSet<GroupKey>,
+    /// Unfinished groups: the attempt number after the last one recorded.
+    pub attempts: HashMap<GroupKey, u32>,
+    /// Accepted summaries, for the ratios of comparisons still running.
+    pub summaries: Vec<SummaryRecord>,
+}
+
+/// Read the records of run `run_id` from `path`. Lines that do not parse,
+/// such as one cut off by the interruption, are ignored.
+pub fn load(path: &Path, run_id: &str) -> Result<Resumed> {
+    let text =
+        std::fs::read_to_string(path).with_context(|| format!("cannot read {}", path.display()))?;
+    let mut resumed = Resumed {
+        done: HashSet::new(),
+        attempts: HashMap::new(),
+        summaries: Vec::new(),
+    };
+    let mut found = false;
+    for line in text.lines() {
+        let Ok(rec) = serde_json::from_str::<Value>(line) else {
+            continue;
+        };
+        if rec["run_id"].as_str() != Some(run_id) {
+            continue;
+        }
+        found = true;
+        let (Some(engine), Some(workload), Some(size), Some(n)) = (
+            rec["engine"].as_str(),
+            rec["workload"].as_str(),
+            rec["size"].as_str(),
+            rec["n"].as_u64(),
+        ) else {
+            continue;
+        };
+        let key = (
+            engine.to_string(),
+            workload.to_string(),
+            size.to_string(),
+            n as usize,
+            rec["seed"].as_u64().unwrap_or(0),
+        );
+        let rejected = rec["rejected"].as_bool().unwrap_or(false);
+        match rec["kind"].as_str() {
+            // Iteration records and rejected summaries leave the group open.
+            None => {}
+            Some("summary") if rejected => {}
+            Some("summary") => {
+                resumed.done.insert(key);
+                resumed.summaries.push(
+                    serde_json::from_value(rec)
+                        .with_context(|| format!("malformed summary in {}", path.display()))?,
+                );
+                continue;
+            }
+            Some("timeout") => {
+                resumed.done.insert(key);
+                continue;
+            }
+            Some(_) => continue,
+        }
+        let attempt = rec["attempt"].as_u64().unwrap_or(0) as u32;
+        let next = resumed.attempts.entry(key).or_default();
+        *next = (*next).max(attempt + 1);
+    }
+    if !found {
+        bail!("no records of run {run_id} in {}", path.display());
+    }
+    Ok(resumed)
+}
@@ -0,0 +1,548 @@
+//! The library API: [`Runner`] and its settings.
+
+use std::ops::ControlFlow;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex};
+
+use anyhow::{Context, Result, bail};
+use clap::{CommandFactory, FromArgMatches, ValueEnum};
+
+use super::{
+    BUNDLED_SCRIPTS, BenchRecord, Cli, Commands, Engine, EngineName, Selection, Size, WorkloadName,
+    check_conflicts, complete_run_args, output, resume, run,
+};
+
+/// An engine for [`Runner::engine`].
+#[derive(Clone)]
+pub enum EngineConfig {
+    /// The starlark-rust evaluator built into the bench.
+    Starlark,
+    /// A Python interpreter, by name on PATH or by path (`--python`).
+    Python { binary: String },
+}
+
+impl EngineConfig {
+    pub fn python(binary: impl Into<String>) -> EngineConfig {
+        EngineConfig::Python {
+            binary: binary.into(),
+        }
+    }
+}
+
+/// A workload for [`Runner::workload`].
+#[derive(Clone)]
+pub struct WorkloadSpec {
+    name: WorkloadName,
+}
+
+impl WorkloadSpec {
+    /// The bundled workload called `name`, as --workload spells it, e.g.
+    /// "json_building".
+    pub fn builtin(name: &str) -> Result<WorkloadSpec> {
+        let name = WorkloadName::from_str(name, false).map_err(anyhow::Error::msg)?;
+        Ok(WorkloadSpec { name })
+    }
+
+    /// Every bundled workload.
+    pub fn all() -> Vec<WorkloadSpec> {
+        WorkloadName::value_variants()
+            .iter()
+            .map(|name| WorkloadSpec { name: name.clone() })
+            .collect()
+    }
+
+    /// The name the records carry, which is also the stem of its scripts.
+    pub fn name(&self) -> &'static str {
+        self.name.file_stem()
+    }
+}
+
+/// A script of a [`CustomWorkload`]: a file, or the text of one.
+#[derive(Clone)]
+pub enum Script {
+    Path(PathBuf),
+    Inline(String),
+}
+
+/// The checksum a custom workload's `run(n, seed)` should return.
+pub(crate) type ExpectedResult = dyn Fn(usize, u64) -> i64 + Send + Sync;
+
+/// A workload of the caller's own, for [`Runner::register_workload`]. Its
+/// scripts follow the bundled ones: they define `run(n, seed)`, returning a
+/// checksum, and a Python script hands it to `bench_main` from `_harness`,
+/// which registered scripts can import wherever they are. Inline Starlark
+/// cannot `load()` anything.
+#[derive(Clone, Default)]
+pub struct CustomWorkload {
+    starlark: Option<Script>,
+    python: Option<Script>,
+    expected: Option<Arc<ExpectedResult>>,
+}
+
+impl CustomWorkload {
+    pub fn new() -> CustomWorkload {
+        CustomWorkload::default()
+    }
+
+    /// The script --engine starlark runs.
+    pub fn starlark(mut self, script: Script) -> CustomWorkload {
+        self.starlark = Some(script);
+        self
+    }
+
+    /// The script --engine python runs.
+    pub fn python(mut self, script: Script) -> CustomWorkload {
+        self.python = Some(script);
+        self
+    }
+
+    /// What `run(n, seed)` has to return. Every engine's result is checked
+    /// against it instead of against each other, and a mismatch is reported
+    /// like theirs, with "expected" as the baseline engine.
+    pub fn expected(
+        mut self,
+        checksum: impl Fn(usize, u64) -> i64 + Send + Sync + 'static,
+    ) -> CustomWorkload {
+        self.expected = Some(Arc::new(checksum));
+        self
+    }
+
+    /// Ready to run as `name`, writing any inline script to `scratch`.
+    fn register(&self, name: &str, scratch: &mut Option<ScratchDir>) -> Result<RegisteredWorkload> {
+        let mut write = |script: &Option<Script>, dir: &str, extension: &str| -> Result<_> {
+            let body = match script {
+                None => return Ok(None),
+                Some(Script::Path(path)) => return Ok(Some(path.clone())),
+                Some(Script::Inline(body)) => body,
+            };
+            let scratch = match scratch {
+                Some(scratch) => scratch,
+                None => scratch.insert(ScratchDir::new()?),
+            };
+            let dir = scratch.0.join(dir);
+            std::fs::create_dir_all(&dir)
+                .with_context(|| format!("cannot create {}", dir.display()))?;
+            let path = dir.join(format!("{name}.{extension}"));
+            std::fs::write(&path, body)
+                .with_context(|| format!("cannot write {}", path.display()))?;
+            Ok(Some(path))
+        };
+        Ok(RegisteredWorkload {
+            // Workloads are named by `&'static str` throughout a run; this
+            // leaks a few bytes per registered workload and run.
+            name: name.to_string().leak(),
+            starlark: write(&self.starlark, "starlark", "star")?,
+            python: write(&self.python, "python", "py")?,
+            expected: self.expected.clone(),
+        })
+    }
+}
+
+/// A [`CustomWorkload`] as a run sees it: named, with its scripts on disk.
+pub(crate) struct RegisteredWorkload {
+    pub(crate) name: &'static str,
+    pub(crate) starlark: Option<PathBuf>,
+    pub(crate) python: Option<PathBuf>,
+    pub(crate) expected: Option<Arc<ExpectedResult>>,
+}
+
+impl RegisteredWorkload {
+    /// Its script for `dir` ("starlark" or "python"), if it has one.
+    pub(crate) fn script(&self, dir: &str) -> Option<PathBuf> {
+        match dir {
+            "starlark" => self.starlark.clone(),
+            "python" => self.python.clone(),
+            _ => None,
+        }
+    }
+}
+
+/// A temporary directory for the inline scripts of a run, removed with it.
+pub(crate) struct ScratchDir(pub(crate) PathBuf);
+
+impl ScratchDir {
+    pub(crate) fn new() -> Result<ScratchDir> {
+        static DIRS: AtomicUsize = AtomicUsize::new(0);
+        let path = std::env::temp_dir().join(format!(
+            "starlark-bench-{}-workloads-{}",
+            std::process::id(),
+            DIRS.fetch_add(1, Ordering::Relaxed)
+        ));
+        std::fs::create_dir_all(&path)
+            .with_context(|| format!("cannot create {}", path.display()))?;
+        Ok(ScratchDir(path))
+    }
+}
+
+impl Drop for ScratchDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+/// A function of [`Runner::on_record`].
+type RecordCallback = dyn FnMut(&BenchRecord) -> ControlFlow<()> + Send;
+
+/// Which groups to measure, by their `resume::GroupKey`.
+pub(crate) type GroupFilter = dyn Fn(&resume::GroupKey) -> bool + Send + Sync;
+
+/// An [`Engine`] of a [`Runner`], with the name its records are labeled
+/// with.
+pub(crate) struct RegisteredEngine {
+    pub(crate) name: String,
+    pub(crate) engine: Mutex<Box<dyn Engine>>,
+}
+
+/// Runs benchmarks from Rust: `bench run` with the flags the builder methods
+/// stand for, returning the iteration records instead of writing them out.
+/// As with `bench run`, engines and workloads have to be chosen, here or by
+/// a --config given to [`Runner::args`]; everything else defaults as there.
+///
+/// ```no_run
+/// use starlark_bench::{EngineConfig, Runner, Size, WorkloadSpec};
+///
+/// let records = Runner::new()
+///     .engine(EngineConfig::Starlark)
+///     .engine(EngineConfig::python("python3.12"))
+///     .workload(WorkloadSpec::builtin("arithmetic")?)
+///     .size(Size::S)
+///     .iters(5)
+///     .run()?;
+/// for r in records.iter().filter(|r| !r.warmup()) {
+///     println!("{} {} {}", r.engine(), r.workload(), r.eval_ns());
+/// }
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+#[derive(Clone, Default)]
+pub struct Runner {
+    engines: Vec<EngineConfig>,
+    custom_engines: Vec<Arc<RegisteredEngine>>,
+    workloads: Vec<WorkloadSpec>,
+    custom_workloads: Vec<(String, CustomWorkload)>,
+    on_record: Option<Arc<Mutex<RecordCallback>>>,
+    only: Option<Arc<GroupFilter>>,
+    /// Set by [`Runner::once`].
+    once: bool,
+    sizes: Vec<Size>,
+    n: Vec<usize>,
+    iters: Option<u32>,
+    warmup: Option<u32>,
+    seed: Option<u64>,
+    scripts_dir: Option<PathBuf>,
+    args: Vec<String>,
+}
+
+impl Runner {
+    pub fn new() -> Runner {
+        Runner::default()
+    }
+
+    /// Add an engine (--engine, and --python for each Python one).
+    pub fn engine(mut self, engine: EngineConfig) -> Runner {
+        self.engines.push(engine);
+        self
+    }
+
+    /// Add an engine of the caller's own, labeled in the records with the
+    /// name from its [`Engine::metadata`].
+    pub fn custom_engine(mut self, engine: impl Engine + 'static) -> Runner {
+        self.custom_engines.push(Arc::new(RegisteredEngine {
+            name: engine.metadata().name,
+            engine: Mutex::new(Box::new(engine)),
+        }));
+        self
+    }
+
+    /// Add a workload (--workload).
+    pub fn workload(mut self, workload: WorkloadSpec) -> Runner {
+        self.workloads.push(workload);
+        self
+    }
+
+    /// Add a workload of the caller's own, run like the ones from
+    /// [`Runner::workload`] and recorded as `name`, which has to differ
+    /// from the bundled workloads' and may hold letters, digits, '_' and
+    /// '-'.
+    pub fn register_workload(mut self, name: impl Into<String>, spec: CustomWorkload) -> Runner {
+        self.custom_workloads.push((name.into(), spec));
+        self
+    }
+
+    /// Add a size preset (--size).
+    pub fn size(mut self, size: Size) -> Runner {
+        self.sizes.push(size);
+        self
+    }
+
+    /// Add an N, which overrides the size presets (--n).
+    pub fn n(mut self, n: usize) -> Runner {
+        self.n.push(n);
+        self
+    }
+
+    /// Measurement iterations per group (--iters).
+    pub fn iters(mut self, iters: u32) -> Runner {
+        self.iters = Some(iters);
+        self
+    }
+
+    /// Warmup iterations per group (--warmup).
+    pub fn warmup(mut self, warmup: u32) -> Runner {
+        self.warmup = Some(warmup);
+        self
+    }
+
+    /// The workloads' RNG seed (--seed).
+    pub fn seed(mut self, seed: u64) -> Runner {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// Where the workload scripts are (--scripts-dir); by default the
+    /// `scripts/` directory of this crate's source, wherever the caller runs.
+    pub fn scripts_dir(mut self, dir: impl Into<PathBuf>) -> Runner {
+        self.scripts_dir = Some(dir.into());
+        self
+    }
+
+    /// Any other `bench run` flags, e.g. `["--clock", "thread"]` or
+    /// `["--config", "suite.toml"]`. The builder's own settings win over
+    /// both these and the config file.
+    pub fn args<I, S>(mut self, args: I) -> Runner
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.args.extend(args.into_iter().map(Into::into));
+        self
+    }
+
+    /// Call `f` with every iteration record as it is written: those of a
+    /// group together once it stops measuring (see "Record buffering" in
+    /// README.md), before its summary. Returning `ControlFlow::Break`
+    /// stops the run there, as a success.
+    pub fn on_record(
+        mut self,
+        f: impl FnMut(&BenchRecord) -> ControlFlow<()> + Send + 'static,
+    ) -> Runner {
+        self.on_record = Some(Arc::new(Mutex::new(f)));
+        self
+    }
+
+    /// Measure every group and return the iteration records, warmups
+    /// included, in the order they were taken. Summary and ratio records
+    /// are not returned. Fails as `bench run` would, including on a
+    /// --fail-on failure.
+    pub fn run(&self) -> Result<Vec<BenchRecord>> {
+        let records = Arc::new(Mutex::new(Vec::new()));
+        let kept = Arc::clone(&records);
+        self.run_to(move |record| {
+            kept.lock().expect("records lock poisoned").push(record);
+            ControlFlow::Continue(())
+        })?;
+        Ok(std::mem::take(
+            &mut *records.lock().expect("records lock poisoned"),
+        ))
+    }
+
+    /// Run on a thread of its own, yielding the iteration records as
+    /// [`Runner::on_record`] sees them; a failed run ends with its error.
+    /// Dropping the iterator stops the run once the group it is measuring
+    /// finishes.
+    pub fn stream(&self) -> RecordStream {
+        let (sender, receiver) = mpsc::channel();
+        let runner = self.clone();
+        std::thread::spawn(move || {
+            let records = sender.clone();
+            let result = runner.run_to(move |record| match records.send(Ok(record)) {
+                Ok(()) => ControlFlow::Continue(()),
+                Err(_) => ControlFlow::Break(()),
+            });
+            if let Err(e) = result {
+                let _ = sender.send(Err(e));
+            }
+        });
+        RecordStream { receiver }
+    }
+
+    /// Run, handing every iteration record to [`Runner::on_record`]'s
+    /// function and then `deliver`, until either breaks.
+    fn run_to(
+        &self,
+        mut deliver: impl FnMut(BenchRecord) -> ControlFlow<()> + Send + 'static,
+    ) -> Result<()> {
+        let argv = ["bench", "run"]
+            .into_iter()
+            .map(String::from)
+            .chain(self.args.iter().cloned());
+        let matches = Cli::command().try_get_matches_from(argv)?;
+        let Some(Commands::Run(args)) = Cli::from_arg_matches(&matches)?.command else {
+            unreachable!("parsed as `bench run`");
+        };
+        let mut args = *args;
+        let run_matches = matches
+            .subcommand_matches("run")
+            .expect("parsed as `bench run`");
+        let mut set = complete_run_args(&mut args, run_matches)?;
+        if args.remote.is_some() {
+            bail!("--remote does not apply to a Runner, which measures on this machine");
+        }
+        if args.output.is_some() || args.resume.is_some() {
+            bail!(
+                "--output and --resume do not apply to a Runner, which returns the records \
+                 instead of writing them"
+            );
+        }
+        // These would outlast the run, in the caller's thread and process.
+        if args.pin_cpu.is_some() || args.nice.is_some() || args.realtime.is_some() {
+            bail!(
+                "--pin-cpu, --nice and --realtime would change the calling process; \
+                 set them on it before running the Runner instead"
+            );
+        }
+
+        if !self.engines.is_empty() || !self.custom_engines.is_empty() {
+            args.engine = self
+                .engines
+                .iter()
+                .map(|e| match e {
+                    EngineConfig::Starlark => Selection::One(EngineName::Starlark),
+                    EngineConfig::Python { .. } => Selection::One(EngineName::Python),
+                })
+                .collect();
+            let pythons: Vec<String> = self
+                .engines
+                .iter()
+                .filter_map(|e| match e {
+                    EngineConfig::Python { binary } => Some(binary.clone()),
+                    EngineConfig::Starlark => None,
+                })
+                .collect();
+            if !pythons.is_empty() {
+                args.python = pythons;
+                set.push(("python", "Runner::engine"));
+            }
+            set.push(("engine", "Runner::engine"));
+        }
+        if !self.workloads.is_empty() || !self.custom_workloads.is_empty() {
+            args.workload = self
+                .workloads
+                .iter()
+                .map(|w| Selection::One(w.name.clone()))
+                .collect();
+            set.push(("workload", "Runner::workload"));
+        }
+        if !self.sizes.is_empty() {
+            args.size = self.sizes.iter().cloned().map(Selection::One).collect();
+            set.push(("size", "Runner::size"));
+        }
+        if !self.n.is_empty() {
+            args.n = self.n.clone();
+            set.push(("n", "Runner::n"));
+        }
+        if let Some(iters) = self.iters {
+            args.iters = iters;
+            set.push(("iters", "Runner::iters"));
+        }
+        if let Some(warmup) = self.warmup {
+            args.warmup = warmup;
+            set.push(("warmup", "Runner::warmup"));
+        }
+        if let Some(seed) = self.seed {
+            args.seed = seed;
+            set.push(("seed", "Runner::seed"));
+        }
+        // clap checked only `args`; the setters may conflict with them.
+        check_conflicts(run_matches, &set)?;
+        if self.once {
+            args.iters = 1;
+            args.warmup = 0;
+            args.target_ci = None;
+            args.min_time = None;
+            args.auto_warmup = false;
+            args.jobs = 1;
+        }
+        if let Some(dir) = &self.scripts_dir {
+            args.scripts_dir = Some(dir.clone());
+        } else if args.scripts_dir.is_none() {
+            args.scripts_dir = Some(PathBuf::from(BUNDLED_SCRIPTS));
+        }
+        args.custom_engines = self.custom_engines.clone();
+        let mut scratch = None;
+        args.custom_workloads = self
+            .custom_workloads
+            .iter()
+            .map(|(name, spec)| spec.register(name, &mut scratch))
+            .collect::<Result<_>>()?;
+        args.no_progress = true;
+        args.library = true;
+        args.only = self.only.clone();
+
+        // The records go through one process-wide sink.
+        static RUNNING: Mutex<()> = Mutex::new(());
+        let _running = RUNNING.lock().unwrap_or_else(|e| e.into_inner());
+        let on_record = self.on_record.clone();
+        output::init_records(move |record| {
+            let mut flow = ControlFlow::Continue(());
+            if let Some(f) = &on_record {
+                flow = (f.lock().expect("record callback lock poisoned"))(&record);
+            }
+            if flow.is_continue() {
+                flow = deliver(record);
+            }
+            match flow {
+                ControlFlow::Continue(()) => Ok(()),
+                ControlFlow::Break(()) => Err(Stopped.into()),
+            }
+        });
+        let result = run(&args);
+        output::reset();
+        match result {
+            Err(e) if e.is::<Stopped>() => Ok(()),
+            result => result,
+        }
+    }
+
+    /// Measure only the groups `f` returns true for.
+    pub(crate) fn only(
+        mut self,
+        f: impl Fn(&resume::GroupKey) -> bool + Send + Sync + 'static,
+    ) -> Runner {
+        self.only = Some(Arc::new(f));
+        self
+    }
+
+    /// Run every group once, as a test: one iteration without warmup, one
+    /// group at a time, whatever the setters and flags ask for.
+    pub(crate) fn once(mut self) -> Runner {
+        self.once = true;
+        self
+    }
+}
+
+/// The iteration records of a [`Runner::stream`] run.
+pub struct RecordStream {
+    receiver: mpsc::Receiver<Result<BenchRecord>>,
+}
+
+impl Iterator for RecordStream {
+    type Item = Result<BenchRecord>;
+
+    fn next(&mut self) -> Option<Result<BenchRecord>> {
+        self.receiver.recv().ok()
+    }
+}
+
+/// Raised through the output when a record callback stops the run.
+#[derive(Debug)]
+struct Stopped;
+
+impl std::fmt::Display for Stopped {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("stopped by a record callback")
+    }
+}
+
+impl std::error::Error for Stopped {}
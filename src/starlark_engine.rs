@@ -0,0 +1,1314 @@
+//! Starlark engine.
+
+use std::cell::{Cell, RefCell};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Component, Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result, anyhow, bail};
+use starlark::ErrorKind;
+use starlark::codemap::FileSpanRef;
+use starlark::environment::{FrozenModule, Globals, GlobalsBuilder, LibraryExtension, Module};
+use starlark::eval::{
+    BeforeStmtFuncDyn, Evaluator, FileLoader, ProfileData, ProfileMode, ReturnFileLoader,
+};
+use starlark::starlark_module;
+use starlark::syntax::{AstModule, Dialect, DialectTypes};
+use starlark::typing::AstModuleTypecheck;
+use starlark::values::float::StarlarkFloat;
+use starlark::values::list::ListRef;
+use starlark::values::tuple::TupleRef;
+use starlark::values::{Heap, OwnedFrozenValue, Value, ValueLike};
+use tracing::warn;
+
+use super::{
+    DialectFeature, DialectName, DialectToggle, EvalOption, Param, ProfileKind, Rusage,
+    StarlarkLib, TimedOut, alloc_count,
+};
+
+/// starlark-rust's call-stack limit, in frames, which it does not export.
+pub const DEFAULT_MAX_CALLSTACK: u32 = 50;
+
+/// The language scripts are parsed and evaluated in (--dialect): syntax
+/// features, plus the `set` type, which starlark-rust provides as a
+/// library extension rather than a dialect flag, and the settings of
+/// the evaluators that run them: the call-stack limit
+/// (--starlark-max-callstack) and any --eval-config options.
+#[derive(Clone)]
+pub struct Language {
+    dialect: Dialect,
+    sets: bool,
+    max_callstack: Option<u32>,
+    /// --starlark-lib.
+    libraries: Vec<LibraryExtension>,
+    eval_options: Vec<EvalOption>,
+}
+
+impl Language {
+    pub fn new(base: DialectName, toggles: &[DialectToggle]) -> Self {
+        let dialect = match base {
+            DialectName::Standard => Dialect::Standard,
+            DialectName::Extended => Dialect::Extended,
+            DialectName::Custom => Dialect {
+                enable_def: false,
+                enable_lambda: false,
+                enable_load: false,
+                enable_keyword_only_arguments: false,
+                enable_positional_only_arguments: false,
+                enable_types: DialectTypes::Disable,
+                enable_load_reexport: false,
+                enable_top_level_stmt: false,
+                enable_f_strings: false,
+                ..Dialect::Standard
+            },
+        };
+        let mut language = Language {
+            dialect,
+            sets: false,
+            max_callstack: None,
+            libraries: Vec::new(),
+            eval_options: Vec::new(),
+        };
+        for t in toggles {
+            let d = &mut language.dialect;
+            match t.feature {
+                DialectFeature::Def => d.enable_def = t.enabled,
+                DialectFeature::Lambda => d.enable_lambda = t.enabled,
+                DialectFeature::Load => {
+                    d.enable_load = t.enabled;
+                    d.enable_load_reexport = t.enabled;
+                }
+                DialectFeature::KeywordOnlyArgs => d.enable_keyword_only_arguments = t.enabled,
+                DialectFeature::PositionalOnlyArgs => {
+                    d.enable_positional_only_arguments = t.enabled
+                }
+                DialectFeature::Types => {
+                    d.enable_types = if t.enabled {
+                        DialectTypes::Enable
+                    } else {
+                        DialectTypes::Disable
+                    }
+                }
+                DialectFeature::TopLevelStmt => d.enable_top_level_stmt = t.enabled,
+                DialectFeature::FStrings => d.enable_f_strings = t.enabled,
+                DialectFeature::Sets => language.sets = t.enabled,
+            }
+        }
+        language
+    }
+
+    pub fn with_max_callstack(self, frames: Option<u32>) -> Self {
+        Language {
+            max_callstack: frames,
+            ..self
+        }
+    }
+
+    pub fn with_libraries(self, libs: &[StarlarkLib]) -> Self {
+        let libraries = libs
+            .iter()
+            .map(|lib| match lib {
+                StarlarkLib::Json => LibraryExtension::Json,
+                StarlarkLib::Struct => LibraryExtension::StructType,
+                StarlarkLib::Record => LibraryExtension::RecordType,
+                StarlarkLib::Enum => LibraryExtension::EnumType,
+                StarlarkLib::Namespace => LibraryExtension::NamespaceType,
+                StarlarkLib::Map => LibraryExtension::Map,
+                StarlarkLib::Filter => LibraryExtension::Filter,
+                StarlarkLib::Partial => LibraryExtension::Partial,
+                StarlarkLib::Typing => LibraryExtension::Typing,
+                StarlarkLib::Print => LibraryExtension::Print,
+            })
+            .collect();
+        Language { libraries, ..self }
+    }
+
+    pub fn with_eval_options(self, options: &[EvalOption]) -> Self {
+        Language {
+            eval_options: options.to_vec(),
+            ..self
+        }
+    }
+
+    fn globals(&self) -> Globals {
+        let mut extensions = self.libraries.clone();
+        if self.sets {
+            extensions.push(LibraryExtension::SetType);
+        }
+        GlobalsBuilder::extended_by(&extensions)
+            .with(natives)
+            .build()
+    }
+}
+
+/// Rust functions available to every script, next to the standard
+/// library; `ffi_calls` measures the cost of calling them.
+#[starlark_module]
+fn natives(builder: &mut GlobalsBuilder) {
+    /// `base ** exp % modulus`, like Python's three-argument `pow`.
+    fn pow_mod(base: i32, exp: i32, modulus: i32) -> anyhow::Result<i32> {
+        if exp < 0 || modulus <= 0 {
+            return Err(anyhow!("pow_mod() needs exp >= 0 and modulus > 0"));
+        }
+        let m = i64::from(modulus);
+        let (mut b, mut e, mut r) = (i64::from(base).rem_euclid(m), exp, 1 % m);
+        while e > 0 {
+            if e & 1 == 1 {
+                r = r * b % m;
+            }
+            b = b * b % m;
+            e >>= 1;
+        }
+        Ok(r as i32)
+    }
+}
+
+/// Setup costs of a script, in nanoseconds.
+#[derive(Clone, Copy)]
+pub struct PrepareTimes {
+    pub parse_ns: u64,
+    /// Evaluating the module's top level, which defines `run`.
+    pub module_eval_ns: u64,
+    pub freeze_ns: u64,
+    /// With --typecheck, time spent in the static typechecker, and the
+    /// number of type errors it reported.
+    pub typecheck_ns: Option<u64>,
+    pub typecheck_errors: Option<u64>,
+    /// For scripts that call load(): time spent parsing, evaluating and
+    /// freezing the loaded modules (not included in `module_eval_ns`),
+    /// and how many distinct modules were loaded.
+    pub load_ns: Option<u64>,
+    pub loaded_modules: Option<u64>,
+}
+
+pub struct PreparedScript {
+    pub times: PrepareTimes,
+    frozen: FrozenModule,
+    run_fn: OwnedFrozenValue,
+    /// `run_parallel`, for workloads that split their work over threads.
+    run_parallel_fn: Option<OwnedFrozenValue>,
+    /// Per-call time limit; the bytecode is instrumented when set.
+    timeout: Option<Duration>,
+    globals: Globals,
+    /// The empty top-level statement --starlark-gc evaluates.
+    gc_stmt: AstModule,
+    /// The script, its language and the modules it loaded, for --phase;
+    /// with `load_dir`, everything --starlark-cold recompiles it from.
+    source: String,
+    language: Language,
+    load_dir: PathBuf,
+    loaded: HashMap<String, FrozenModule>,
+    /// Frozen heap size of a module holding only `result = None`;
+    /// subtracted from --heap-profile's retained bytes.
+    retained_baseline: u64,
+    /// With --profile, a second copy compiled with statement hooks for
+    /// the profiled call, so the measured copy stays uninstrumented.
+    profiled: Option<Box<PreparedScript>>,
+}
+
+/// Resolves `load()` paths relative to the scripts directory. Every module
+/// is evaluated once per script, in the script's language, and may load
+/// further modules through the same loader.
+struct ScriptLoader<'a> {
+    dir: &'a Path,
+    language: &'a Language,
+    globals: &'a Globals,
+    instrument: bool,
+    loaded: RefCell<HashMap<String, FrozenModule>>,
+    /// Modules whose evaluation is in progress, to report load cycles.
+    stack: RefCell<Vec<String>>,
+    load_dur: Cell<Duration>,
+}
+
+impl ScriptLoader<'_> {
+    fn load_module(&self, path: &str) -> Result<FrozenModule> {
+        let relative = Path::new(path);
+        if !relative
+            .components()
+            .all(|c| matches!(c, Component::Normal(_) | Component::CurDir))
+        {
+            bail!("load path must be relative to the scripts directory: {path}");
+        }
+        let file = self.dir.join(relative);
+        let body = std::fs::read_to_string(&file)
+            .with_context(|| format!("cannot read {}", file.display()))?;
+        let ast = AstModule::parse(path, body, &self.language.dialect)
+            .map_err(|e| anyhow!("starlark parse error: {e}"))?;
+        let module = Module::new();
+        {
+            let mut eval = Evaluator::new(&module);
+            eval.set_loader(self);
+            configure(&mut eval, self.language)?;
+            if self.instrument {
+                install(&mut eval, None);
+            }
+            eval.eval_module(ast, self.globals)
+                .map_err(|e| anyhow!("starlark eval error in {path}: {e}"))?;
+        }
+        module
+            .freeze()
+            .map_err(|e| anyhow!("starlark freeze error in {path}: {e:?}"))
+    }
+}
+
+impl FileLoader for ScriptLoader<'_> {
+    fn load(&self, path: &str) -> starlark::Result<FrozenModule> {
+        if let Some(module) = self.loaded.borrow().get(path) {
+            return Ok(module.clone());
+        }
+        if self.stack.borrow().iter().any(|p| p == path) {
+            let cycle = self.stack.borrow().join(" -> ");
+            return Err(starlark::Error::new_other(anyhow!(
+                "load cycle: {cycle} -> {path}"
+            )));
+        }
+        let outermost = self.stack.borrow().is_empty();
+        self.stack.borrow_mut().push(path.to_owned());
+        let start = Instant::now();
+        let module = self.load_module(path);
+        if outermost {
+            self.load_dur.set(self.load_dur.get() + start.elapsed());
+        }
+        self.stack.borrow_mut().pop();
+        let module = module.map_err(starlark::Error::new_other)?;
+        self.loaded
+            .borrow_mut()
+            .insert(path.to_owned(), module.clone());
+        Ok(module)
+    }
+}
+
+/// Statements between clock reads in the --timeout watchdog.
+const WATCHDOG_STRIDE: u32 = 1024;
+
+/// Runs before every statement and aborts evaluation once the deadline
+/// (if any) has passed.
+struct Watchdog {
+    deadline: Option<Instant>,
+    stmts: u32,
+}
+
+impl<'a, 'e: 'a> BeforeStmtFuncDyn<'a, 'e> for Watchdog {
+    fn call<'v>(
+        &mut self,
+        _span: FileSpanRef,
+        _eval: &mut Evaluator<'v, 'a, 'e>,
+    ) -> starlark::Result<()> {
+        self.stmts = self.stmts.wrapping_add(1);
+        if self.stmts.is_multiple_of(WATCHDOG_STRIDE)
+            && self.deadline.is_some_and(|d| Instant::now() >= d)
+        {
+            return Err(starlark::Error::new_other(TimedOut));
+        }
+        Ok(())
+    }
+}
+
+fn install(eval: &mut Evaluator, deadline: Option<Instant>) {
+    let watchdog: Box<dyn BeforeStmtFuncDyn> = Box::new(Watchdog { deadline, stmts: 0 });
+    eval.before_stmt_for_dap(watchdog.into());
+}
+
+/// Apply the evaluator settings of `language` to a new evaluator: its
+/// call-stack limit, if any, and its --eval-config options.
+fn configure(eval: &mut Evaluator, language: &Language) -> Result<()> {
+    if let Some(frames) = language.max_callstack {
+        eval.set_max_callstack_size(frames as usize)?;
+    }
+    for option in &language.eval_options {
+        match option {
+            EvalOption::StaticTypecheck => eval.enable_static_typechecking(true),
+            EvalOption::NoGc => eval.disable_gc(),
+            EvalOption::VerboseGc => eval.verbose_gc(),
+        }
+    }
+    Ok(())
+}
+
+/// Configure an evaluator for the script's language, and install the
+/// watchdog if the script has a time limit.
+fn arm(eval: &mut Evaluator, prepared: &PreparedScript) -> Result<()> {
+    configure(eval, &prepared.language)?;
+    if let Some(limit) = prepared.timeout {
+        install(eval, Some(Instant::now() + limit));
+    }
+    Ok(())
+}
+
+/// Thread CPU time used since `start`, a [`super::thread_cpu_time`].
+fn thread_cpu_since(start: Option<Duration>) -> Option<Duration> {
+    Some(super::thread_cpu_time()?.saturating_sub(start?))
+}
+
+/// The process's CPU time since `start`, a [`super::process_cpu_time`].
+fn process_cpu_since(start: Option<Duration>) -> Option<Duration> {
+    Some(super::process_cpu_time()?.saturating_sub(start?))
+}
+
+/// The thread's context switches and page faults since `start`, a
+/// [`super::thread_rusage`].
+fn rusage_since(start: Option<Rusage>) -> Option<Rusage> {
+    Some(super::thread_rusage()?.since(&start?))
+}
+
+/// Allocations the thread made since `start`, an [`alloc_count::current`].
+fn alloc_since(start: Option<alloc_count::Counts>) -> Option<alloc_count::Counts> {
+    Some(alloc_count::current()?.since(&start?))
+}
+
+/// Call `func` `calls` times back to back (at least once), returning
+/// what the last call returned.
+fn call_repeatedly<'v>(
+    eval: &mut Evaluator<'v, '_, '_>,
+    func: Value<'v>,
+    args: &[Value<'v>],
+    named: &[(&str, Value<'v>)],
+    calls: u32,
+) -> Result<Value<'v>> {
+    let mut value = eval.eval_function(func, args, named).map_err(eval_error)?;
+    for _ in 1..calls {
+        value = eval.eval_function(func, args, named).map_err(eval_error)?;
+    }
+    Ok(value)
+}
+
+fn eval_error(e: starlark::Error) -> anyhow::Error {
+    match e.kind() {
+        ErrorKind::Other(inner) if inner.is::<TimedOut>() => TimedOut.into(),
+        _ => anyhow!("starlark eval error: {e}"),
+    }
+}
+
+pub struct RunResult {
+    /// When the timed region began.
+    pub eval_start: Instant,
+    pub eval_dur: Duration,
+    /// CPU time of the evaluating thread over the same span; with
+    /// several threads, their sum.
+    pub cpu_dur: Option<Duration>,
+    /// CPU time of the whole process over the same span; None with
+    /// several threads, whose spans would each count the others.
+    pub process_cpu_dur: Option<Duration>,
+    /// Rust heap allocations over the same span, in builds with the
+    /// counting allocator; with several threads, their sum.
+    pub rust_alloc: Option<alloc_count::Counts>,
+    /// The evaluating thread's context switches and page faults over the
+    /// same span (Linux); with several threads, their sum.
+    pub rusage: Option<Rusage>,
+    pub result: i64,
+    /// Bytes allocated on the Starlark heap before and after the call.
+    pub heap_bytes_before: u64,
+    pub heap_bytes_after: u64,
+    /// With --heap-profile, what the call allocated on the Starlark heap.
+    pub heap: Option<HeapStats>,
+    /// With --starlark-gc, the collection that followed the call.
+    pub gc: Option<GcStats>,
+    /// With --input, time spent converting it to Starlark values.
+    pub marshal_dur: Option<Duration>,
+}
+
+/// Starlark heap use of one `run` call, from the heap's per-type
+/// allocation summary.
+pub struct HeapStats {
+    pub allocated_bytes: u64,
+    pub allocations: u64,
+    /// Bytes reachable from the return value; None in reuse-module mode,
+    /// where the shared module cannot be frozen.
+    pub retained_bytes: Option<u64>,
+}
+
+/// How a timed region calls `run`: the heap instrumentation around it
+/// (--heap-profile, --starlark-gc), and how many calls it makes back to
+/// back (--min-sample-ns).
+#[derive(Clone, Copy)]
+pub struct CallOptions {
+    pub profile: bool,
+    pub gc: bool,
+    pub calls: u32,
+    /// Call `run_parallel(n, seed, threads)` instead, as one of
+    /// `threads` concurrent calls (--threads on a parallel workload).
+    pub parallel: Option<u32>,
+}
+
+/// The starlark crate the bench is built against.
+pub const VERSION: &str = concat!("starlark ", env!("STARLARK_VERSION"));
+
+/// starlark-rust's collection threshold, which it does not export: a new
+/// evaluator collects once the heap holds this many bytes.
+const GC_THRESHOLD: u64 = 100_000;
+
+/// A chance for the collector to run after a call.
+pub struct GcStats {
+    pub gc_dur: Duration,
+    /// Whether a collection ran: only when the heap reached GC_THRESHOLD,
+    /// and never with the `no-gc` --eval-config option.
+    pub collected: bool,
+    /// Bytes still allocated on the heap afterwards.
+    pub bytes_after: u64,
+}
+
+/// Let the collector run on `module`'s heap, which keeps whatever the
+/// module's slots reach. starlark-rust only collects before top-level
+/// statements of a module, never inside a call, so this evaluates an
+/// empty one; without it the heap of a reused module only grows.
+fn collect(prepared: &PreparedScript, module: &Module) -> Result<GcStats> {
+    let bytes_before = module.heap().allocated_bytes() as u64;
+    let stmt = prepared.gc_stmt.clone();
+    let mut eval = Evaluator::new(module);
+    configure(&mut eval, &prepared.language)?;
+    let start = Instant::now();
+    eval.eval_module(stmt, &prepared.globals)
+        .map_err(|e| anyhow!("starlark eval error during GC: {e}"))?;
+    let gc_dur = start.elapsed();
+    Ok(GcStats {
+        gc_dur,
+        collected: bytes_before >= GC_THRESHOLD
+            && !prepared.language.eval_options.contains(&EvalOption::NoGc),
+        bytes_after: module.heap().allocated_bytes() as u64,
+    })
+}
+
+/// Bytes on the frozen heap of `module` once frozen.
+fn frozen_size(module: Module) -> Result<u64> {
+    let frozen = module
+        .freeze()
+        .map_err(|e| anyhow!("starlark freeze error: {e:?}"))?;
+    Ok(frozen
+        .frozen_heap()
+        .allocated_summary()
+        .total_allocated_bytes() as u64)
+}
+
+/// (Values, bytes) allocated on `heap` so far.
+fn heap_totals(heap: &Heap) -> (u64, u64) {
+    let summary = heap.allocated_summary();
+    let count: usize = summary.summary().values().map(|&(count, _)| count).sum();
+    (count as u64, summary.total_allocated_bytes() as u64)
+}
+
+impl HeapStats {
+    fn since(heap: &Heap, (count, bytes): (u64, u64)) -> Self {
+        let (count_now, bytes_now) = heap_totals(heap);
+        HeapStats {
+            allocated_bytes: bytes_now.saturating_sub(bytes),
+            allocations: count_now.saturating_sub(count),
+            retained_bytes: None,
+        }
+    }
+}
+
+/// Parse the script in `language` and freeze the module; its `load()`
+/// statements resolve against `load_dir`.
+/// The script **must** define a `run(n, seed)` function, which also takes
+/// any --param values as keyword arguments. With a
+/// `timeout`, every later call of `run` aborts once it exceeds the limit.
+/// With `profiled`, [`profile_run`] can be used on the result. With
+/// `typecheck`, the typechecker also runs over the parsed AST; type errors
+/// are counted and logged, but do not fail the script.
+pub fn prepare(
+    script_body: &str,
+    load_dir: &Path,
+    language: &Language,
+    timeout: Option<Duration>,
+    profiled: bool,
+    typecheck: bool,
+) -> Result<PreparedScript> {
+    let mut prepared = compile(
+        script_body,
+        load_dir,
+        language,
+        timeout,
+        timeout.is_some(),
+        typecheck,
+    )?;
+    if profiled {
+        prepared.profiled = Some(Box::new(compile(
+            script_body,
+            load_dir,
+            language,
+            timeout,
+            true,
+            false,
+        )?));
+    }
+    Ok(prepared)
+}
+
+fn compile(
+    script_body: &str,
+    load_dir: &Path,
+    language: &Language,
+    timeout: Option<Duration>,
+    instrument: bool,
+    typecheck: bool,
+) -> Result<PreparedScript> {
+    let parse_start = Instant::now();
+    let ast = AstModule::parse("bench.star", script_body.to_owned(), &language.dialect)
+        .map_err(|e| anyhow!("starlark parse error: {e}"))?;
+    let parse_dur = parse_start.elapsed();
+
+    let globals = language.globals();
+    let typechecked = typecheck.then(|| {
+        // The typechecker consumes the AST; clone it outside the timer.
+        let ast = ast.clone();
+        let typecheck_start = Instant::now();
+        let (errors, ..) = ast.typecheck(&globals, &HashMap::new());
+        let typecheck_dur = typecheck_start.elapsed();
+        for e in &errors {
+            warn!("starlark type error: {e}");
+        }
+        (typecheck_dur.as_nanos() as u64, errors.len() as u64)
+    });
+    let loader = ScriptLoader {
+        dir: load_dir,
+        language,
+        globals: &globals,
+        instrument,
+        loaded: RefCell::default(),
+        stack: RefCell::default(),
+        load_dur: Cell::default(),
+    };
+    let module = Module::new();
+    let module_eval_start = Instant::now();
+    {
+        let mut eval = Evaluator::new(&module);
+        eval.set_loader(&loader);
+        configure(&mut eval, language)?;
+        if instrument {
+            // Statement hooks only fire in code compiled while one is
+            // registered, so instrument the module's functions now.
+            install(&mut eval, None);
+        }
+        eval.eval_module(ast, &globals)
+            .map_err(|e| anyhow!("starlark eval error during prepare: {e}"))?;
+    }
+    let load_dur = loader.load_dur.get();
+    let module_eval_dur = module_eval_start.elapsed().saturating_sub(load_dur);
+    let loaded = loader.loaded.into_inner();
+    let loaded_modules = loaded.len() as u64;
+    let loads = (loaded_modules > 0).then_some((load_dur.as_nanos() as u64, loaded_modules));
+
+    let freeze_start = Instant::now();
+    let frozen = module
+        .freeze()
+        .map_err(|e| anyhow!("starlark freeze error: {e:?}"))?;
+    let freeze_dur = freeze_start.elapsed();
+    let run_fn = frozen
+        .get("run")
+        .map_err(|e| anyhow!("script must define run(n, seed): {e}"))?;
+    let run_parallel_fn = frozen.get("run_parallel").ok();
+    let baseline = Module::new();
+    baseline.import_public_symbols(&frozen);
+    baseline.set("result", Value::new_none());
+    let retained_baseline = frozen_size(baseline)?;
+
+    Ok(PreparedScript {
+        times: PrepareTimes {
+            parse_ns: parse_dur.as_nanos() as u64,
+            module_eval_ns: module_eval_dur.as_nanos() as u64,
+            freeze_ns: freeze_dur.as_nanos() as u64,
+            typecheck_ns: typechecked.map(|(ns, _)| ns),
+            typecheck_errors: typechecked.map(|(_, errors)| errors),
+            load_ns: loads.map(|(ns, _)| ns),
+            loaded_modules: loads.map(|(_, modules)| modules),
+        },
+        frozen,
+        run_fn,
+        run_parallel_fn,
+        timeout,
+        globals,
+        gc_stmt: AstModule::parse("gc.star", "None".to_owned(), &Dialect::Standard)
+            .map_err(|e| anyhow!("starlark parse error: {e}"))?,
+        source: script_body.to_owned(),
+        language: language.clone(),
+        load_dir: load_dir.to_owned(),
+        loaded,
+        retained_baseline,
+        profiled: None,
+    })
+}
+
+/// Compiles `prepared`'s script again from scratch, loads included, for
+/// --starlark-cold; the result carries the fresh phase times.
+pub fn recompile(prepared: &PreparedScript) -> Result<PreparedScript> {
+    compile(
+        &prepared.source,
+        &prepared.load_dir,
+        &prepared.language,
+        prepared.timeout,
+        prepared.timeout.is_some(),
+        false,
+    )
+}
+
+/// Whether the script defines `run_parallel(n, seed, threads)`, which
+/// --threads then calls in place of `run`.
+pub fn defines_run_parallel(prepared: &PreparedScript) -> bool {
+    prepared.run_parallel_fn.is_some()
+}
+
+/// Calls of `run` made at once from several threads (--threads).
+pub struct ConcurrentRun {
+    /// From the first call's start to the last call's end; the other
+    /// fields are the first thread's.
+    pub run: RunResult,
+    /// Each thread's eval time.
+    pub thread_eval_durs: Vec<Duration>,
+}
+
+/// Call `run(n, seed, **params)` from `threads` threads at once, each
+/// with its own Module and Evaluator over the shared frozen script, or
+/// `run_parallel(n, seed, threads, **params)` where the script defines
+/// it. The threads are released together by a barrier, and get
+/// `stack_size` bytes of native stack when given.
+#[allow(clippy::too_many_arguments)]
+pub fn call_run_concurrent(
+    prepared: &PreparedScript,
+    n: usize,
+    seed: u64,
+    params: &[Param],
+    input: Option<&serde_json::Value>,
+    threads: u32,
+    stack_size: Option<usize>,
+) -> Result<ConcurrentRun> {
+    let barrier = std::sync::Barrier::new(threads as usize);
+    let call_options = CallOptions {
+        profile: false,
+        gc: false,
+        calls: 1,
+        parallel: defines_run_parallel(prepared).then_some(threads),
+    };
+    let runs = std::thread::scope(|scope| {
+        let handles = (0..threads)
+            .map(|_| {
+                let mut builder = std::thread::Builder::new();
+                if let Some(bytes) = stack_size {
+                    builder = builder.stack_size(bytes);
+                }
+                builder
+                    .spawn_scoped(scope, || {
+                        barrier.wait();
+                        call_run(prepared, n, seed, params, input, call_options)
+                    })
+                    .context("cannot start evaluator thread")
+            })
+            .collect::<Result<Vec<_>>>()?;
+        handles
+            .into_iter()
+            .map(|h| h.join().expect("evaluator thread panicked"))
+            .collect::<Result<Vec<_>>>()
+    })?;
+
+    let start = runs
+        .iter()
+        .map(|r| r.eval_start)
+        .min()
+        .expect("threads > 0");
+    let end = runs
+        .iter()
+        .map(|r| r.eval_start + r.eval_dur)
+        .max()
+        .expect("threads > 0");
+    if let Some(r) = runs.iter().find(|r| r.result != runs[0].result) {
+        bail!(
+            "threads computed different results: {} and {}",
+            runs[0].result,
+            r.result
+        );
+    }
+    let thread_eval_durs = runs.iter().map(|r| r.eval_dur).collect();
+    let cpu_dur = runs.iter().map(|r| r.cpu_dur).sum();
+    let rust_alloc = runs
+        .iter()
+        .map(|r| r.rust_alloc)
+        .try_fold(alloc_count::Counts::default(), |sum, c| Some(sum + c?));
+    let rusage = runs
+        .iter()
+        .map(|r| r.rusage)
+        .try_fold(Rusage::default(), |sum, u| Some(sum + u?));
+    let first = runs.into_iter().next().expect("threads > 0");
+    Ok(ConcurrentRun {
+        run: RunResult {
+            eval_start: start,
+            eval_dur: end - start,
+            cpu_dur,
+            process_cpu_dur: None,
+            rust_alloc,
+            rusage,
+            ..first
+        },
+        thread_eval_durs,
+    })
+}
+
+/// Parse the script again without calling `run`, for --phase, and with
+/// `compile` also evaluate its top level into a new module, which
+/// compiles every `def` to bytecode. `load()` statements get the modules
+/// loaded by [`prepare`], so only the script itself is timed. The result
+/// is always 0; the heap bytes are those of the new module.
+pub fn reparse(prepared: &PreparedScript, compile: bool) -> Result<RunResult> {
+    let source = prepared.source.clone();
+    let modules: HashMap<&str, &FrozenModule> = prepared
+        .loaded
+        .iter()
+        .map(|(path, module)| (path.as_str(), module))
+        .collect();
+    let loader = ReturnFileLoader { modules: &modules };
+    let module = Module::new();
+    let mut eval = Evaluator::new(&module);
+    eval.set_loader(&loader);
+    configure(&mut eval, &prepared.language)?;
+
+    let cpu_start = super::thread_cpu_time();
+    let process_cpu_start = super::process_cpu_time();
+    let rusage_start = super::thread_rusage();
+    let alloc_start = alloc_count::current();
+    let eval_start = Instant::now();
+    let ast = AstModule::parse("bench.star", source, &prepared.language.dialect)
+        .map_err(|e| anyhow!("starlark parse error: {e}"))?;
+    if compile {
+        eval.eval_module(ast, &prepared.globals)
+            .map_err(|e| anyhow!("starlark eval error: {e}"))?;
+    } else {
+        std::hint::black_box(ast);
+    }
+    let eval_dur = eval_start.elapsed();
+    let cpu_dur = thread_cpu_since(cpu_start);
+    let process_cpu_dur = process_cpu_since(process_cpu_start);
+    let rust_alloc = alloc_since(alloc_start);
+    let rusage = rusage_since(rusage_start);
+
+    Ok(RunResult {
+        eval_start,
+        eval_dur,
+        cpu_dur,
+        process_cpu_dur,
+        rust_alloc,
+        rusage,
+        result: 0,
+        heap_bytes_before: 0,
+        heap_bytes_after: module.heap().allocated_bytes() as u64,
+        heap: None,
+        gc: None,
+        marshal_dur: None,
+    })
+}
+
+/// Bytes of the script --phase parses.
+pub fn source_bytes(prepared: &PreparedScript) -> u64 {
+    prepared.source.len() as u64
+}
+
+/// Reduce a workload's return value to the checksum compared across
+/// engines, by the rules `checksum()` in `_harness.py` mirrors: None is 0,
+/// bools and ints are themselves (big ints wrapped to 64 bits), floats
+/// and strings are FNV-1a hashes of their little-endian IEEE 754 and
+/// UTF-8 bytes, and lists and tuples fold their elements' checksums.
+fn extract_i64(value: Value) -> Result<i64> {
+    if value.is_none() {
+        return Ok(0);
+    }
+    if let Some(b) = value.unpack_bool() {
+        return Ok(i64::from(b));
+    }
+    if let Some(i) = value.unpack_i32() {
+        return Ok(i64::from(i));
+    }
+    if value.get_type() == "int" {
+        // Starlark big integers render as decimal strings via to_repr();
+        // wrap them to 64 bits, as Python's `& MASK` does.
+        let repr = value.to_repr();
+        let (negative, digits) = match repr.strip_prefix('-') {
+            Some(digits) => (true, digits),
+            None => (false, repr.as_str()),
+        };
+        let magnitude = digits.bytes().fold(0u64, |acc, d| {
+            acc.wrapping_mul(10).wrapping_add(u64::from(d - b'0'))
+        });
+        let wrapped = if negative {
+            magnitude.wrapping_neg()
+        } else {
+            magnitude
+        };
+        return Ok(wrapped as i64);
+    }
+    if let Some(f) = value.downcast_ref::<StarlarkFloat>() {
+        return Ok(fnv1a(&f.0.to_le_bytes()));
+    }
+    if let Some(s) = value.unpack_str() {
+        return Ok(fnv1a(s.as_bytes()));
+    }
+    let items = match (ListRef::from_value(value), TupleRef::from_value(value)) {
+        (Some(list), _) => list.content(),
+        (None, Some(tuple)) => tuple.content(),
+        (None, None) => bail!(
+            "workload returned unexpected type: got {}, repr = {:?}",
+            value.get_type(),
+            value.to_repr()
+        ),
+    };
+    items.iter().try_fold(0i64, |acc, &item| {
+        Ok(acc
+            .wrapping_mul(CHECKSUM_MULTIPLIER)
+            .wrapping_add(extract_i64(item)?))
+    })
+}
+
+/// Folds the checksums of a list's elements: `acc * this + element`.
+const CHECKSUM_MULTIPLIER: i64 = 1_000_003;
+
+/// 64-bit FNV-1a of `bytes`, as the hash of float and string results.
+fn fnv1a(bytes: &[u8]) -> i64 {
+    let hash = bytes.iter().fold(0xcbf2_9ce4_8422_2325u64, |h, &b| {
+        (h ^ u64::from(b)).wrapping_mul(0x0000_0100_0000_01b3)
+    });
+    hash as i64
+}
+
+/// --param values as keyword arguments.
+fn alloc_params<'v, 'p>(heap: &'v Heap, params: &'p [Param]) -> Vec<(&'p str, Value<'v>)> {
+    params
+        .iter()
+        .map(|p| {
+            let value = match &p.value {
+                serde_json::Value::Bool(b) => Value::new_bool(*b),
+                serde_json::Value::Number(v) => match v.as_i64() {
+                    Some(i) => heap.alloc(i),
+                    None => heap.alloc(v.as_f64().unwrap_or(f64::NAN)),
+                },
+                serde_json::Value::String(s) => heap.alloc(s.as_str()),
+                other => heap.alloc(other.to_string()),
+            };
+            (p.name.as_str(), value)
+        })
+        .collect()
+}
+
+/// Convert --input data to a Starlark value on `heap`, appending it to
+/// the keyword arguments as `data`; returns how long that took.
+fn alloc_input<'v>(
+    heap: &'v Heap,
+    input: Option<&serde_json::Value>,
+    named: &mut Vec<(&str, Value<'v>)>,
+) -> Option<Duration> {
+    let input = input?;
+    let start = Instant::now();
+    let data = heap.alloc(input);
+    let marshal_dur = start.elapsed();
+    named.push(("data", data));
+    Some(marshal_dur)
+}
+
+/// Call the frozen `run(n, seed, **params)` function once, or
+/// `call_options.calls` times in one timed region, measuring only eval
+/// time. With `input`, it is converted before the call and passed as
+/// `data`. With `call_options.profile`, the heap is summarized before
+/// and after the call, and with `call_options.gc` collected after it,
+/// both outside the timed region.
+pub fn call_run(
+    prepared: &PreparedScript,
+    n: usize,
+    seed: u64,
+    params: &[Param],
+    input: Option<&serde_json::Value>,
+    call_options: CallOptions,
+) -> Result<RunResult> {
+    let module = Module::new();
+    // Import the frozen module so the evaluator can see the function's closure.
+    module.import_public_symbols(&prepared.frozen);
+    let mut eval = Evaluator::new(&module);
+    arm(&mut eval, prepared)?;
+
+    let heap = module.heap();
+    let n_val = heap.alloc(n as i64);
+    let seed_val = heap.alloc(seed as i64);
+    let mut named = alloc_params(heap, params);
+    let marshal_dur = alloc_input(heap, input, &mut named);
+    let (func, args) = match (call_options.parallel, &prepared.run_parallel_fn) {
+        (Some(threads), Some(f)) => (f.value(), vec![n_val, seed_val, heap.alloc(threads)]),
+        _ => (prepared.run_fn.value(), vec![n_val, seed_val]),
+    };
+    let before = call_options.profile.then(|| heap_totals(heap));
+    let heap_bytes_before = heap.allocated_bytes() as u64;
+
+    let cpu_start = super::thread_cpu_time();
+    let process_cpu_start = super::process_cpu_time();
+    let rusage_start = super::thread_rusage();
+    let alloc_start = alloc_count::current();
+    let eval_start = Instant::now();
+    let value = call_repeatedly(&mut eval, func, &args, &named, call_options.calls)?;
+    let eval_dur = eval_start.elapsed();
+    let cpu_dur = thread_cpu_since(cpu_start);
+    let process_cpu_dur = process_cpu_since(process_cpu_start);
+    let rust_alloc = alloc_since(alloc_start);
+    let rusage = rusage_since(rusage_start);
+
+    let result = extract_i64(value)?;
+    std::hint::black_box(result);
+    let heap_bytes_after = heap.allocated_bytes() as u64;
+
+    let mut heap_stats = before.map(|before| HeapStats::since(heap, before));
+    drop(eval);
+    // The collector and freezing both trace from the module's slots, so
+    // with only the return value stored, what survives is what the call
+    // retained.
+    module.set("result", value);
+    let gc = match call_options.gc {
+        true => Some(collect(prepared, &module)?),
+        false => None,
+    };
+    if let Some(stats) = &mut heap_stats {
+        let retained = frozen_size(module)?;
+        stats.retained_bytes = Some(retained.saturating_sub(prepared.retained_baseline));
+    }
+
+    Ok(RunResult {
+        eval_start,
+        eval_dur,
+        cpu_dur,
+        process_cpu_dur,
+        rust_alloc,
+        rusage,
+        result,
+        heap_bytes_before,
+        heap_bytes_after,
+        heap: heap_stats,
+        gc,
+        marshal_dur,
+    })
+}
+
+/// Call `run(n, seed, **params)` reusing an existing Module
+/// (hot-interpreter mode). The Module retains heap state from previous
+/// calls, unless `call_options.gc` collects it.
+pub fn call_run_reuse(
+    prepared: &PreparedScript,
+    module: &Module,
+    n: usize,
+    seed: u64,
+    params: &[Param],
+    input: Option<&serde_json::Value>,
+    call_options: CallOptions,
+) -> Result<RunResult> {
+    let mut eval = Evaluator::new(module);
+    arm(&mut eval, prepared)?;
+
+    let heap = module.heap();
+    let n_val = heap.alloc(n as i64);
+    let seed_val = heap.alloc(seed as i64);
+    let mut named = alloc_params(heap, params);
+    let marshal_dur = alloc_input(heap, input, &mut named);
+    let func: Value = prepared.run_fn.value();
+    let before = call_options.profile.then(|| heap_totals(heap));
+    let heap_bytes_before = heap.allocated_bytes() as u64;
+
+    let cpu_start = super::thread_cpu_time();
+    let process_cpu_start = super::process_cpu_time();
+    let rusage_start = super::thread_rusage();
+    let alloc_start = alloc_count::current();
+    let eval_start = Instant::now();
+    let value = call_repeatedly(
+        &mut eval,
+        func,
+        &[n_val, seed_val],
+        &named,
+        call_options.calls,
+    )?;
+    let eval_dur = eval_start.elapsed();
+    let cpu_dur = thread_cpu_since(cpu_start);
+    let process_cpu_dur = process_cpu_since(process_cpu_start);
+    let rust_alloc = alloc_since(alloc_start);
+    let rusage = rusage_since(rusage_start);
+
+    let result = extract_i64(value)?;
+    std::hint::black_box(result);
+    let heap_bytes_after = heap.allocated_bytes() as u64;
+    let heap_stats = before.map(|before| HeapStats::since(heap, before));
+    drop(eval);
+    let gc = match call_options.gc {
+        true => Some(collect(prepared, module)?),
+        false => None,
+    };
+
+    Ok(RunResult {
+        eval_start,
+        eval_dur,
+        cpu_dur,
+        process_cpu_dur,
+        rust_alloc,
+        rusage,
+        result,
+        heap_bytes_before,
+        heap_bytes_after,
+        heap: heap_stats,
+        gc,
+        marshal_dur,
+    })
+}
+
+/// Call `run(n, seed, **params)` once on a fresh module with the starlark
+/// profiler for `kind` enabled, and write its output to `path`. The
+/// script must have been prepared with `profiled`, except for
+/// [`ProfileKind::Native`], which samples uninstrumented calls of the
+/// measured copy for a second instead.
+pub fn profile_run(
+    prepared: &PreparedScript,
+    n: usize,
+    seed: u64,
+    params: &[Param],
+    input: Option<&serde_json::Value>,
+    kind: ProfileKind,
+    path: &Path,
+) -> Result<()> {
+    if kind == ProfileKind::Native {
+        let call_options = CallOptions {
+            profile: false,
+            gc: false,
+            calls: 1,
+            parallel: None,
+        };
+        return super::native_profile::record(path, || {
+            call_run(prepared, n, seed, params, input, call_options).map(drop)
+        });
+    }
+    let prepared = prepared
+        .profiled
+        .as_deref()
+        .ok_or_else(|| anyhow!("script was not prepared for profiling"))?;
+    let mode = match kind {
+        ProfileKind::StarlarkTime | ProfileKind::StarlarkCoverage => ProfileMode::Statement,
+        ProfileKind::StarlarkFlame => ProfileMode::TimeFlame,
+        ProfileKind::Native => unreachable!("sampled above"),
+    };
+    let profile = profiled_call(prepared, n, seed, params, input, &mode)?;
+    if kind == ProfileKind::StarlarkCoverage {
+        let csv = profile
+            .r#gen()
+            .map_err(|e| anyhow!("cannot render statement profile: {e}"))?;
+        let report = coverage_report(prepared, &csv)?;
+        return std::fs::write(path, report)
+            .with_context(|| format!("cannot write profile {}", path.display()));
+    }
+    profile
+        .write(path)
+        .map_err(|e| anyhow!("cannot write profile {}: {e}", path.display()))
+}
+
+/// The script with gcov-style execution counts, `count:line:source`,
+/// from the CSV of a statement profile of `run`. A line's count is the
+/// most any statement starting on it ran, `#####` if one never ran, and
+/// `-` for lines without statements. Top-level statements run while the
+/// script is prepared, not in the profiled call, so they count as `-`.
+fn coverage_report(prepared: &PreparedScript, csv: &str) -> Result<String> {
+    use std::fmt::Write;
+
+    let ast = AstModule::parse(
+        "bench.star",
+        prepared.source.clone(),
+        &prepared.language.dialect,
+    )
+    .map_err(|e| anyhow!("starlark parse error: {e}"))?;
+    // 0-based line -> count, None while no statement on it has run.
+    let mut counts: HashMap<usize, Option<u64>> = HashMap::new();
+    for location in ast.stmt_locations() {
+        let begin = location.resolve_span().begin;
+        if begin.column > 0 {
+            counts.insert(begin.line, None);
+        }
+    }
+    // File,Span,Duration(s),Count with spans like "12:9-50".
+    for row in csv.lines().skip(1) {
+        let fields: Vec<&str> = row.split(',').collect();
+        let [file, span, _, count] = fields[..] else {
+            bail!("unexpected statement profile row: {row}");
+        };
+        if file != "\"bench.star\"" {
+            continue;
+        }
+        let line = span
+            .trim_matches('"')
+            .split(':')
+            .next()
+            .and_then(|line| line.parse::<usize>().ok())
+            .with_context(|| format!("unexpected statement profile span: {span}"))?;
+        let count: u64 = count
+            .parse()
+            .with_context(|| format!("unexpected statement profile count: {count}"))?;
+        if let Some(slot) = counts.get_mut(&(line - 1)) {
+            *slot = Some(slot.map_or(count, |c| c.max(count)));
+        }
+    }
+
+    let mut report = String::new();
+    for (i, text) in prepared.source.lines().enumerate() {
+        let count = match counts.get(&i) {
+            None => "-".to_string(),
+            Some(None) => "#####".to_string(),
+            Some(Some(count)) => count.to_string(),
+        };
+        writeln!(report, "{count:>9}:{:>5}:{text}", i + 1).expect("writing to a String");
+    }
+    Ok(report)
+}
+
+/// Count the bytecode instructions one call of `run(n, seed, **params)`
+/// executes, using the evaluator's bytecode profile. Instrumented
+/// dispatch is slower, so this is a call of its own.
+pub fn count_instructions(
+    prepared: &PreparedScript,
+    n: usize,
+    seed: u64,
+    params: &[Param],
+    input: Option<&serde_json::Value>,
+) -> Result<u64> {
+    let csv = profiled_call(prepared, n, seed, params, input, &ProfileMode::Bytecode)?
+        .r#gen()
+        .map_err(|e| anyhow!("cannot render bytecode profile: {e}"))?;
+    // Opcode,Count,Count / Total, then "TOTAL",<count>,"1.000".
+    csv.lines()
+        .find(|line| line.starts_with("\"TOTAL\""))
+        .and_then(|line| line.split(',').nth(1))
+        .and_then(|count| count.parse().ok())
+        .ok_or_else(|| anyhow!("bytecode profile has no TOTAL row"))
+}
+
+fn profiled_call(
+    prepared: &PreparedScript,
+    n: usize,
+    seed: u64,
+    params: &[Param],
+    input: Option<&serde_json::Value>,
+    mode: &ProfileMode,
+) -> Result<ProfileData> {
+    let module = Module::new();
+    module.import_public_symbols(&prepared.frozen);
+    let mut eval = Evaluator::new(&module);
+    eval.enable_profile(mode)?;
+    arm(&mut eval, prepared)?;
+
+    let heap = module.heap();
+    let n_val = heap.alloc(n as i64);
+    let seed_val = heap.alloc(seed as i64);
+    let mut named = alloc_params(heap, params);
+    alloc_input(heap, input, &mut named);
+    eval.eval_function(prepared.run_fn.value(), &[n_val, seed_val], &named)
+        .map_err(eval_error)?;
+    eval.gen_profile()
+        .map_err(|e| anyhow!("cannot collect profile: {e}"))
+}
+
+/// Access the frozen module (for creating shared Modules in reuse mode).
+pub fn frozen(prepared: &PreparedScript) -> &FrozenModule {
+    &prepared.frozen
+}
+
+/// What a frozen module keeps resident: its heap's bytes and values, in
+/// total and per value type, and the bytes of the modules it loaded.
+pub struct FrozenHeap {
+    pub bytes: u64,
+    pub values: u64,
+    /// Type name to (values, bytes).
+    pub types: BTreeMap<String, (u64, u64)>,
+    /// None when the script loads nothing.
+    pub loaded_bytes: Option<u64>,
+}
+
+pub fn frozen_heap(prepared: &PreparedScript) -> FrozenHeap {
+    let summary = prepared.frozen.frozen_heap().allocated_summary();
+    let types: BTreeMap<_, _> = summary
+        .summary()
+        .into_iter()
+        .map(|(name, (count, bytes))| (name, (count as u64, bytes as u64)))
+        .collect();
+    let loaded_bytes = (!prepared.loaded.is_empty()).then(|| {
+        prepared
+            .loaded
+            .values()
+            .map(|m| m.frozen_heap().allocated_summary().total_allocated_bytes() as u64)
+            .sum()
+    });
+    FrozenHeap {
+        bytes: summary.total_allocated_bytes() as u64,
+        values: types.values().map(|&(count, _)| count).sum(),
+        types,
+        loaded_bytes,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::ErrorKind;
+    use std::process::Command;
+
+    use super::*;
+
+    /// Expressions that read the same in Starlark and Python.
+    const VALUES: &[&str] = &[
+        "None",
+        "True",
+        "False",
+        "0",
+        "-7",
+        "2147483648",
+        "-2147483649",
+        "9223372036854775807",
+        "-9223372036854775808",
+        "18446744073709551621",
+        "-1267650600228229401496703205376",
+        "0.5",
+        "-0.0",
+        "1e300",
+        "''",
+        "'abc'",
+        "'héllo'",
+        "[]",
+        "[1, 2, 3]",
+        "(1, [2, (3, 'x')], 4.5)",
+        "[[True, None], -7, [False]]",
+        "[9223372036854775807] * 3",
+        "[-1] * 40",
+        "list(range(60))",
+        "[18446744073709551615, [1267650600228229401496703205376, -3], 'z']",
+    ];
+
+    /// `extract_i64` and the Python harness's `checksum()` agree.
+    #[test]
+    fn checksum_matches_python() {
+        let harness = concat!(env!("CARGO_MANIFEST_DIR"), "/scripts/python");
+        let output = match Command::new("python3")
+            .arg("-c")
+            .arg(
+                "import sys, _harness\n\
+                 for expr in sys.argv[1:]:\n    print(_harness.checksum(eval(expr)))",
+            )
+            .args(VALUES)
+            .current_dir(harness)
+            .output()
+        {
+            Err(e) if e.kind() == ErrorKind::NotFound => {
+                eprintln!("python3 not found; skipping");
+                return;
+            }
+            output => output.unwrap(),
+        };
+        assert!(
+            output.status.success(),
+            "{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        let python: Vec<i64> = String::from_utf8(output.stdout)
+            .unwrap()
+            .lines()
+            .map(|line| line.parse().unwrap())
+            .collect();
+        assert_eq!(python.len(), VALUES.len());
+
+        for (expr, python) in VALUES.iter().zip(python) {
+            let ast = AstModule::parse("value.star", expr.to_string(), &Dialect::Standard).unwrap();
+            let module = Module::new();
+            let mut eval = Evaluator::new(&module);
+            let value = eval.eval_module(ast, &Globals::standard()).unwrap();
+            assert_eq!(extract_i64(value).unwrap(), python, "{expr}");
+        }
+    }
+}
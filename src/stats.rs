@@ -0,0 +1,166 @@
+//! Statistics helpers.
+
+pub fn mean(xs: &[u64]) -> f64 {
+    if xs.is_empty() {
+        return 0.0;
+    }
+    xs.iter().map(|&x| x as f64).sum::<f64>() / xs.len() as f64
+}
+
+pub fn median(xs: &[u64]) -> f64 {
+    if xs.is_empty() {
+        return 0.0;
+    }
+    let mut s = xs.to_vec();
+    s.sort_unstable();
+    let mid = s.len() / 2;
+    if s.len() % 2 == 1 {
+        s[mid] as f64
+    } else {
+        (s[mid - 1] as f64 + s[mid] as f64) / 2.0
+    }
+}
+
+/// Sample standard deviation (Bessel-corrected); 0 for fewer than two samples.
+pub fn stddev(xs: &[u64]) -> f64 {
+    if xs.len() < 2 {
+        return 0.0;
+    }
+    let m = mean(xs);
+    let var = xs.iter().map(|&x| (x as f64 - m).powi(2)).sum::<f64>() / (xs.len() - 1) as f64;
+    var.sqrt()
+}
+
+/// Throughput: `count` items in `ns` nanoseconds, per second.
+pub fn per_sec(count: usize, ns: f64) -> f64 {
+    if ns <= 0.0 {
+        return 0.0;
+    }
+    count as f64 * 1e9 / ns
+}
+
+/// Least-squares fit of sample value against sample index.
+pub struct Trend {
+    /// Change per iteration, in the unit of the samples.
+    pub slope: f64,
+    /// t-statistic of the slope (slope / standard error).
+    pub t: f64,
+}
+
+/// Fit a linear trend over the series; None for fewer than three samples.
+pub fn linear_trend(xs: &[u64]) -> Option<Trend> {
+    if xs.len() < 3 {
+        return None;
+    }
+    let n = xs.len() as f64;
+    let mean_x = (n - 1.0) / 2.0;
+    let mean_y = mean(xs);
+    let mut sxx = 0.0;
+    let mut sxy = 0.0;
+    for (i, &y) in xs.iter().enumerate() {
+        let dx = i as f64 - mean_x;
+        sxx += dx * dx;
+        sxy += dx * (y as f64 - mean_y);
+    }
+    let slope = sxy / sxx;
+    let ssr: f64 = xs
+        .iter()
+        .enumerate()
+        .map(|(i, &y)| {
+            let fit = mean_y + slope * (i as f64 - mean_x);
+            (y as f64 - fit).powi(2)
+        })
+        .sum();
+    let se = (ssr / (n - 2.0) / sxx).sqrt();
+    let t = if se > 0.0 {
+        slope / se
+    } else if slope == 0.0 {
+        0.0
+    } else {
+        slope.signum() * f64::INFINITY
+    };
+    Some(Trend { slope, t })
+}
+
+/// Coefficient of variation in percent.
+pub fn cv_pct(xs: &[u64]) -> f64 {
+    let m = mean(xs);
+    if m == 0.0 {
+        return 0.0;
+    }
+    stddev(xs) / m * 100.0
+}
+
+/// Distribution-free ~95% confidence interval of the median, using the
+/// order-statistic ranks from the normal approximation to the binomial.
+/// For small samples this degenerates to the full [min, max] range.
+pub fn median_ci(xs: &[u64]) -> Option<(f64, f64)> {
+    if xs.is_empty() {
+        return None;
+    }
+    let mut s = xs.to_vec();
+    s.sort_unstable();
+    let n = s.len() as f64;
+    let half = 1.96 * n.sqrt() / 2.0;
+    // 1-based ranks, clamped to the sample.
+    let lo = ((n / 2.0 - half).floor() as usize).max(1);
+    let hi = ((1.0 + n / 2.0 + half).ceil() as usize).min(s.len());
+    Some((s[lo - 1] as f64, s[hi - 1] as f64))
+}
+
+/// Half-width of [`median_ci`] as a percentage of the median.
+pub fn ci_pct(xs: &[u64]) -> Option<f64> {
+    let (lo, hi) = median_ci(xs)?;
+    let m = median(xs);
+    if m == 0.0 {
+        return None;
+    }
+    Some((hi - lo) / 2.0 / m * 100.0)
+}
+
+/// Index of the first steady-state sample: the point right after the
+/// first pair of consecutive `window`-sized windows whose medians differ
+/// by at most `tol_pct` percent. None if the series never settles.
+pub fn steady_state(xs: &[u64], window: usize, tol_pct: f64) -> Option<usize> {
+    (2 * window..=xs.len()).find(|&end| {
+        let prev = median(&xs[end - 2 * window..end - window]);
+        let cur = median(&xs[end - window..end]);
+        prev > 0.0 && ((cur - prev) / prev).abs() * 100.0 <= tol_pct
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn median_ci_ranks() {
+        assert_eq!(median_ci(&[]), None);
+        let xs: Vec<u64> = (1..=100).collect();
+        assert_eq!(median_ci(&xs), Some((40.0, 61.0)));
+        // Small samples span the whole range.
+        assert_eq!(median_ci(&[5, 1, 3]), Some((1.0, 5.0)));
+    }
+
+    #[test]
+    fn steady_state_after_settling() {
+        assert_eq!(steady_state(&[100, 90, 10, 10, 10, 10], 2, 5.0), Some(6));
+        assert_eq!(steady_state(&[10, 10, 50], 1, 5.0), Some(2));
+        assert_eq!(steady_state(&[1, 2, 4, 8, 16, 32], 1, 10.0), None);
+        assert_eq!(steady_state(&[10, 10, 10], 2, 5.0), None);
+    }
+
+    #[test]
+    fn linear_trend_slope_and_t() {
+        assert!(linear_trend(&[1, 2]).is_none());
+        let flat = linear_trend(&[5, 5, 5]).unwrap();
+        assert_eq!((flat.slope, flat.t), (0.0, 0.0));
+        let exact = linear_trend(&[10, 20, 30, 40]).unwrap();
+        assert_eq!(exact.slope, 10.0);
+        assert_eq!(exact.t, f64::INFINITY);
+        let falling = linear_trend(&[40, 30, 20, 10]).unwrap();
+        assert_eq!(falling.t, f64::NEG_INFINITY);
+        let noisy = linear_trend(&[10, 12, 11, 13, 12, 14]).unwrap();
+        assert!(noisy.slope > 0.0 && noisy.t.is_finite() && noisy.t > 2.0);
+    }
+}
@@ -0,0 +1,200 @@
+//! Runs driven through the library API, on small N so that they stay quick.
+
+use std::ops::ControlFlow;
+use std::sync::{Arc, Mutex};
+
+use starlark_bench::{
+    Call, CustomWorkload, Engine, EngineConfig, EngineMetadata, Iteration, Runner, Script,
+    StarlarkEngine, Workload, WorkloadSpec,
+};
+
+/// `arithmetic` on Starlark at two N, one warmup and two iterations each.
+fn starlark_runner() -> Runner {
+    Runner::new()
+        .engine(EngineConfig::Starlark)
+        .workload(WorkloadSpec::builtin("arithmetic").unwrap())
+        .n(200)
+        .n(400)
+        .iters(2)
+        .warmup(1)
+        .args(["--no-canary"])
+}
+
+const DOUBLE: &str = "def run(n, seed):\n    return 2 * n + seed\n";
+
+#[test]
+fn starlark_run() {
+    let records = starlark_runner().run().unwrap();
+    assert_eq!(records.len(), 6);
+    assert!(records.iter().all(|r| r.engine() == "starlark"));
+    assert!(records.iter().all(|r| r.workload() == "arithmetic"));
+    assert_eq!(records.iter().filter(|r| r.warmup()).count(), 2);
+    for n in [200, 400] {
+        let results: Vec<i64> = records
+            .iter()
+            .filter(|r| r.n() == n)
+            .map(|r| r.result())
+            .collect();
+        assert_eq!(results.len(), 3);
+        assert!(results.iter().all(|&r| r == results[0]));
+    }
+}
+
+#[test]
+fn registered_workload_matches_expected() {
+    let records = Runner::new()
+        .engine(EngineConfig::Starlark)
+        .register_workload(
+            "double",
+            CustomWorkload::new()
+                .starlark(Script::Inline(DOUBLE.into()))
+                .expected(|n, seed| 2 * n as i64 + seed as i64),
+        )
+        .n(10)
+        .seed(3)
+        .iters(2)
+        .warmup(0)
+        .args(["--no-canary"])
+        .run()
+        .unwrap();
+    assert_eq!(records.len(), 2);
+    assert!(records.iter().all(|r| r.workload() == "double"));
+    assert!(records.iter().all(|r| r.result() == 23));
+}
+
+#[test]
+fn registered_workload_mismatching_expected_fails() {
+    let err = Runner::new()
+        .engine(EngineConfig::Starlark)
+        .register_workload(
+            "double_wrong",
+            CustomWorkload::new()
+                .starlark(Script::Inline(DOUBLE.into()))
+                .expected(|_, _| -1),
+        )
+        .n(10)
+        .iters(1)
+        .warmup(0)
+        .args(["--no-canary"])
+        .run()
+        .err()
+        .expect("checksum mismatch");
+    assert!(
+        err.to_string().contains("unexpected results"),
+        "unexpected error: {err:#}"
+    );
+}
+
+/// The built-in evaluator as a custom engine of another name.
+struct Plain(StarlarkEngine);
+
+impl Engine for Plain {
+    fn metadata(&self) -> EngineMetadata {
+        EngineMetadata {
+            name: "plain".into(),
+            ..self.0.metadata()
+        }
+    }
+
+    fn prepare(&mut self, workload: &Workload) -> anyhow::Result<()> {
+        self.0.prepare(workload)
+    }
+
+    fn run_iter(&mut self, call: &Call) -> anyhow::Result<Iteration> {
+        self.0.run_iter(call)
+    }
+}
+
+#[test]
+fn custom_engine_runs_next_to_starlark() {
+    let records = starlark_runner()
+        .custom_engine(Plain(StarlarkEngine::new()))
+        .args(["--min-sample-ns", "100000"])
+        .run()
+        .unwrap();
+    assert_eq!(records.len(), 12);
+    assert_eq!(records.iter().filter(|r| r.engine() == "plain").count(), 6);
+    for n in [200, 400] {
+        let results: Vec<i64> = records
+            .iter()
+            .filter(|r| r.n() == n)
+            .map(|r| r.result())
+            .collect();
+        assert!(results.iter().all(|&r| r == results[0]));
+    }
+}
+
+#[test]
+fn setters_conflicting_with_args_fail() {
+    for (runner, message) in [
+        (
+            starlark_runner().args(["--n-sweep", "10:30:10"]),
+            "--n-sweep cannot be used with --n (from Runner::n)",
+        ),
+        (
+            starlark_runner().args(["--target-ci", "5"]),
+            "--target-ci cannot be used with --iters (from Runner::iters)",
+        ),
+        (
+            starlark_runner().args(["--auto-warmup"]),
+            "--auto-warmup cannot be used with --warmup (from Runner::warmup)",
+        ),
+    ] {
+        let err = runner.run().err().expect("a conflict");
+        assert_eq!(err.to_string(), message);
+    }
+}
+
+#[test]
+fn records_keep_their_stamps() {
+    let first = starlark_runner().run().unwrap();
+    std::thread::sleep(std::time::Duration::from_millis(500));
+    let second = starlark_runner().run().unwrap();
+    for records in [&first, &second] {
+        let elapsed: Vec<u64> = records
+            .iter()
+            .map(|r| r.elapsed_ms_since_start().unwrap())
+            .collect();
+        assert!(elapsed.is_sorted(), "{elapsed:?}");
+        assert!(records.iter().all(|r| r.ts_unix_ms().is_some()));
+    }
+    // Each run counts from its own start.
+    assert!(second[0].elapsed_ms_since_start().unwrap() < 500);
+    assert!(second[0].ts_unix_ms() >= first[5].ts_unix_ms());
+}
+
+#[test]
+fn on_record_break_stops_the_run() {
+    let seen = Arc::new(Mutex::new(0));
+    let count = Arc::clone(&seen);
+    let records = starlark_runner()
+        .on_record(move |_| {
+            *count.lock().unwrap() += 1;
+            ControlFlow::Break(())
+        })
+        .run()
+        .unwrap();
+    assert_eq!(*seen.lock().unwrap(), 1);
+    assert!(records.is_empty());
+}
+
+#[test]
+fn stream_yields_every_record() {
+    let records: Vec<_> = starlark_runner()
+        .stream()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(records.len(), 6);
+    let first = records.iter().position(|r| r.n() == 400).unwrap();
+    assert!(records[..first].iter().all(|r| r.n() == 200));
+}
+
+#[test]
+fn stream_ends_with_the_error() {
+    let items: Vec<_> = starlark_runner()
+        .args(["--no-such-flag"])
+        .stream()
+        .collect();
+    assert_eq!(items.len(), 1);
+    assert!(items[0].is_err());
+}